@@ -0,0 +1,29 @@
+#version 450
+#extension GL_EXT_buffer_reference : require
+
+layout (location = 0) out vec4 outColor;
+
+// debug_draw::DebugVertex
+struct DebugVertex {
+	vec3 position;
+	uint color; // RGBA8
+};
+
+layout(buffer_reference, std430) readonly buffer DebugVertexBuffer{
+	DebugVertex vertices[];
+};
+
+// renderer::debug_renderer::DebugConstants
+layout( push_constant ) uniform constants
+{
+	mat4 view_projection;
+	DebugVertexBuffer vertexBuffer;
+} PushConstants;
+
+void main()
+{
+	DebugVertex v = PushConstants.vertexBuffer.vertices[gl_VertexIndex];
+
+	gl_Position = PushConstants.view_projection * vec4(v.position, 1.0f);
+	outColor = unpackUnorm4x8(v.color);
+}
@@ -2,8 +2,35 @@
 
 layout(location = 0) in vec3 fragColor;
 layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec3 fragWorldPosition;
+layout(location = 3) in vec3 fragBarycentric;
+layout(location = 4) in vec2 fragUV;
+layout(location = 5) flat in uint fragTriangleId;
+layout(location = 6) flat in uint fragSubmeshId;
+layout(location = 7) flat in uint fragSmoothingGroup;
 
-layout(location = 0) out vec3 outColor;
+layout(location = 0) out vec4 outColor;
+
+// material::DisplayMode variant, LINE/POINT polygon modes use DISPLAY_SHADED
+const uint DISPLAY_SHADED = 0;
+const uint DISPLAY_WIREFRAME = 1;
+const uint DISPLAY_POINTS = 2;
+const uint DISPLAY_SHADED_WIREFRAME = 3;
+layout(constant_id = 0) const uint DISPLAY_MODE = DISPLAY_SHADED;
+
+// material::DebugShading variant, replaces the lit color
+const uint DEBUG_NONE = 0;
+const uint DEBUG_NORMALS = 1;
+const uint DEBUG_UV_CHECKER = 2;
+const uint DEBUG_LINEAR_DEPTH = 3;
+const uint DEBUG_TRIANGLE_ID = 4;
+const uint DEBUG_SUBMESH_ID = 5;
+const uint DEBUG_SMOOTHING_GROUP_ID = 6;
+layout(constant_id = 1) const uint DEBUG_SHADING = DEBUG_NONE;
+
+const float UV_CHECKER_SIZE = 8.0f; // squares per uv unit
+
+const vec3 WIRE_COLOR = vec3(1.0f);
 
 
 layout(set = 0, binding = 0) buffer MaterialParams {
@@ -17,20 +44,131 @@ layout(set = 0, binding = 0) buffer MaterialParams {
     int illumination;
 } materials_params;
 
+// Light.kind, see light::light_params
+const uint LIGHT_DIRECTIONAL = 0;
+const uint LIGHT_POINT = 1;
+const uint LIGHT_SPOT = 2;
+
+struct Light {
+	vec3 position;
+	uint kind;
+	vec3 direction;
+	float range;
+	vec3 color;
+	float intensity;
+	float inner_cutoff;
+	float outer_cutoff;
+};
+
+layout(set = 1, binding = 0) readonly buffer SceneLights {
+	mat4 view_projection;
+	vec3 camera_position;
+	uint count;
+	float camera_near;
+	float camera_far;
+	vec2 _pad;
+	Light lights[];
+} scene;
+
+
+// diffuse contribution of a single light
+vec3 light_contribution(Light light, vec3 normal) {
+	vec3 lightDir; // frag space frag to light
+	float attenuation = 1.0f;
+
+	if (light.kind == LIGHT_DIRECTIONAL) {
+		lightDir = -light.direction;
+	} else {
+		vec3 toLight = light.position - fragWorldPosition;
+		float distance = length(toLight);
+		lightDir = toLight / max(distance, 0.0001f);
+		attenuation = clamp(1.0f - distance / light.range, 0.0f, 1.0f);
+
+		if (light.kind == LIGHT_SPOT) {
+			float theta = dot(lightDir, -light.direction);
+			float epsilon = max(light.inner_cutoff - light.outer_cutoff, 0.0001f);
+			attenuation *= clamp((theta - light.outer_cutoff) / epsilon, 0.0f, 1.0f);
+		}
+	}
+
+	float diffuseStrength = max(0.0, dot(lightDir, normal)); // dot
+	return diffuseStrength * light.color * light.intensity * attenuation; // diffuse color
+}
+
+// 0 on a triangle edge, 1 inside (about one pixel wide)
+float edge_factor() {
+	vec3 width = fwidth(fragBarycentric);
+	vec3 edge = smoothstep(vec3(0.0f), width * 1.5f, fragBarycentric);
+	return min(min(edge.x, edge.y), edge.z);
+}
+
+// 1 on a triangle corner, 0 elsewhere (a few pixels wide)
+float corner_factor() {
+	vec3 width = fwidth(fragBarycentric);
+	vec3 corner = smoothstep(vec3(1.0f) - width * 3.0f, vec3(1.0f), fragBarycentric);
+	return max(max(corner.x, corner.y), corner.z);
+}
+
+// stable distinct color for an id
+vec3 id_color(uint id) {
+	uint h = id * 2654435761u;
+	h ^= h >> 15;
+	h *= 2246822519u;
+	h ^= h >> 13;
+	return vec3((h >> 16) & 0xffu, (h >> 8) & 0xffu, h & 0xffu) / 255.0f;
+}
+
+vec3 debug_color() {
+	if (DEBUG_SHADING == DEBUG_NORMALS) {
+		return normalize(fragNormal) * 0.5f + 0.5f;
+	}
+	if (DEBUG_SHADING == DEBUG_UV_CHECKER) {
+		ivec2 cell = ivec2(floor(fragUV * UV_CHECKER_SIZE));
+		float checker = float((cell.x + cell.y) & 1);
+		return mix(vec3(0.2f), vec3(0.8f), checker) * vec3(fract(fragUV), 1.0f);
+	}
+	if (DEBUG_SHADING == DEBUG_LINEAR_DEPTH) {
+		float distance = length(fragWorldPosition - scene.camera_position);
+		float depth = (distance - scene.camera_near) / (scene.camera_far - scene.camera_near);
+		return vec3(clamp(depth, 0.0f, 1.0f));
+	}
+	if (DEBUG_SHADING == DEBUG_TRIANGLE_ID) {
+		return id_color(fragTriangleId);
+	}
+	if (DEBUG_SHADING == DEBUG_SUBMESH_ID) {
+		return id_color(fragSubmeshId);
+	}
+	// smoothing off is group 0
+	return fragSmoothingGroup == 0 ? vec3(0.0f) : id_color(fragSmoothingGroup);
+}
 
 void main() {
-    // Lighting
-	vec3 lightColor = vec3(1.0f,1.0f,1.0f); // white
-	vec3 lightDir = vec3(0.0f, -1.0f, -1.0f); // world space (aka "light to frag")
+	if (DISPLAY_MODE == DISPLAY_WIREFRAME) {
+		if (edge_factor() > 0.5f) discard;
+		outColor = vec4(WIRE_COLOR, 1.0f);
+		return;
+	}
+	if (DISPLAY_MODE == DISPLAY_POINTS) {
+		if (corner_factor() < 0.5f) discard;
+		outColor = vec4(WIRE_COLOR, 1.0f);
+		return;
+	}
 
+    // Lighting
+	vec3 diffuse = vec3(0.0f);
+	for (uint i = 0; i < scene.count; i++) {
+		diffuse += light_contribution(scene.lights[i], fragNormal);
+	}
 
-    lightDir *= -1; // frag space frag to light
-	float diffuseStrength = max(0.0, dot(lightDir, fragNormal)); // dot
-	vec3 diffuse = diffuseStrength * lightColor; // diffuse color
+	vec3 lighting = materials_params.ambient + diffuse; // apply changes
 
-	vec3 lighting = materials_params.ambient;
-	lighting = materials_params.ambient + diffuse; // apply changes
 
+    outColor = vec4(fragNormal * lighting, materials_params.dissolve);
+	if (DEBUG_SHADING != DEBUG_NONE) {
+		outColor = vec4(debug_color(), 1.0f);
+	}
 
-    outColor = fragNormal * lighting;
+	if (DISPLAY_MODE == DISPLAY_SHADED_WIREFRAME) {
+		outColor.rgb = mix(WIRE_COLOR, outColor.rgb, edge_factor());
+	}
 }
@@ -3,6 +3,12 @@
 
 layout (location = 0) out vec3 outColor;
 layout (location = 1) out vec3 outNormal;
+layout (location = 2) out vec3 outWorldPosition;
+layout (location = 3) out vec3 outBarycentric;
+layout (location = 4) out vec2 outUV;
+layout (location = 5) flat out uint outTriangleId;
+layout (location = 6) flat out uint outSubmeshId;
+layout (location = 7) flat out uint outSmoothingGroup;
 
 struct Vertex {
 	vec3 position; //considered as vec4
@@ -10,6 +16,7 @@ struct Vertex {
 	vec3 color;
 	uint uv_y;
 	vec3 normal;
+	uint smoothing_group;
 };
 
 layout(buffer_reference, std430) readonly buffer VertexBuffer{
@@ -31,10 +38,16 @@ layout(set = 0, binding = 0) buffer MaterialParams {
 
 
 
+// camera part of the scene buffer, see light::Header
+layout(set = 1, binding = 0) readonly buffer SceneLights {
+	mat4 view_projection;
+} scene;
+
 //push constants block
 layout( push_constant ) uniform constants
 {
-	mat4 render_matrix;
+	mat4 model;
+	mat3 normal_matrix;
 	VertexBuffer vertexBuffer;
 } PushConstants;
 
@@ -44,11 +57,23 @@ void main()
 	Vertex v = PushConstants.vertexBuffer.vertices[gl_VertexIndex];
 
 	// Position
-	gl_Position = PushConstants.render_matrix * vec4(v.position, 1.0f);
+	vec4 worldPosition = PushConstants.model * vec4(v.position, 1.0f);
+	gl_Position = scene.view_projection * worldPosition;
 
 	// Color
 	vec3 color = vec3(0.5,0.5,0.5);
 
-	outNormal = v.normal;
+	outNormal = normalize(PushConstants.normal_matrix * v.normal);
+	outWorldPosition = worldPosition.xyz;
+	// vertices are not shared between triangles (MeshAsset::from_obj), the corner is the index modulo 3
+	outBarycentric = vec3(0.0f);
+	outBarycentric[gl_VertexIndex % 3] = 1.0f;
+	gl_PointSize = 1.0f; // PolygonMode::POINT
+
+	// debug shading inputs, submesh index is passed as first_instance (renderer::RenderQueue)
+	outUV = vec2(uintBitsToFloat(v.uv_x), uintBitsToFloat(v.uv_y));
+	outTriangleId = uint(gl_VertexIndex) / 3;
+	outSubmeshId = uint(gl_InstanceIndex);
+	outSmoothingGroup = v.smoothing_group;
 	outColor = color;
 }
\ No newline at end of file
@@ -0,0 +1,27 @@
+use ecs::{component::Component, macros::Component};
+use glam::Vec3;
+
+// Direction is taken from the entity Rotation (forward is -Z)
+#[derive(Component, Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+// Position is taken from the entity Position
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PointLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+}
+
+// Position and direction are taken from the entity Position/Rotation
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SpotLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+    pub inner_angle: f32, // degrees
+    pub outer_angle: f32, // degrees
+}
@@ -0,0 +1,10 @@
+use std::ops::Range;
+
+use ecs::{component::Component, macros::Component};
+
+// Entity drawn by the render system, indices refer to the meshes/materials loaded at startup
+#[derive(Component, Debug, Clone)]
+pub struct MeshRenderable {
+    pub mesh: usize,
+    pub materials: Range<usize>, // submeshes match these by name, see RenderQueue::push_mesh
+}
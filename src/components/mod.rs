@@ -1,11 +1,17 @@
 pub mod camera;
 pub mod input;
+pub mod light;
+pub mod mesh_renderable;
 pub mod physics_body;
 pub mod position;
 pub mod rotation;
+pub mod scale;
 
 pub use camera::Camera;
 pub use input::Input;
+pub use light::{DirectionalLight, PointLight, SpotLight};
+pub use mesh_renderable::MeshRenderable;
 pub use physics_body::PhysicsBody;
 pub use position::Position;
 pub use rotation::Rotation;
+pub use scale::Scale;
@@ -0,0 +1,5 @@
+use ecs::{component::Component, macros::Component};
+use glam::Vec3;
+
+#[derive(Component, Debug)]
+pub struct Scale(pub Vec3);
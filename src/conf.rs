@@ -1,12 +1,13 @@
 use ash::vk;
 
-#[cfg(target_os = "windows")]
-//pub const PHYSICAL_DEVICE_NAME: &str = "NVIDIA GeForce RTX 2060";
-pub const PHYSICAL_DEVICE_NAME: &str = "NVIDIA GeForce RTX 4070 Ti";
+// Physical device override by name (or part of it) or index, e.g. "NVIDIA GeForce RTX 4070 Ti" or 1
+pub const PHYSICAL_DEVICE_ENV: &str = "SCOP42_DEVICE";
+pub const PHYSICAL_DEVICE_FLAG: &str = "--device";
 
-#[cfg(target_os = "linux")]
-pub const PHYSICAL_DEVICE_NAME: &str = "Intel(R) Graphics (ADL GT2)";
-//pub const PHYSICAL_DEVICE_NAME: &str = "AMD Unknown (RADV NAVI14)";
+// `--headless <output.ppm|png> [--reference <reference.ppm>]`: render one frame without a window
+pub const HEADLESS_FLAG: &str = "--headless";
+pub const REFERENCE_FLAG: &str = "--reference";
+pub const HEADLESS_TOLERANCE: u8 = 2; // per channel difference allowed against the reference
 
 pub const APPLICATION_NAME: &str = "AppName";
 pub const APPLICATION_VERSION: u32 = vk::make_api_version(0, 1, 0, 0);
@@ -14,18 +15,107 @@ pub const ENGINE_NAME: &str = "No Engine";
 pub const ENGINE_VERSION: u32 = vk::make_api_version(0, 1, 0, 0);
 pub const API_VERSION: u32 = vk::API_VERSION_1_3;
 
+// Validation layers, enabled when validation is requested and the layer is installed
+pub const VALIDATION_ENV: &str = "SCOP42_VALIDATION"; // "0" disables, "1" enables, defaults to debug builds
+pub const PANIC_ON_VALIDATION_ENV: &str = "SCOP42_PANIC_ON_VALIDATION"; // "1" panics on validation errors, see DebugMessenger::check
+pub const DEBUG_MIN_SEVERITY: vk::DebugUtilsMessageSeverityFlagsEXT =
+    vk::DebugUtilsMessageSeverityFlagsEXT::WARNING;
+pub const LOG_LEVEL: log::LevelFilter = log::LevelFilter::Info;
+
 #[cfg(target_os = "windows")]
 pub const LAYER_NAMES: [&str; 2] = ["VK_LAYER_KHRONOS_validation", "VK_LAYER_LUNARG_monitor"];
 
 #[cfg(target_os = "linux")]
 pub const LAYER_NAMES: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
 
+// ft_vk::RenderingPath
+pub const RENDERING_PATH_ENV: &str = "SCOP42_RENDERING"; // "render_pass" (default) or "dynamic"
+
+// ft_vk::PresentModePreference, toggled at runtime
+pub const PRESENT_MODE_ENV: &str = "SCOP42_PRESENT_MODE"; // "vsync", "immediate", "mailbox" (default) or "fifo_relaxed"
+
+pub const WINDOW_TITLE: &str = "scop42";
 pub const WINDOW_WIDTH: u32 = 800;
 pub const WINDOW_HEIGHT: u32 = 600;
 
-pub fn get_layer_names() -> (Vec<std::ffi::CString>, Vec<*const i8>) {
+pub const PIPELINE_CACHE_DIR: &str = "cache"; // one file per vendor/device id
+
+pub const MSAA_SAMPLES: u32 = 4; // clamped to the device, 1 disables multisampling
+
+pub const HEADLESS_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB; // Engine::new_headless color image
+
+pub const SCREENSHOT_DIR: &str = "screenshots";
+pub const SCREENSHOT_FORMAT: crate::helpers::image::ImageFormat =
+    crate::helpers::image::ImageFormat::Png;
+pub const SCREENSHOT_SCALE: u32 = 2; // high resolution screenshots, multiplies the window size
+
+pub const UPLOAD_STAGING_SIZE: vk::DeviceSize = 16 << 20; // Uploader ring, bigger uploads get their own buffer
+
+pub const MAX_FRAMES_IN_FLIGHT: usize = 2; // FrameData count, independent of the swapchain image count
+
+pub const MAX_LIGHTS: usize = 16; // per frame light buffer capacity
+
+// Descriptor pools, see ft_vk::descriptor_allocator
+pub const DESCRIPTOR_POOL_RATIOS: [crate::ft_vk::descriptor_allocator::PoolSizeRatio; 4] = [
+    crate::ft_vk::descriptor_allocator::PoolSizeRatio {
+        ty: vk::DescriptorType::STORAGE_BUFFER,
+        ratio: 2.0,
+    },
+    crate::ft_vk::descriptor_allocator::PoolSizeRatio {
+        ty: vk::DescriptorType::UNIFORM_BUFFER,
+        ratio: 1.0,
+    },
+    crate::ft_vk::descriptor_allocator::PoolSizeRatio {
+        ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        ratio: 4.0,
+    },
+    crate::ft_vk::descriptor_allocator::PoolSizeRatio {
+        ty: vk::DescriptorType::STORAGE_IMAGE,
+        ratio: 1.0,
+    },
+];
+pub const DESCRIPTOR_POOL_INITIAL_SETS: u32 = 16; // sets of the first pool, then x1.5 per pool
+pub const DESCRIPTOR_POOL_MAX_SETS: u32 = 4096; // cap of a single pool
+pub const FRAME_DESCRIPTOR_POOL_INITIAL_SETS: u32 = 8; // transient allocator of each FrameData
+
+pub const MAX_DEBUG_VERTICES: usize = 1 << 18; // per frame DebugDraw capacity, extra lines are dropped
+
+// ft_vk::GpuProfiler and ft_vk::FrameStats
+pub const PROFILER_MAX_SCOPES: u32 = 32; // per frame, extra scopes are not recorded
+pub const PROFILER_HISTORY: usize = 120; // frames averaged by the rolling stats
+pub const PROFILER_PIPELINE_STATISTICS: bool = true; // when the device supports the queries
+
+// `--device <value>` / `--device=<value>` flag first, then PHYSICAL_DEVICE_ENV
+pub fn get_physical_device_override() -> Option<String> {
+    get_flag(PHYSICAL_DEVICE_FLAG).or_else(|| std::env::var(PHYSICAL_DEVICE_ENV).ok())
+}
+
+// Value of a `<flag> <value>` or `<flag>=<value>` command line argument
+pub fn get_flag(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(&format!("{}=", flag)) {
+            return Some(value.to_string());
+        }
+    }
+
+    None
+}
+
+// LAYER_NAMES found in available_layers, missing ones are reported
+pub fn get_layer_names(available_layers: &[String]) -> (Vec<std::ffi::CString>, Vec<*const i8>) {
     let layer_names: Vec<std::ffi::CString> = LAYER_NAMES
         .iter()
+        .filter(|&&p| {
+            let available = available_layers.iter().any(|layer| layer == p);
+            if !available {
+                log::warn!("layer {} requested but not available", p);
+            }
+            available
+        })
         .map(|&p| std::ffi::CString::new(p).unwrap())
         .collect();
 
@@ -37,21 +127,26 @@ pub fn get_layer_names() -> (Vec<std::ffi::CString>, Vec<*const i8>) {
     (layer_names, layer_names_ptr)
 }
 
+// Required instance extensions, debug_utils is added when validation is enabled
 #[cfg(target_os = "windows")]
-pub const EXTENSION_NAMES: [*const i8; 3] = [
-    ash::ext::debug_utils::NAME.as_ptr(),
+pub const EXTENSION_NAMES: [*const i8; 2] = [
     ash::khr::surface::NAME.as_ptr(),
     ash::khr::win32_surface::NAME.as_ptr(),
 ];
 
 #[cfg(target_os = "linux")]
-pub const EXTENSION_NAMES: [*const i8; 4] = [
-    ash::ext::debug_utils::NAME.as_ptr(),
+pub const EXTENSION_NAMES: [*const i8; 3] = [
     ash::khr::surface::NAME.as_ptr(),
     ash::khr::xlib_surface::NAME.as_ptr(),
     ash::khr::wayland_surface::NAME.as_ptr(),
 ];
 
+// headless engine has no surface
+pub const HEADLESS_EXTENSION_NAMES: [*const i8; 0] = [];
+
+pub const HEADLESS_DEVICE_EXTENSION_NAMES: [&std::ffi::CStr; 1] =
+    [ash::khr::buffer_device_address::NAME];
+
 pub const DEVICE_EXTENSION_NAMES: [&std::ffi::CStr; 2] = [
     ash::khr::swapchain::NAME,
     ash::khr::buffer_device_address::NAME,
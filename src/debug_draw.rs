@@ -0,0 +1,195 @@
+use glam::{Mat4, Vec3, Vec4};
+
+const SPHERE_SEGMENTS: usize = 24; // per circle, a sphere is drawn as 3 circles
+const ARROW_HEAD: f32 = 0.15f32; // head length relative to the arrow length
+
+/**
+ * struct DebugVertex
+ * read by debug_line.vert through its buffer device address
+ * color is RGBA8 (unpackUnorm4x8)
+ */
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DebugVertex {
+    pub position: Vec3,
+    pub color: u32,
+}
+
+// Labelled world position, drawn as a marker, the label is logged (see DebugDraw::log_text_anchors)
+#[derive(Debug, Clone)]
+pub struct TextAnchor {
+    pub position: Vec3,
+    pub text: String,
+}
+
+// Shapes added by debug_draw_system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugToggles {
+    pub grid: bool,
+    pub origin_axes: bool, // at Entity::Origin
+    pub aabbs: bool,       // of every MeshRenderable
+    pub normals: bool,     // per vertex
+    pub lights: bool,      // range and direction of the light components, labelled
+}
+
+/**
+ * Resource collecting immediate mode lines for the current frame.
+ * Any system can push shapes, they are drawn in a single line list after the meshes
+ * (see renderer::DebugRenderer) then cleared.
+ */
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    pub vertices: Vec<DebugVertex>, // line list, 2 per line
+    pub text_anchors: Vec<TextAnchor>,
+    pub toggles: DebugToggles,
+    logged_labels: Vec<String>, // text of the previous frame anchors
+}
+
+impl DebugDraw {
+    // Labels of the anchors absent from the previous frame, there is no text rendering
+    pub fn log_text_anchors(&mut self) {
+        for anchor in &self.text_anchors {
+            if !self.logged_labels.contains(&anchor.text) {
+                log::info!(
+                    "{} at ({:.2}, {:.2}, {:.2})",
+                    anchor.text,
+                    anchor.position.x,
+                    anchor.position.y,
+                    anchor.position.z
+                );
+            }
+        }
+        self.logged_labels = self
+            .text_anchors
+            .iter()
+            .map(|anchor| anchor.text.clone())
+            .collect();
+    }
+
+    // toggles are kept
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.text_anchors.clear();
+    }
+
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: Vec4) {
+        let color = pack_color(color);
+        self.vertices.extend([
+            DebugVertex {
+                position: from,
+                color,
+            },
+            DebugVertex {
+                position: to,
+                color,
+            },
+        ]);
+    }
+
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        self.transformed_box(Mat4::IDENTITY, min, max, color);
+    }
+
+    // box from min to max in the space of transform (e.g. a model matrix)
+    pub fn transformed_box(&mut self, transform: Mat4, min: Vec3, max: Vec3, color: Vec4) {
+        let corner = |index: usize| {
+            transform.transform_point3(Vec3::new(
+                if index & 1 == 0 { min.x } else { max.x },
+                if index & 2 == 0 { min.y } else { max.y },
+                if index & 4 == 0 { min.z } else { max.z },
+            ))
+        };
+
+        // corners differing by a single bit share an edge
+        for index in 0..8 {
+            for bit in [1, 2, 4] {
+                if index & bit == 0 {
+                    self.line(corner(index), corner(index | bit), color);
+                }
+            }
+        }
+    }
+
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec4) {
+        self.circle(center, Vec3::X * radius, Vec3::Y * radius, color);
+        self.circle(center, Vec3::Y * radius, Vec3::Z * radius, color);
+        self.circle(center, Vec3::Z * radius, Vec3::X * radius, color);
+    }
+
+    // circle in the plane of the two (orthogonal, radius long) axes
+    fn circle(&mut self, center: Vec3, axis_a: Vec3, axis_b: Vec3, color: Vec4) {
+        let point = |segment: usize| {
+            let angle = segment as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + axis_a * angle.cos() + axis_b * angle.sin()
+        };
+
+        for segment in 0..SPHERE_SEGMENTS {
+            self.line(point(segment), point(segment + 1), color);
+        }
+    }
+
+    pub fn arrow(&mut self, from: Vec3, to: Vec3, color: Vec4) {
+        self.line(from, to, color);
+
+        let direction = to - from;
+        let length = direction.length();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = direction / length;
+        let (side_a, side_b) = direction.any_orthonormal_pair();
+        let head = length * ARROW_HEAD;
+        let base = to - direction * head;
+        for side in [side_a, -side_a, side_b, -side_b] {
+            self.line(to, base + side * head * 0.5f32, color);
+        }
+    }
+
+    // X red, Y green, Z blue arrows of the transform basis
+    pub fn axes(&mut self, transform: Mat4, length: f32) {
+        let origin = transform.transform_point3(Vec3::ZERO);
+        for (axis, color) in [
+            (Vec3::X, Vec4::new(1.0, 0.0, 0.0, 1.0)),
+            (Vec3::Y, Vec4::new(0.0, 1.0, 0.0, 1.0)),
+            (Vec3::Z, Vec4::new(0.0, 0.0, 1.0, 1.0)),
+        ] {
+            self.arrow(origin, transform.transform_point3(axis * length), color);
+        }
+    }
+
+    // square grid on the XZ plane centered on the origin, cells lines per side
+    pub fn grid(&mut self, cells: u32, spacing: f32, color: Vec4) {
+        let half = cells as f32 * spacing * 0.5f32;
+        for line in 0..=cells {
+            let offset = line as f32 * spacing - half;
+            self.line(
+                Vec3::new(offset, 0.0, -half),
+                Vec3::new(offset, 0.0, half),
+                color,
+            );
+            self.line(
+                Vec3::new(-half, 0.0, offset),
+                Vec3::new(half, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    // small cross marks the anchor
+    pub fn text(&mut self, position: Vec3, text: impl Into<String>, color: Vec4) {
+        const MARKER: f32 = 0.05f32;
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.line(position - axis * MARKER, position + axis * MARKER, color);
+        }
+        self.text_anchors.push(TextAnchor {
+            position,
+            text: text.into(),
+        });
+    }
+}
+
+fn pack_color(color: Vec4) -> u32 {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u32;
+
+    channel(color.x) | (channel(color.y) << 8) | (channel(color.z) << 16) | (channel(color.w) << 24)
+}
@@ -0,0 +1,141 @@
+use std::sync::Mutex;
+
+use ash::vk;
+
+use crate::conf;
+
+pub struct DebugConfig {
+    pub validation: bool, // VK_LAYER_KHRONOS_validation, only enabled when present
+    pub min_severity: vk::DebugUtilsMessageSeverityFlagsEXT, // messages below are dropped
+    pub panic_on_validation_error: bool, // see DebugMessenger::check
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        let validation = match std::env::var(conf::VALIDATION_ENV) {
+            Ok(value) => value != "0",
+            Err(_) => cfg!(debug_assertions),
+        };
+
+        let panic_on_validation_error =
+            std::env::var(conf::PANIC_ON_VALIDATION_ENV).is_ok_and(|value| value != "0");
+
+        Self {
+            validation,
+            min_severity: conf::DEBUG_MIN_SEVERITY,
+            panic_on_validation_error,
+        }
+    }
+}
+
+// Owned by the messenger, handed to the callback as p_user_data
+struct DebugState {
+    min_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    panic_on_validation_error: bool,
+    validation_errors: Mutex<Vec<String>>,
+}
+
+pub struct DebugMessenger {
+    pub loader: ash::ext::debug_utils::Instance,
+    pub messenger: vk::DebugUtilsMessengerEXT,
+    state: Box<DebugState>,
+}
+
+impl DebugMessenger {
+    pub fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        config: &DebugConfig,
+    ) -> Result<Self, vk::Result> {
+        let loader = ash::ext::debug_utils::Instance::new(entry, instance);
+        let state = Box::new(DebugState {
+            min_severity: config.min_severity,
+            panic_on_validation_error: config.panic_on_validation_error,
+            validation_errors: Mutex::new(vec![]),
+        });
+
+        // only ask the driver for what passes the filter
+        let message_severity = [
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+        ]
+        .into_iter()
+        .filter(|severity| severity.as_raw() >= config.min_severity.as_raw())
+        .fold(vk::DebugUtilsMessageSeverityFlagsEXT::empty(), |acc, s| {
+            acc | s
+        });
+
+        let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(message_severity)
+            .message_type(
+                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                    | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            )
+            .pfn_user_callback(Some(vulkan_debug_utils_callback))
+            .user_data(state.as_ref() as *const DebugState as *mut std::ffi::c_void);
+        let messenger = unsafe { loader.create_debug_utils_messenger(&debug_info, None) }?;
+
+        Ok(Self {
+            loader,
+            messenger,
+            state,
+        })
+    }
+
+    /**
+     * Panics when panic_on_validation_error is set and validation errors were reported.
+     * Panicking inside the callback would abort (extern "system"), so errors are raised here.
+     */
+    pub fn check(&self) {
+        if !self.state.panic_on_validation_error {
+            return;
+        }
+
+        let errors = std::mem::take(&mut *self.state.validation_errors.lock().unwrap());
+        if !errors.is_empty() {
+            panic!("vulkan validation errors:\n{}", errors.join("\n"));
+        }
+    }
+
+    pub unsafe fn destroy(self) {
+        self.loader
+            .destroy_debug_utils_messenger(self.messenger, None);
+    }
+}
+
+unsafe extern "system" fn vulkan_debug_utils_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    p_user_data: *mut std::ffi::c_void,
+) -> vk::Bool32 {
+    let state = &*(p_user_data as *const DebugState);
+    if message_severity.as_raw() < state.min_severity.as_raw() {
+        return vk::FALSE;
+    }
+
+    let message = std::ffi::CStr::from_ptr((*p_callback_data).p_message).to_string_lossy();
+    let ty = format!("{:?}", message_type).to_lowercase();
+
+    let level = match message_severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => log::Level::Error,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Info,
+        _ => log::Level::Trace,
+    };
+    log::log!(target: "vulkan", level, "[{}] {}", ty, message);
+
+    if state.panic_on_validation_error
+        && message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+        && message_type.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION)
+    {
+        if let Ok(mut errors) = state.validation_errors.lock() {
+            errors.push(message.into_owned());
+        }
+    }
+
+    vk::FALSE
+}
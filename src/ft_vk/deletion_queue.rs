@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use ash::vk;
+
+use super::{
+    allocated_buffer::AllocatedBuffer, allocated_image::AllocatedImage, swapchain::Swapchain,
+};
+
+// GPU objects whose destruction waits for the frames that may still use them
+pub enum Resource {
+    Buffer(AllocatedBuffer),
+    Image(AllocatedImage), // image view included
+    Pipeline(vk::Pipeline),
+    PipelineLayout(vk::PipelineLayout),
+    DescriptorSet(vk::DescriptorSet), // freed back to Engine::descriptor_allocator
+    Framebuffer(vk::Framebuffer),
+    RenderPass(vk::RenderPass),
+    Swapchain(Box<Swapchain>),
+}
+
+impl From<AllocatedBuffer> for Resource {
+    fn from(buffer: AllocatedBuffer) -> Self {
+        Self::Buffer(buffer)
+    }
+}
+
+impl From<AllocatedImage> for Resource {
+    fn from(image: AllocatedImage) -> Self {
+        Self::Image(image)
+    }
+}
+
+/**
+ * Resources tagged with the last frame (Engine::frame_count) that may use them.
+ * Frames are submitted in order, so entries are released front to back once their frame fence signaled.
+ */
+#[derive(Default)]
+pub struct DeletionQueue {
+    pending: VecDeque<(usize, Resource)>,
+}
+
+impl DeletionQueue {
+    pub fn push(&mut self, frame: usize, resource: Resource) {
+        self.pending.push_back((frame, resource));
+    }
+
+    /**
+     * Resources of every frame the fences waited for so far, with frame_count frames submitted:
+     * the FrameData about to be reused was last signaled by frame frame_count - frames_in_flight.
+     */
+    pub fn drain_completed(
+        &mut self,
+        frame_count: usize,
+        frames_in_flight: usize,
+    ) -> Vec<Resource> {
+        let Some(completed_frame) = frame_count.checked_sub(frames_in_flight) else {
+            return vec![];
+        };
+        let count = self
+            .pending
+            .iter()
+            .take_while(|(frame, _)| *frame <= completed_frame)
+            .count();
+
+        self.pending
+            .drain(..count)
+            .map(|(_, resource)| resource)
+            .collect()
+    }
+
+    pub fn drain_all(&mut self) -> Vec<Resource> {
+        self.pending
+            .drain(..)
+            .map(|(_, resource)| resource)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    fn pipelines(resources: Vec<Resource>) -> Vec<u64> {
+        resources
+            .into_iter()
+            .map(|resource| match resource {
+                Resource::Pipeline(pipeline) => pipeline.as_raw(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drain_completed_frames() {
+        let mut queue = DeletionQueue::default();
+        for frame in [0, 1, 1, 3] {
+            queue.push(
+                frame,
+                Resource::Pipeline(vk::Pipeline::from_raw(frame as u64 + 10)),
+            );
+        }
+
+        // 2 frames in flight: nothing is complete before the third frame
+        assert!(queue.drain_completed(0, 2).is_empty());
+        assert!(queue.drain_completed(1, 2).is_empty());
+        assert_eq!(pipelines(queue.drain_completed(2, 2)), [10]);
+        assert_eq!(pipelines(queue.drain_completed(3, 2)), [11, 11]);
+        assert!(queue.drain_completed(4, 2).is_empty());
+        assert_eq!(pipelines(queue.drain_completed(5, 2)), [13]);
+        assert!(queue.drain_all().is_empty());
+    }
+
+    #[test]
+    fn drain_all_in_order() {
+        let mut queue = DeletionQueue::default();
+        for frame in [4, 5] {
+            queue.push(
+                frame,
+                Resource::Pipeline(vk::Pipeline::from_raw(frame as u64)),
+            );
+        }
+
+        assert!(queue.drain_completed(6, 3).is_empty());
+        assert_eq!(pipelines(queue.drain_all()), [4, 5]);
+    }
+}
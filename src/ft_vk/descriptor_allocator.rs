@@ -1,43 +1,71 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 
 use ash::vk;
 
+use super::EngineError;
+
+// Descriptors of a type reserved per set in each pool, e.g. 2.0 storage buffers per set
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSizeRatio {
+    pub ty: vk::DescriptorType,
+    pub ratio: f32,
+}
+
+/**
+ * Growable descriptor set allocator.
+ * Pools are sized from the ratios and sets_per_pool, every new pool is 1.5x bigger up to max_sets_per_pool.
+ * A pool that can't allocate anymore (OUT_OF_POOL_MEMORY/FRAGMENTED_POOL) is kept full until reset_pools
+ * or until one of its sets is freed.
+ */
 #[derive(Debug)]
 pub struct DescriptorAllocator {
     full_pools: VecDeque<vk::DescriptorPool>,
     ready_pools: VecDeque<vk::DescriptorPool>,
-    pool_sizes: Vec<vk::DescriptorPoolSize>,
-    max_sets: u32,
+    set_pools: HashMap<vk::DescriptorSet, vk::DescriptorPool>, // pool of each live set, see free_descriptor_set
+    ratios: Vec<PoolSizeRatio>,
+    sets_per_pool: u32,
+    max_sets_per_pool: u32,
 }
 
 impl DescriptorAllocator {
-    pub fn new(max_sets: u32, pool_sizes: Vec<vk::DescriptorPoolSize>) -> Self {
+    pub fn new(initial_sets: u32, max_sets_per_pool: u32, ratios: &[PoolSizeRatio]) -> Self {
         Self {
-            max_sets,
-            pool_sizes,
+            ratios: ratios.to_vec(),
+            sets_per_pool: initial_sets.max(1),
+            max_sets_per_pool: max_sets_per_pool.max(initial_sets.max(1)),
             full_pools: VecDeque::new(),
             ready_pools: VecDeque::new(),
+            set_pools: HashMap::new(),
         }
     }
 
-    pub fn reset_pools(&mut self, device: &ash::Device) {
+    // every set allocated so far becomes invalid, pools are kept
+    pub fn reset_pools(&mut self, device: &ash::Device) -> Result<(), EngineError> {
         for &pool in &self.ready_pools {
-            unsafe {
-                device
-                    .reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())
-                    .unwrap()
-            };
+            unsafe { device.reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())? };
         }
 
-        for &pool in &self.full_pools {
-            unsafe {
-                device
-                    .reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())
-                    .unwrap()
-            };
+        while let Some(pool) = self.full_pools.pop_front() {
+            unsafe { device.reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())? };
             self.ready_pools.push_back(pool);
         }
-        self.full_pools.clear();
+        self.set_pools.clear();
+
+        Ok(())
+    }
+
+    // set must not be used by a pending command buffer, see Engine::destroy_later
+    pub fn free_descriptor_set(&mut self, device: &ash::Device, set: vk::DescriptorSet) {
+        let Some(pool) = self.set_pools.remove(&set) else {
+            panic!("DescriptorAllocator: {:?} was not allocated here", set);
+        };
+        unsafe { device.free_descriptor_sets(pool, &[set]) }.unwrap();
+
+        // room for at least one more set
+        if let Some(index) = self.full_pools.iter().position(|&full| full == pool) {
+            self.full_pools.remove(index);
+            self.ready_pools.push_front(pool);
+        }
     }
 
     pub fn destroy_pools(&mut self, device: &ash::Device) {
@@ -50,65 +78,80 @@ impl DescriptorAllocator {
             unsafe { device.destroy_descriptor_pool(pool, None) };
         }
         self.full_pools.clear();
+        self.set_pools.clear();
     }
 
+    // Fails when the layout doesn't fit in an empty pool (missing ratio for one of its types)
     pub fn allocate_descriptor_set(
         &mut self,
         device: &ash::Device,
         layout: vk::DescriptorSetLayout,
-    ) -> vk::DescriptorSet {
-        let pool = self.get_pool(device);
-
+    ) -> Result<vk::DescriptorSet, EngineError> {
         let layouts = [layout];
-        let descriptor_set_info = vk::DescriptorSetAllocateInfo::default()
-            .set_layouts(&layouts)
-            .descriptor_pool(pool);
-
-        let (pool, descriptor_sets) = match unsafe {
-            device.allocate_descriptor_sets(&descriptor_set_info)
-        } {
-            Ok(destriptor_sets) => (pool, destriptor_sets),
-            Err(error) => {
-                println!(
-                        "Descriptor.allocate: can't allocate descriptor_sets error=`{}`, retrying by creating a new pool...",
-                        error
-                    );
-                self.full_pools.push_back(pool);
-
-                let pool = self.get_pool(device);
-                let description_set_info = descriptor_set_info.descriptor_pool(pool);
-                let descriptor_sets = unsafe {
-                    device
-                        .allocate_descriptor_sets(&description_set_info)
-                        .unwrap()
-                };
 
-                (pool, descriptor_sets)
+        loop {
+            let (pool, created) = self.get_pool(device)?;
+            let descriptor_set_info = vk::DescriptorSetAllocateInfo::default()
+                .set_layouts(&layouts)
+                .descriptor_pool(pool);
+
+            match unsafe { device.allocate_descriptor_sets(&descriptor_set_info) } {
+                Ok(descriptor_sets) => {
+                    self.ready_pools.push_back(pool);
+                    self.set_pools.insert(descriptor_sets[0], pool);
+                    return Ok(descriptor_sets[0]);
+                }
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL)
+                    if !created =>
+                {
+                    // try the next ready pool, or a new one
+                    self.full_pools.push_back(pool);
+                }
+                Err(error) => {
+                    // a new pool failing means the layout needs more than the ratios provide
+                    self.ready_pools.push_back(pool);
+                    log::error!(
+                        "DescriptorAllocator: can't allocate a descriptor set: {} (ratios {:?})",
+                        error,
+                        self.ratios
+                    );
+                    return Err(error.into());
+                }
             }
-        };
-
-        self.ready_pools.push_back(pool);
-
-        descriptor_sets.first().copied().unwrap()
+        }
     }
 
-    fn get_pool(&mut self, device: &ash::Device) -> vk::DescriptorPool {
-        let new_pool: vk::DescriptorPool = match self.ready_pools.pop_back() {
-            Some(pool) => pool,
-            None => {
-                let pool = {
-                    let pool_info = vk::DescriptorPoolCreateInfo::default()
-                        .flags(vk::DescriptorPoolCreateFlags::empty())
-                        .max_sets(self.max_sets)
-                        .pool_sizes(&self.pool_sizes);
-                    unsafe { device.create_descriptor_pool(&pool_info, None).unwrap() }
-                };
-                self.max_sets = (self.max_sets + (self.max_sets / 2));
-
-                pool
-            }
-        };
+    // ready pool, or a new one (true)
+    fn get_pool(
+        &mut self,
+        device: &ash::Device,
+    ) -> Result<(vk::DescriptorPool, bool), EngineError> {
+        if let Some(pool) = self.ready_pools.pop_back() {
+            return Ok((pool, false));
+        }
 
-        new_pool
+        let pool_sizes: Vec<vk::DescriptorPoolSize> = self
+            .ratios
+            .iter()
+            .map(|ratio| {
+                vk::DescriptorPoolSize::default()
+                    .ty(ratio.ty)
+                    .descriptor_count(
+                        ((ratio.ratio * self.sets_per_pool as f32).ceil() as u32).max(1),
+                    )
+            })
+            .collect();
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+            .max_sets(self.sets_per_pool)
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe { device.create_descriptor_pool(&pool_info, None)? };
+
+        // geometric growth, the next pool is bigger
+        self.sets_per_pool = (self.sets_per_pool * 3)
+            .div_ceil(2)
+            .min(self.max_sets_per_pool);
+
+        Ok((pool, true))
     }
 }
@@ -0,0 +1,252 @@
+use ash::vk;
+
+use super::EngineError;
+
+/**
+ * Collects descriptor writes then applies them to one or more sets.
+ * Infos are owned by the writer, the vk::WriteDescriptorSet pointing to them only live
+ * inside update_set so they can't outlive their data.
+ */
+#[derive(Debug, Default)]
+pub struct DescriptorWriter {
+    writes: Vec<PendingWrite>,
+}
+
+#[derive(Debug)]
+struct PendingWrite {
+    binding: u32,
+    first_element: u32, // dst_array_element
+    ty: vk::DescriptorType,
+    infos: Infos,
+}
+
+#[derive(Debug)]
+enum Infos {
+    Buffers(Vec<vk::DescriptorBufferInfo>),
+    Images(Vec<vk::DescriptorImageInfo>),
+}
+
+impl DescriptorWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // range can be vk::WHOLE_SIZE
+    pub fn write_buffer(
+        &mut self,
+        binding: u32,
+        buffer: vk::Buffer,
+        range: vk::DeviceSize,
+        offset: vk::DeviceSize,
+        ty: vk::DescriptorType,
+    ) -> Result<&mut Self, EngineError> {
+        self.write_buffers(
+            binding,
+            0,
+            &[vk::DescriptorBufferInfo::default()
+                .buffer(buffer)
+                .offset(offset)
+                .range(range)],
+            ty,
+        )
+    }
+
+    // consecutive elements of an array binding, starting at first_element
+    pub fn write_buffers(
+        &mut self,
+        binding: u32,
+        first_element: u32,
+        infos: &[vk::DescriptorBufferInfo],
+        ty: vk::DescriptorType,
+    ) -> Result<&mut Self, EngineError> {
+        if !is_buffer_type(ty) {
+            return Err(EngineError::DescriptorType(ty));
+        }
+
+        Ok(self.push(binding, first_element, ty, Infos::Buffers(infos.to_vec())))
+    }
+
+    /**
+     * COMBINED_IMAGE_SAMPLER or STORAGE_IMAGE, sampler is ignored (may be null) for the latter.
+     * layout is the one the image is in when the set is used,
+     * e.g. SHADER_READ_ONLY_OPTIMAL for sampling, GENERAL for storage images.
+     */
+    pub fn write_image(
+        &mut self,
+        binding: u32,
+        image_view: vk::ImageView,
+        sampler: vk::Sampler,
+        layout: vk::ImageLayout,
+        ty: vk::DescriptorType,
+    ) -> Result<&mut Self, EngineError> {
+        self.write_images(
+            binding,
+            0,
+            &[vk::DescriptorImageInfo::default()
+                .image_view(image_view)
+                .sampler(sampler)
+                .image_layout(layout)],
+            ty,
+        )
+    }
+
+    // consecutive elements of an array binding, starting at first_element
+    pub fn write_images(
+        &mut self,
+        binding: u32,
+        first_element: u32,
+        infos: &[vk::DescriptorImageInfo],
+        ty: vk::DescriptorType,
+    ) -> Result<&mut Self, EngineError> {
+        if !is_image_type(ty) {
+            return Err(EngineError::DescriptorType(ty));
+        }
+
+        Ok(self.push(binding, first_element, ty, Infos::Images(infos.to_vec())))
+    }
+
+    // every pending write to set, the writer can be reused for other sets
+    pub fn update_set(&self, device: &ash::Device, set: vk::DescriptorSet) {
+        unsafe { device.update_descriptor_sets(&self.descriptor_writes(set), &[]) };
+    }
+
+    fn descriptor_writes(&self, set: vk::DescriptorSet) -> Vec<vk::WriteDescriptorSet<'_>> {
+        self.writes
+            .iter()
+            .map(|write| {
+                let write_descriptor_set = vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(write.binding)
+                    .dst_array_element(write.first_element)
+                    .descriptor_type(write.ty);
+                match &write.infos {
+                    Infos::Buffers(infos) => write_descriptor_set.buffer_info(infos),
+                    Infos::Images(infos) => write_descriptor_set.image_info(infos),
+                }
+            })
+            .collect()
+    }
+
+    fn push(
+        &mut self,
+        binding: u32,
+        first_element: u32,
+        ty: vk::DescriptorType,
+        infos: Infos,
+    ) -> &mut Self {
+        self.writes.push(PendingWrite {
+            binding,
+            first_element,
+            ty,
+            infos,
+        });
+
+        self
+    }
+}
+
+fn is_buffer_type(ty: vk::DescriptorType) -> bool {
+    matches!(
+        ty,
+        vk::DescriptorType::UNIFORM_BUFFER
+            | vk::DescriptorType::STORAGE_BUFFER
+            | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+            | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC
+    )
+}
+
+fn is_image_type(ty: vk::DescriptorType) -> bool {
+    matches!(
+        ty,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER | vk::DescriptorType::STORAGE_IMAGE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    #[test]
+    fn writes_buffers_and_images() {
+        let mut writer = DescriptorWriter::new();
+        writer
+            .write_buffer(
+                0,
+                vk::Buffer::from_raw(1),
+                vk::WHOLE_SIZE,
+                0,
+                vk::DescriptorType::STORAGE_BUFFER,
+            )
+            .unwrap()
+            .write_image(
+                1,
+                vk::ImageView::from_raw(2),
+                vk::Sampler::from_raw(3),
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            )
+            .unwrap()
+            .write_images(
+                2,
+                4,
+                &[vk::DescriptorImageInfo::default().image_layout(vk::ImageLayout::GENERAL); 3],
+                vk::DescriptorType::STORAGE_IMAGE,
+            )
+            .unwrap();
+
+        let set = vk::DescriptorSet::from_raw(9);
+        let writes = writer.descriptor_writes(set);
+        assert_eq!(writes.len(), 3);
+        assert!(writes.iter().all(|write| write.dst_set == set));
+
+        let (buffer, sampled, storage) = (&writes[0], &writes[1], &writes[2]);
+        assert_eq!(buffer.descriptor_type, vk::DescriptorType::STORAGE_BUFFER);
+        assert_eq!(buffer.descriptor_count, 1);
+        assert!(!buffer.p_buffer_info.is_null() && buffer.p_image_info.is_null());
+
+        assert_eq!(sampled.dst_binding, 1);
+        assert_eq!(sampled.descriptor_count, 1);
+        assert!(sampled.p_buffer_info.is_null());
+        let info = unsafe { &*sampled.p_image_info };
+        assert_eq!(info.sampler, vk::Sampler::from_raw(3));
+        assert_eq!(info.image_view, vk::ImageView::from_raw(2));
+        assert_eq!(info.image_layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+        assert_eq!(storage.descriptor_type, vk::DescriptorType::STORAGE_IMAGE);
+        assert_eq!(
+            (storage.dst_array_element, storage.descriptor_count),
+            (4, 3)
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_types() {
+        let mut writer = DescriptorWriter::new();
+        assert_eq!(
+            writer
+                .write_buffer(
+                    0,
+                    vk::Buffer::null(),
+                    vk::WHOLE_SIZE,
+                    0,
+                    vk::DescriptorType::STORAGE_IMAGE
+                )
+                .err(),
+            Some(EngineError::DescriptorType(
+                vk::DescriptorType::STORAGE_IMAGE
+            ))
+        );
+        assert_eq!(
+            writer
+                .write_images(0, 0, &[], vk::DescriptorType::UNIFORM_BUFFER)
+                .err(),
+            Some(EngineError::DescriptorType(
+                vk::DescriptorType::UNIFORM_BUFFER
+            ))
+        );
+        assert!(writer
+            .descriptor_writes(vk::DescriptorSet::null())
+            .is_empty());
+    }
+}
@@ -0,0 +1,47 @@
+use ash::vk;
+
+/**
+ * Failure of the engine or of a resource creator.
+ * DeviceLost invalidates every resource of the device, see Engine::recreate.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    DeviceLost,
+    OutOfMemory(vk::Result), // host or device memory
+    Vulkan(vk::Result),
+    NoSuitableDevice(String),
+    MissingExtension(String),
+    Surface(String), // window handle unsupported or unavailable
+    Shader(String),  // SPIR-V module unreadable or stages disagreeing on an interface
+    // written with infos of another kind, see DescriptorWriter
+    DescriptorType(vk::DescriptorType),
+}
+
+impl From<vk::Result> for EngineError {
+    fn from(result: vk::Result) -> Self {
+        match result {
+            vk::Result::ERROR_DEVICE_LOST => EngineError::DeviceLost,
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY | vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
+                EngineError::OutOfMemory(result)
+            }
+            _ => EngineError::Vulkan(result),
+        }
+    }
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::DeviceLost => write!(f, "device lost"),
+            EngineError::OutOfMemory(result) => write!(f, "out of memory ({})", result),
+            EngineError::Vulkan(result) => write!(f, "vulkan error {}", result),
+            EngineError::NoSuitableDevice(reason) => write!(f, "no suitable device: {}", reason),
+            EngineError::MissingExtension(name) => write!(f, "missing instance extension {}", name),
+            EngineError::Surface(reason) => write!(f, "surface creation failed: {}", reason),
+            EngineError::Shader(reason) => write!(f, "invalid shader: {}", reason),
+            EngineError::DescriptorType(ty) => write!(f, "unexpected descriptor type {:?}", ty),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
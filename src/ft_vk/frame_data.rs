@@ -1,6 +1,9 @@
+use std::cell::RefCell;
+
 use ash::vk;
 
-use super::descriptor_allocator::{self, DescriptorAllocator};
+use super::{descriptor_allocator::DescriptorAllocator, error::EngineError};
+use crate::conf;
 
 #[derive(Debug)]
 pub struct FrameData {
@@ -8,53 +11,52 @@ pub struct FrameData {
     pub command_buffer: vk::CommandBuffer,
 
     pub fence: vk::Fence,
-    pub render_semaphore: vk::Semaphore,
-    pub present_semaphore: vk::Semaphore,
-    pub descriptor_allocator: DescriptorAllocator,
+    pub present_semaphore: vk::Semaphore, // signaled by acquire_next_image, render semaphores are per swapchain image
+    pub descriptor_allocator: RefCell<DescriptorAllocator>, // transient sets, reset by Engine::draw_frame
 }
 
 impl FrameData {
-    pub fn new(device: &ash::Device, graphics_family: u32) -> Self {
-        let descriptor_allocator = DescriptorAllocator::new(0, vec![]);
+    pub fn new(device: &ash::Device, graphics_family: u32) -> Result<Self, EngineError> {
+        let descriptor_allocator = RefCell::new(DescriptorAllocator::new(
+            conf::FRAME_DESCRIPTOR_POOL_INITIAL_SETS,
+            conf::DESCRIPTOR_POOL_MAX_SETS,
+            &conf::DESCRIPTOR_POOL_RATIOS,
+        ));
 
         let command_pool_info = vk::CommandPoolCreateInfo::default()
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
             .queue_family_index(graphics_family);
-        let command_pool = unsafe { device.create_command_pool(&command_pool_info, None) }.unwrap();
+        let command_pool = unsafe { device.create_command_pool(&command_pool_info, None) }?;
 
         let command_buffer_info = vk::CommandBufferAllocateInfo::default()
             .command_pool(command_pool)
             .level(vk::CommandBufferLevel::PRIMARY)
             .command_buffer_count(1);
-        let command_buffer =
-            unsafe { device.allocate_command_buffers(&command_buffer_info) }.unwrap()[0];
+        let command_buffer = unsafe { device.allocate_command_buffers(&command_buffer_info) }?[0];
 
         let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
-        let fence = unsafe { device.create_fence(&fence_info, None).unwrap() };
+        let fence = unsafe { device.create_fence(&fence_info, None)? };
 
         let semaphore_info = vk::SemaphoreCreateInfo::default();
-        let render_semaphore = unsafe { device.create_semaphore(&semaphore_info, None).unwrap() };
-        let present_semaphore = unsafe { device.create_semaphore(&semaphore_info, None).unwrap() };
+        let present_semaphore = unsafe { device.create_semaphore(&semaphore_info, None)? };
 
-        Self {
+        Ok(Self {
             command_pool,
             command_buffer,
             fence,
-            render_semaphore,
             present_semaphore,
             descriptor_allocator,
-        }
+        })
     }
 
     pub fn destroy(mut self, device: &ash::Device) {
         unsafe {
-            device.destroy_semaphore(self.render_semaphore, None);
             device.destroy_semaphore(self.present_semaphore, None);
             device.destroy_fence(self.fence, None);
             device.free_command_buffers(self.command_pool, &[self.command_buffer]);
             device.destroy_command_pool(self.command_pool, None);
         }
 
-        self.descriptor_allocator.destroy_pools(device);
+        self.descriptor_allocator.get_mut().destroy_pools(device);
     }
 }
@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+
+use super::profiler::{PipelineStatistics, ScopeResult};
+
+// Last capacity samples of a value, the oldest is dropped first
+#[derive(Debug, Clone)]
+pub struct RollingStats {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl RollingStats {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn last(&self) -> Option<f32> {
+        self.samples.back().copied()
+    }
+
+    // 0 without samples, same for min and max
+    pub fn average(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().sum::<f32>() / self.samples.len() as f32
+    }
+
+    pub fn min(&self) -> f32 {
+        self.samples.iter().copied().reduce(f32::min).unwrap_or(0.0)
+    }
+
+    pub fn max(&self) -> f32 {
+        self.samples.iter().copied().reduce(f32::max).unwrap_or(0.0)
+    }
+
+    // nearest rank, p in 0..=100 (e.g. 95 for the slowest 5% of frames)
+    pub fn percentile(&self, p: f32) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let mut sorted: Vec<f32> = self.samples.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * sorted.len() as f32).ceil() as usize;
+        sorted[rank.saturating_sub(1)]
+    }
+}
+
+impl std::fmt::Display for RollingStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} ms (min {:.2}, max {:.2}, p95 {:.2})",
+            self.average(),
+            self.min(),
+            self.max(),
+            self.percentile(95.0)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScopeStats {
+    pub name: &'static str,
+    pub depth: u32,
+    pub gpu_ms: RollingStats,
+    pub statistics: Option<PipelineStatistics>, // latest frame only
+}
+
+/**
+ * CPU frame timing and GPU scope timings of the last frames, see Engine::frame_stats.
+ * GPU samples lag the CPU ones by the number of frames in flight.
+ */
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    pub cpu_frame_ms: RollingStats, // between the starts of two draw_frame
+    pub cpu_wait_ms: RollingStats,  // waiting for the frame fence, high when GPU bound
+    pub cpu_record_ms: RollingStats, // recording and submitting the frame
+    pub gpu_scopes: Vec<ScopeStats>, // in order of first appearance
+    capacity: usize,
+}
+
+impl FrameStats {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            cpu_frame_ms: RollingStats::new(capacity),
+            cpu_wait_ms: RollingStats::new(capacity),
+            cpu_record_ms: RollingStats::new(capacity),
+            gpu_scopes: vec![],
+            capacity,
+        }
+    }
+
+    pub fn push_gpu(&mut self, results: &[ScopeResult]) {
+        for result in results {
+            let index = match self
+                .gpu_scopes
+                .iter()
+                .position(|scope| scope.name == result.name && scope.depth == result.depth)
+            {
+                Some(index) => index,
+                None => {
+                    self.gpu_scopes.push(ScopeStats {
+                        name: result.name,
+                        depth: result.depth,
+                        gpu_ms: RollingStats::new(self.capacity),
+                        statistics: None,
+                    });
+                    self.gpu_scopes.len() - 1
+                }
+            };
+            let scope = &mut self.gpu_scopes[index];
+            scope.gpu_ms.push(result.gpu_ms);
+            scope.statistics = result.statistics.or(scope.statistics);
+        }
+    }
+
+    pub fn gpu_scope(&self, name: &str) -> Option<&ScopeStats> {
+        self.gpu_scopes.iter().find(|scope| scope.name == name)
+    }
+}
+
+impl std::fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fps = match self.cpu_frame_ms.average() {
+            ms if ms > 0.0 => 1000.0 / ms,
+            _ => 0.0,
+        };
+        writeln!(f, "cpu frame  {} {:.1} fps", self.cpu_frame_ms, fps)?;
+        writeln!(f, "cpu wait   {}", self.cpu_wait_ms)?;
+        write!(f, "cpu record {}", self.cpu_record_ms)?;
+
+        for scope in &self.gpu_scopes {
+            write!(
+                f,
+                "\ngpu {:indent$}{} {}",
+                "",
+                scope.name,
+                scope.gpu_ms,
+                indent = scope.depth as usize * 2
+            )?;
+            if let Some(statistics) = scope.statistics {
+                write!(
+                    f,
+                    " vs {} clip {}/{} fs {}",
+                    statistics.vertex_shader_invocations,
+                    statistics.clipping_invocations,
+                    statistics.clipping_primitives,
+                    statistics.fragment_shader_invocations
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rolling(capacity: usize, values: &[f32]) -> RollingStats {
+        let mut stats = RollingStats::new(capacity);
+        for &value in values {
+            stats.push(value);
+        }
+        stats
+    }
+
+    #[test]
+    fn rolling_evicts_oldest() {
+        let stats = rolling(3, &[10.0, 1.0, 2.0, 3.0]);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats.last(), Some(3.0));
+        assert_eq!(stats.max(), 3.0);
+    }
+
+    #[test]
+    fn rolling_min_max_average() {
+        let stats = rolling(8, &[4.0, 1.0, 7.0, 4.0]);
+        assert_eq!(stats.min(), 1.0);
+        assert_eq!(stats.max(), 7.0);
+        assert_eq!(stats.average(), 4.0);
+
+        let empty = RollingStats::new(8);
+        assert_eq!((empty.min(), empty.max(), empty.average()), (0.0, 0.0, 0.0));
+        assert_eq!(empty.last(), None);
+    }
+
+    #[test]
+    fn rolling_percentile() {
+        let values: Vec<f32> = (1..=20).rev().map(|value| value as f32).collect();
+        let stats = rolling(20, &values);
+        assert_eq!(stats.percentile(0.0), 1.0);
+        assert_eq!(stats.percentile(50.0), 10.0);
+        assert_eq!(stats.percentile(95.0), 19.0);
+        assert_eq!(stats.percentile(100.0), 20.0);
+        assert_eq!(RollingStats::new(4).percentile(95.0), 0.0);
+    }
+
+    #[test]
+    fn frame_stats_groups_scopes() {
+        let scope = |name, depth, gpu_ms| ScopeResult {
+            name,
+            depth,
+            gpu_ms,
+            statistics: None,
+        };
+        let mut stats = FrameStats::new(2);
+        stats.push_gpu(&[scope("frame", 0, 2.0), scope("meshes", 1, 1.0)]);
+        stats.push_gpu(&[scope("frame", 0, 4.0), scope("meshes", 1, 3.0)]);
+        stats.push_gpu(&[scope("frame", 0, 6.0)]);
+
+        assert_eq!(stats.gpu_scopes.len(), 2);
+        let frame = &stats.gpu_scope("frame").unwrap().gpu_ms;
+        assert_eq!((frame.len(), frame.average()), (2, 5.0));
+        assert_eq!(stats.gpu_scope("meshes").unwrap().gpu_ms.average(), 2.0);
+        assert!(stats.gpu_scope("debug lines").is_none());
+    }
+}
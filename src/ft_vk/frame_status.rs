@@ -0,0 +1,15 @@
+// Outcome of Engine::draw_frame, errors other than these are returned as EngineError
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameStatus {
+    Drawn,
+    Suboptimal, // drawn and presented, the swapchain should be recreated (handle_resize)
+    OutOfDate,  // nothing drawn, the swapchain must be recreated
+    Minimized,  // nothing acquired nor presented until the window has a non zero size
+}
+
+// Outcome of Engine::handle_resize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeStatus {
+    Resized,
+    Minimized, // zero extent, the swapchain is kept and rebuilt once the size is valid
+}
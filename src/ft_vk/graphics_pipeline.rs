@@ -8,6 +8,12 @@ pub struct GraphicsPipelineInfoBuilder<'a> {
     color_blend: vk::PipelineColorBlendStateCreateInfo<'a>,
     color_blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
     depth_stencil: vk::PipelineDepthStencilStateCreateInfo<'a>,
+    viewport_state: vk::PipelineViewportStateCreateInfo<'a>,
+    dynamic_states: Vec<vk::DynamicState>,
+    dynamic_state: vk::PipelineDynamicStateCreateInfo<'a>,
+    color_attachment_formats: Vec<vk::Format>,
+    depth_attachment_format: vk::Format,
+    rendering: Option<vk::PipelineRenderingCreateInfo<'a>>,
 }
 
 impl<'a> GraphicsPipelineInfoBuilder<'a> {
@@ -42,6 +48,15 @@ impl<'a> GraphicsPipelineInfoBuilder<'a> {
             color_blend: vk::PipelineColorBlendStateCreateInfo::default()
                 .logic_op(vk::LogicOp::COPY),
             depth_stencil: vk::PipelineDepthStencilStateCreateInfo::default(),
+            // only counts, the viewport/scissor are set at record time or overridden by the caller
+            viewport_state: vk::PipelineViewportStateCreateInfo::default()
+                .viewport_count(1)
+                .scissor_count(1),
+            dynamic_states: vec![],
+            dynamic_state: vk::PipelineDynamicStateCreateInfo::default(),
+            color_attachment_formats: vec![],
+            depth_attachment_format: vk::Format::UNDEFINED,
+            rendering: None,
         }
     }
 
@@ -54,6 +69,12 @@ impl<'a> GraphicsPipelineInfoBuilder<'a> {
         self
     }
 
+    pub fn set_topology(&mut self, topology: vk::PrimitiveTopology) -> &mut Self {
+        self.input_assembly = self.input_assembly.topology(topology);
+
+        self
+    }
+
     pub fn set_depth_stencil(&mut self) -> &mut Self {
         self.depth_stencil = self
             .depth_stencil
@@ -68,12 +89,61 @@ impl<'a> GraphicsPipelineInfoBuilder<'a> {
         self
     }
 
-    pub fn enable_blending_additive(&mut self) -> &mut Self {
+    // LINE and POINT require the fillModeNonSolid feature
+    pub fn set_polygon_mode(&mut self, polygon_mode: vk::PolygonMode) -> &mut Self {
+        self.rasterization = self.rasterization.polygon_mode(polygon_mode);
+
+        self
+    }
+
+    // must match the render pass attachments, see Engine::msaa_samples
+    pub fn set_samples(&mut self, samples: vk::SampleCountFlags) -> &mut Self {
+        self.multisample = self.multisample.rasterization_samples(samples);
+
+        self
+    }
+
+    // depth test stays enabled, used by transparent draws
+    pub fn disable_depth_write(&mut self) -> &mut Self {
+        self.depth_stencil = self.depth_stencil.depth_write_enable(false);
+
+        self
+    }
+
+    // viewport and scissor set by Engine::draw_frame, the pipeline survives swapchain resizes
+    pub fn enable_dynamic_viewport(&mut self) -> &mut Self {
+        self.dynamic_states
+            .extend([vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+        self
+    }
+
+    // core since Vulkan 1.3 (extended dynamic state), set with cmd_set_cull_mode after binding
+    pub fn enable_dynamic_cull_mode(&mut self) -> &mut Self {
+        self.dynamic_states.push(vk::DynamicState::CULL_MODE);
+
+        self
+    }
+
+    // Attachments for dynamic rendering (render pass left null), ignored when a render pass is set
+    pub fn set_rendering_formats(
+        &mut self,
+        color_format: vk::Format,
+        depth_format: vk::Format,
+    ) -> &mut Self {
+        self.color_attachment_formats = vec![color_format];
+        self.depth_attachment_format = depth_format;
+        self.rendering = Some(vk::PipelineRenderingCreateInfo::default());
+
+        self
+    }
+
+    pub fn enable_blending_alphablend(&mut self) -> &mut Self {
         let color_blend_attachment = vk::PipelineColorBlendAttachmentState {
             color_write_mask: vk::ColorComponentFlags::RGBA,
             blend_enable: vk::TRUE,
             src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
-            dst_color_blend_factor: vk::BlendFactor::ONE,
+            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
             color_blend_op: vk::BlendOp::ADD,
             src_alpha_blend_factor: vk::BlendFactor::ONE,
             dst_alpha_blend_factor: vk::BlendFactor::ZERO,
@@ -89,13 +159,28 @@ impl<'a> GraphicsPipelineInfoBuilder<'a> {
     pub fn build(&'a mut self) -> vk::GraphicsPipelineCreateInfo<'a> {
         // build color_blend
         self.color_blend = self.color_blend.attachments(&self.color_blend_attachments);
+        // build dynamic_state
+        self.dynamic_state = self.dynamic_state.dynamic_states(&self.dynamic_states);
 
-        vk::GraphicsPipelineCreateInfo::default()
+        let info = vk::GraphicsPipelineCreateInfo::default()
             .vertex_input_state(&self.vertex_input_state)
             .input_assembly_state(&self.input_assembly)
             .rasterization_state(&self.rasterization)
             .multisample_state(&self.multisample)
             .color_blend_state(&self.color_blend)
             .depth_stencil_state(&self.depth_stencil)
+            .viewport_state(&self.viewport_state)
+            .dynamic_state(&self.dynamic_state);
+
+        // build rendering
+        match &mut self.rendering {
+            Some(rendering) => {
+                *rendering = rendering
+                    .color_attachment_formats(&self.color_attachment_formats)
+                    .depth_attachment_format(self.depth_attachment_format);
+                info.push_next(rendering)
+            }
+            None => info,
+        }
     }
 }
@@ -1,35 +1,73 @@
 pub mod allocated_buffer;
 pub mod allocated_image;
+pub mod debug;
+pub mod deletion_queue;
+mod error;
+pub use error::EngineError;
 mod frame_data;
+mod frame_stats;
+pub use frame_stats::FrameStats;
+mod frame_status;
+pub use frame_status::{FrameStatus, ResizeStatus};
 mod graphics_pipeline;
+pub mod physical_device;
+mod pipeline_cache;
 mod pipeline_layout;
+mod profiler;
+pub use profiler::{GpuProfiler, ProfileScope};
+pub mod reflection;
+mod screenshot;
 mod shader_module;
 use descriptor_allocator::DescriptorAllocator;
 pub use graphics_pipeline::GraphicsPipelineInfoBuilder;
+pub use pipeline_cache::PipelineCache;
 pub use pipeline_layout::PipelineLayout;
+pub use screenshot::Screenshot;
 pub use shader_module::ShaderModule;
 mod queue_famillies;
 pub use queue_famillies::QueueFamilies;
 
 pub mod descriptor_allocator;
 pub mod descriptor_set_layout;
+pub mod descriptor_write;
+mod present_mode;
+pub use present_mode::{present_mode_name, PresentModePreference};
 mod render_pass;
+mod rendering_path;
+pub use rendering_path::RenderingPath;
 mod surface_support;
 mod swapchain;
+mod unwind;
+pub mod uploader;
+pub use swapchain::DEPTH_FORMAT;
 
 use ash::vk::{self};
-use std::{error::Error, time::Instant};
+use std::time::Instant;
 use vk_mem::Alloc;
 use winit::raw_window_handle::HasRawWindowHandle;
 
+use debug::{DebugConfig, DebugMessenger};
+use deletion_queue::{DeletionQueue, Resource};
 use frame_data::FrameData;
+use physical_device::DeviceOverride;
+use screenshot::ReadbackBuffer;
 use surface_support::SurfaceSupport;
 use swapchain::Swapchain;
+use unwind::Unwind;
+use uploader::{UploadToken, Uploader};
 
 use crate::conf;
 
+// What an Engine renders into, see Engine::new and Engine::new_headless
+#[derive(Clone, Copy)]
+enum EngineTarget<'a> {
+    Window(&'a winit::window::Window), // surface and swapchain
+    Offscreen((u32, u32)),             // single color image of this physical size
+}
+
+// Records draw commands inside the render pass begun by Engine::draw_frame
 pub trait Renderer {
-    unsafe fn render(&self, engine: &Engine, framebuffer: vk::Framebuffer, cmd: vk::CommandBuffer);
+    unsafe fn render(&self, engine: &Engine, cmd: vk::CommandBuffer) -> Result<(), EngineError>;
 }
 
 pub struct Engine {
@@ -38,9 +76,8 @@ pub struct Engine {
 
     pub start_instant: Instant,
 
-    // Debug
-    pub debug_utils_loader: ash::ext::debug_utils::Instance,
-    pub debug_utils_messenger: vk::DebugUtilsMessengerEXT,
+    // Debug, None when validation is disabled or unavailable
+    pub debug_messenger: Option<DebugMessenger>,
 
     // Phyisical device
     pub physical_device: vk::PhysicalDevice,
@@ -58,165 +95,285 @@ pub struct Engine {
     // vkMem
     pub allocator: Option<vk_mem::Allocator>,
 
+    // used by every pipeline creation, saved to disk by destroy
+    pub pipeline_cache: PipelineCache,
+
     // descriptor allocator
     pub descriptor_allocator: DescriptorAllocator,
 
+    // buffer uploads, pending ones are submitted by draw_frame
+    pub uploader: Uploader,
+
     // Swapchain
     pub frames: Vec<FrameData>,
 
     pub swapchain_loader: ash::khr::swapchain::Device,
     pub swapchain: swapchain::Swapchain,
+    pub present_mode: PresentModePreference, // see swapchain.present_mode for the active one
 
-    pub render_pass: vk::RenderPass,
-    pub framebuffers: Vec<vk::Framebuffer>,
+    pub rendering_path: RenderingPath,
+    pub render_pass: vk::RenderPass, // null with RenderingPath::Dynamic
+    pub framebuffers: Vec<vk::Framebuffer>, // empty with RenderingPath::Dynamic
+    pub msaa_samples: vk::SampleCountFlags, // shared by the render pass and every pipeline
+    pub fill_mode_non_solid: bool, // PolygonMode::LINE/POINT available
 
-    pub frame_count: usize,
-}
+    // released once the frames that may use them are complete, see destroy_later
+    deletion_queue: DeletionQueue,
 
-impl Engine {
-    pub fn new(entry: ash::Entry, window: &winit::window::Window) -> Self {
-        // Instance
-        let instance = create_instance(&entry);
-        let (debug_utils_loader, debug_utils_messenger) = setup_debug_utils(&entry, &instance);
+    // size requested while the surface extent is zero, no frame is drawn until it's valid
+    minimized_size: Option<(u32, u32)>,
 
-        // Physical device
-        let physical_device = get_physical_device(&instance);
+    // copy of the next presented image, see request_screenshot
+    screenshot_requested: bool,
+    screenshot: Option<Screenshot>,
 
-        // Window
-        let window_physical_size = window.inner_size();
+    // gpu scopes of each frame, read back with the cpu timings into frame_stats
+    pub profiler: GpuProfiler,
+    frame_stats: FrameStats,
+    last_frame_start: Option<Instant>,
 
-        #[cfg(target_os = "windows")]
-        let surface = {
-            let window_info = {
-                use winit::raw_window_handle::HasWindowHandle;
-
-                let hwnd = match window.window_handle().unwrap().as_raw() {
-                    winit::raw_window_handle::RawWindowHandle::Win32(handle) => handle.hwnd.get(),
-                    _ => panic!("Unsupported platform!"),
-                };
-
-                let hinstance = {
-                    let hmodule = unsafe {
-                        windows::Win32::System::LibraryLoader::GetModuleHandleW(None).unwrap()
-                    };
+    pub frame_count: usize,
+}
 
-                    if hmodule.is_invalid() {
-                        panic!("Unsupported windows hinstance")
-                    }
+impl Engine {
+    pub fn new(
+        entry: ash::Entry,
+        window: &winit::window::Window,
+        debug_config: DebugConfig,
+        rendering_path: RenderingPath,
+        present_mode: PresentModePreference,
+    ) -> Result<Self, EngineError> {
+        Self::build(
+            entry,
+            EngineTarget::Window(window),
+            debug_config,
+            rendering_path,
+            present_mode,
+        )
+    }
 
-                    hmodule.0
-                };
+    // No window, surface or swapchain: frames are rendered into an offscreen image (see read_back)
+    pub fn new_headless(
+        entry: ash::Entry,
+        physical_size: (u32, u32),
+        debug_config: DebugConfig,
+        rendering_path: RenderingPath,
+    ) -> Result<Self, EngineError> {
+        Self::build(
+            entry,
+            EngineTarget::Offscreen(physical_size),
+            debug_config,
+            rendering_path,
+            PresentModePreference::Vsync, // nothing is presented
+        )
+    }
 
-                vk::Win32SurfaceCreateInfoKHR::default()
-                    .hwnd(hwnd as vk::HWND)
-                    .hinstance(hinstance as vk::HINSTANCE)
+    fn build(
+        entry: ash::Entry,
+        target: EngineTarget,
+        debug_config: DebugConfig,
+        rendering_path: RenderingPath,
+        present_mode: PresentModePreference,
+    ) -> Result<Self, EngineError> {
+        let (extension_names, device_extension_names): (&[*const i8], &[&std::ffi::CStr]) =
+            match target {
+                EngineTarget::Window(_) => (&conf::EXTENSION_NAMES, &conf::DEVICE_EXTENSION_NAMES),
+                EngineTarget::Offscreen(_) => (
+                    &conf::HEADLESS_EXTENSION_NAMES,
+                    &conf::HEADLESS_DEVICE_EXTENSION_NAMES,
+                ),
             };
 
-            let win_surface_loader = ash::khr::win32_surface::Instance::new(&entry, &instance);
-            unsafe { win_surface_loader.create_win32_surface(&window_info, None) }.unwrap()
-        };
-
-        #[cfg(target_os = "linux")]
-        let surface = {
-            use winit::raw_window_handle::HasDisplayHandle;
-            use winit::raw_window_handle::HasWindowHandle;
+        // Every object is destroyed in reverse order when a step fails, see Unwind
 
-            let window_handle = window.window_handle().unwrap().as_raw();
-            let display_handle = window.display_handle().unwrap().as_raw();
-            match (window_handle, display_handle) {
-                // Xlib
-                (
-                    winit::raw_window_handle::RawWindowHandle::Xlib(window_handle),
-                    winit::raw_window_handle::RawDisplayHandle::Xlib(display_handle),
-                ) => {
-                    let window = window_handle.window;
-                    let display = display_handle
-                        .display
-                        .map(|d| d.as_ptr())
-                        .unwrap_or(std::ptr::null_mut());
-
-                    let window_info = vk::XlibSurfaceCreateInfoKHR::default()
-                        .window(window)
-                        .dpy(display);
-                    let surface_loader = ash::khr::xlib_surface::Instance::new(&entry, &instance);
-                    unsafe { surface_loader.create_xlib_surface(&window_info, None) }.unwrap()
-                }
-                // Wayland
-                (
-                    winit::raw_window_handle::RawWindowHandle::Wayland(window_handle),
-                    winit::raw_window_handle::RawDisplayHandle::Wayland(display_handle),
-                ) => {
-                    let surface = window_handle.surface.as_ptr();
-                    let display = display_handle.display.as_ptr();
-
-                    let window_info = vk::WaylandSurfaceCreateInfoKHR::default()
-                        .surface(surface)
-                        .display(display);
-                    let surface_loader =
-                        ash::khr::wayland_surface::Instance::new(&entry, &instance);
-                    unsafe { surface_loader.create_wayland_surface(&window_info, None) }.unwrap()
-                }
-                _ => panic!("Unsupported platform!"),
+        // Instance
+        let (instance, debug_utils) = create_instance(&entry, extension_names, &debug_config)?;
+        let instance = Unwind::new(instance, |instance| unsafe {
+            instance.destroy_instance(None)
+        });
+        let debug_messenger = debug_utils
+            .then(|| DebugMessenger::new(&entry, &instance, &debug_config))
+            .transpose()?;
+        let debug_messenger = Unwind::new(debug_messenger, |debug_messenger| {
+            if let Some(debug_messenger) = debug_messenger {
+                unsafe { debug_messenger.destroy() };
             }
-        };
+        });
 
-        // Surface
+        // Surface, null when offscreen
         let surface_loader = ash::khr::surface::Instance::new(&entry, &instance);
-        let surface_support = SurfaceSupport::new(physical_device, surface, &surface_loader);
-        if !surface_support.is_physical_device_compatible(&instance, physical_device) {
-            panic!("physical_device invalid")
-        }
+        let surface = match target {
+            EngineTarget::Window(window) => create_surface(&entry, &instance, window)?,
+            EngineTarget::Offscreen(_) => vk::SurfaceKHR::null(),
+        };
+        let surface = Unwind::new(surface, |surface| {
+            if surface != vk::SurfaceKHR::null() {
+                unsafe { surface_loader.destroy_surface(surface, None) };
+            }
+        });
+        let presentation =
+            (*surface != vk::SurfaceKHR::null()).then_some((&surface_loader, *surface));
+
+        // Physical device
+        let physical_device = physical_device::select(
+            &instance,
+            presentation,
+            device_extension_names,
+            conf::get_physical_device_override().map(|value| DeviceOverride::parse(&value)),
+        )?;
+
+        let rendering_path = rendering_path.resolve(physical_device::supports_dynamic_rendering(
+            &instance,
+            physical_device,
+        ));
 
         // Device
-        let (device, queue_families) =
-            create_device(&instance, physical_device, &surface_loader, surface).unwrap();
+        let queue_families = match presentation {
+            Some((surface_loader, surface)) => {
+                QueueFamilies::new(&instance, physical_device, surface_loader, surface)?
+            }
+            None => QueueFamilies::new_headless(&instance, physical_device)?,
+        };
+        let features = unsafe { instance.get_physical_device_features(physical_device) };
+        let fill_mode_non_solid = features.fill_mode_non_solid == vk::TRUE;
+        let pipeline_statistics =
+            conf::PROFILER_PIPELINE_STATISTICS && features.pipeline_statistics_query == vk::TRUE;
+        let device = create_device(
+            &instance,
+            physical_device,
+            &queue_families,
+            fill_mode_non_solid,
+            pipeline_statistics,
+            rendering_path == RenderingPath::Dynamic,
+            device_extension_names,
+        )?;
+        let device = Unwind::new(device, |device| unsafe { device.destroy_device(None) });
         let graphics_queue = unsafe { device.get_device_queue(queue_families.graphics, 0) };
         let present_queue = unsafe { device.get_device_queue(queue_families.present, 0) };
 
         // Allocator
-        let allocator = create_allocator(&instance, &device, physical_device);
+        let allocator = Unwind::new(
+            create_allocator(&instance, &device, physical_device)?,
+            drop, // vmaDestroyAllocator
+        );
+        let pipeline_cache = Unwind::new(
+            PipelineCache::new(&instance, &device, physical_device)?,
+            |pipeline_cache| unsafe { pipeline_cache.destroy(&device) },
+        );
+        let msaa_samples =
+            physical_device::clamp_sample_count(&instance, physical_device, conf::MSAA_SAMPLES);
 
         let descriptor_allocator = DescriptorAllocator::new(
-            1,
-            vec![vk::DescriptorPoolSize::default()
-                .ty(vk::DescriptorType::STORAGE_BUFFER)
-                .descriptor_count(1)],
+            conf::DESCRIPTOR_POOL_INITIAL_SETS,
+            conf::DESCRIPTOR_POOL_MAX_SETS,
+            &conf::DESCRIPTOR_POOL_RATIOS,
         );
-
-        // Swapchain
-        let swapchain_loader = ash::khr::swapchain::Device::new(&instance, &device);
-        let swapchain = Swapchain::new(
-            &swapchain_loader,
+        let uploader = Uploader::new(
             &device,
             &allocator,
-            (window_physical_size.width, window_physical_size.height),
-            &surface_support,
-            surface,
             &queue_families,
-            None,
-        );
+            graphics_queue,
+            conf::UPLOAD_STAGING_SIZE,
+        )?;
+        let uploader = Unwind::new(uploader, |mut uploader| {
+            uploader.destroy(&device, &allocator)
+        });
 
-        let frames = create_present_frames(
+        // Swapchain, or the offscreen image
+        let swapchain_loader = ash::khr::swapchain::Device::new(&instance, &device);
+        let (swapchain, frame_count) = match target {
+            EngineTarget::Window(window) => {
+                let window_physical_size = window.inner_size();
+                let surface_support =
+                    SurfaceSupport::new(physical_device, *surface, &surface_loader)?;
+                let swapchain = Swapchain::new(
+                    &swapchain_loader,
+                    &device,
+                    &allocator,
+                    (window_physical_size.width, window_physical_size.height),
+                    &surface_support,
+                    *surface,
+                    &queue_families,
+                    None,
+                    msaa_samples,
+                    present_mode,
+                )?;
+                (swapchain, conf::MAX_FRAMES_IN_FLIGHT)
+            }
+            EngineTarget::Offscreen(physical_size) => {
+                let swapchain = Swapchain::new_offscreen(
+                    &device,
+                    &allocator,
+                    physical_size,
+                    msaa_samples,
+                    conf::HEADLESS_FORMAT,
+                )?;
+                // single offscreen image, frames can't overlap
+                (swapchain, 1)
+            }
+        };
+        let swapchain = Unwind::new(swapchain, |mut swapchain| {
+            swapchain.destroy(&device, &allocator, &swapchain_loader)
+        });
+
+        let frames = Unwind::new(
+            create_present_frames(&device, queue_families.graphics, frame_count)?,
+            |frames| {
+                for frame in frames {
+                    frame.destroy(&device);
+                }
+            },
+        );
+        let profiler = GpuProfiler::new(
+            &instance,
             &device,
+            physical_device,
             queue_families.graphics,
-            swapchain.min_image_count as usize,
+            frames.len(),
+            conf::PROFILER_MAX_SCOPES,
+            pipeline_statistics,
+        )?;
+        let profiler = Unwind::new(profiler, |mut profiler| unsafe {
+            profiler.destroy(&device)
+        });
+        let render_targets = Unwind::new(
+            create_render_targets(&device, &swapchain, msaa_samples, rendering_path)?,
+            |(render_pass, framebuffers)| unsafe {
+                for framebuffer in framebuffers {
+                    device.destroy_framebuffer(framebuffer, None);
+                }
+                device.destroy_render_pass(render_pass, None);
+            },
         );
-        let render_pass = render_pass::create_default(&device, swapchain.surface_format.format);
-        let framebuffers = swapchain.get_framebuffers(&device, render_pass);
 
-        Self {
+        // built, Engine::teardown destroys them from now on
+        let (render_pass, framebuffers) = render_targets.into_inner();
+        let profiler = profiler.into_inner();
+        let frames = frames.into_inner();
+        let swapchain = swapchain.into_inner();
+        let uploader = uploader.into_inner();
+        let pipeline_cache = pipeline_cache.into_inner();
+        let allocator = allocator.into_inner();
+        let device = device.into_inner();
+        let surface = surface.into_inner();
+        let debug_messenger = debug_messenger.into_inner();
+        let instance = instance.into_inner();
+
+        Ok(Self {
             entry,
             instance,
             device,
 
             allocator: Some(allocator),
+            pipeline_cache,
             descriptor_allocator,
+            uploader,
 
             swapchain_loader,
             swapchain,
+            present_mode,
 
-            debug_utils_loader,
-            debug_utils_messenger,
+            debug_messenger,
 
             surface_loader,
             surface,
@@ -226,72 +383,201 @@ impl Engine {
             graphics_queue,
             present_queue,
 
+            rendering_path,
             render_pass,
             framebuffers,
+            msaa_samples,
+            fill_mode_non_solid,
+
+            deletion_queue: DeletionQueue::default(),
+            minimized_size: None,
+
+            screenshot_requested: false,
+            screenshot: None,
+
+            profiler,
+            frame_stats: FrameStats::new(conf::PROFILER_HISTORY),
+            last_frame_start: None,
 
             frames,
             frame_count: 0,
 
             start_instant: Instant::now(),
-        }
+        })
     }
 
-    pub unsafe fn draw_frame(&mut self, renderer: &impl Renderer) -> Result<(), vk::Result> {
+    pub unsafe fn draw_frame(
+        &mut self,
+        renderers: &[&dyn Renderer],
+    ) -> Result<FrameStatus, EngineError> {
+        // swapchain rebuilt lazily, once the surface has a size again
+        if let Some(size) = self.minimized_size {
+            if self.handle_resize(size)? == ResizeStatus::Minimized {
+                return Ok(FrameStatus::Minimized);
+            }
+        }
+
+        let frame_start = Instant::now();
+        if let Some(last_frame_start) = self.last_frame_start.replace(frame_start) {
+            self.frame_stats
+                .cpu_frame_ms
+                .push(duration_ms(frame_start - last_frame_start));
+        }
+
         self.frame_count += 1;
         let FrameData {
             command_buffer: cmd,
             fence,
             present_semaphore,
-            render_semaphore,
             ..
-        } = self.frames[self.frame_count % self.swapchain.min_image_count as usize];
+        } = self.frames[self.frame_index()];
+
+        self.device.wait_for_fences(&[fence], true, u64::MAX)?;
+        let record_start = Instant::now();
+        self.frame_stats
+            .cpu_wait_ms
+            .push(duration_ms(record_start - frame_start));
+        // queries of the previous use of this frame are available, no stall
+        let frame_index = self.frame_index();
+        let scopes = self.profiler.collect(&self.device, frame_index)?;
+        self.frame_stats.push_gpu(&scopes);
+        // sets of the previous use of this frame are no longer read by the gpu
+        self.frames[frame_index]
+            .descriptor_allocator
+            .get_mut()
+            .reset_pools(&self.device)?;
+        // uploads recorded since the last frame are ordered before this frame's commands
+        self.uploader
+            .retire_completed(&self.device, self.allocator.as_ref().unwrap())?;
+        self.uploader.submit(&self.device)?;
+        // the fence of this FrameData was last signaled by frame_count - frames.len()
+        for resource in self
+            .deletion_queue
+            .drain_completed(self.frame_count, self.frames.len())
+        {
+            self.release(resource);
+        }
 
-        self.device
-            .wait_for_fences(&[fence], true, u64::MAX)
-            .unwrap();
+        let offscreen = self.swapchain.is_offscreen();
 
-        let swapchain_image_idx = self
-            .swapchain_loader
-            .acquire_next_image(
+        let (swapchain_image_idx, acquire_suboptimal) = if offscreen {
+            (0, false)
+        } else {
+            match self.swapchain_loader.acquire_next_image(
                 self.swapchain.chain,
                 u64::MAX,
                 present_semaphore,
                 vk::Fence::null(),
-            )?
-            .0;
+            ) {
+                Ok(acquired) => acquired,
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return Ok(FrameStatus::OutOfDate),
+                Err(error) => return Err(error.into()),
+            }
+        };
 
-        let framebuffer = self.framebuffers[swapchain_image_idx as usize];
+        // image may still be rendered by another frame in flight (acquire order != frame order)
+        let image_fence = self.swapchain.images_in_flight[swapchain_image_idx as usize];
+        if image_fence != vk::Fence::null() && image_fence != fence {
+            self.device
+                .wait_for_fences(&[image_fence], true, u64::MAX)?;
+        }
+        self.swapchain.images_in_flight[swapchain_image_idx as usize] = fence;
+        let render_semaphore = if offscreen {
+            vk::Semaphore::null()
+        } else {
+            self.swapchain.render_semaphores[swapchain_image_idx as usize]
+        };
 
         self.device
-            .reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())
-            .unwrap();
+            .reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())?;
 
         //RECORD
         let cmd_begin_info = vk::CommandBufferBeginInfo::default()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-        self.device
-            .begin_command_buffer(cmd, &cmd_begin_info)
-            .unwrap();
-
-        //RENDERER
-        renderer.render(&self, framebuffer, cmd);
-
-        self.device.end_command_buffer(cmd).unwrap();
+        self.device.begin_command_buffer(cmd, &cmd_begin_info)?;
+        self.profiler.begin_frame(&self.device, cmd, frame_index);
+        let frame_scope = self.profiler.begin_scope(&self.device, cmd, "frame");
+
+        match self.rendering_path {
+            RenderingPath::RenderPass => self.record_render_pass(
+                cmd,
+                self.render_pass,
+                self.framebuffers[swapchain_image_idx as usize],
+                self.swapchain.extent,
+                renderers,
+            ),
+            RenderingPath::Dynamic => self.record_dynamic_rendering(
+                cmd,
+                &self.swapchain,
+                swapchain_image_idx as usize,
+                renderers,
+            ),
+        }?;
+        self.profiler.end_scope(&self.device, cmd, frame_scope);
+        self.profiler.end_frame();
+
+        // SCREENSHOT, the swapchain image is copied once the render pass resolved into it
+        let readback = (self.screenshot_requested && self.swapchain.transfer_src)
+            .then(|| {
+                let readback =
+                    ReadbackBuffer::new(self.allocator.as_ref().unwrap(), self.swapchain.extent)?;
+                readback.record_copy(
+                    &self.device,
+                    cmd,
+                    self.swapchain.images[swapchain_image_idx as usize],
+                    self.swapchain.present_layout(),
+                );
+                Ok::<_, EngineError>(readback)
+            })
+            .transpose()?;
+        self.screenshot_requested = false;
+
+        self.device.end_command_buffer(cmd)?;
 
         //SUBMIT
-        self.device.reset_fences(&[fence]).unwrap();
+        self.device.reset_fences(&[fence])?;
 
         let command_buffers = [cmd];
         let present_semaphores = [present_semaphore];
         let render_semaphores = [render_semaphore];
-        let submit_info = vk::SubmitInfo::default()
-            .command_buffers(&command_buffers)
-            .wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
-            .wait_semaphores(&present_semaphores)
-            .signal_semaphores(&render_semaphores);
+        let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+        let submit_info = if offscreen {
+            submit_info
+        } else {
+            submit_info
+                .wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
+                .wait_semaphores(&present_semaphores)
+                .signal_semaphores(&render_semaphores)
+        };
         self.device
-            .queue_submit(self.graphics_queue, &[submit_info], fence)
-            .unwrap();
+            .queue_submit(self.graphics_queue, &[submit_info], fence)?;
+        self.frame_stats
+            .cpu_record_ms
+            .push(duration_ms(record_start.elapsed()));
+
+        // raise validation errors reported while recording/submitting
+        if let Some(debug_messenger) = &self.debug_messenger {
+            debug_messenger.check();
+        }
+
+        if let Some(readback) = readback {
+            // stalls this frame only, screenshots are rare
+            self.device.wait_for_fences(&[fence], true, u64::MAX)?;
+            let allocator = self.allocator.as_ref().unwrap();
+            let extent = self.swapchain.extent;
+            let screenshot = Screenshot::from_pixels(
+                extent.width,
+                extent.height,
+                &readback.read(allocator),
+                self.swapchain.surface_format.format,
+            );
+            readback.destroy(allocator);
+            self.screenshot = Some(screenshot?);
+        }
+
+        if offscreen {
+            return Ok(FrameStatus::Drawn);
+        }
 
         //PRESENTATION
         let swapchains = [self.swapchain.chain];
@@ -300,16 +586,367 @@ impl Engine {
             .swapchains(&swapchains)
             .wait_semaphores(&render_semaphores)
             .image_indices(&image_indices);
-        self.swapchain_loader
-            .queue_present(self.graphics_queue, &present_info)?;
+        match self
+            .swapchain_loader
+            .queue_present(self.graphics_queue, &present_info)
+        {
+            Ok(present_suboptimal) if present_suboptimal || acquire_suboptimal => {
+                Ok(FrameStatus::Suboptimal)
+            }
+            Ok(_) => Ok(FrameStatus::Drawn),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(FrameStatus::OutOfDate),
+            Err(error) => Err(error.into()),
+        }
+    }
 
-        Ok(())
+    // Begin render_pass (cleared), set the dynamic viewport/scissor to extent and run the renderers
+    unsafe fn record_render_pass(
+        &self,
+        cmd: vk::CommandBuffer,
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        renderers: &[&dyn Renderer],
+    ) -> Result<(), EngineError> {
+        //RENDER PASS
+        let color_clear_value = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0f32, 0.0f32, 0.0f32, 1.0f32],
+            },
+        };
+        let depth_clear_value = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0f32,
+                stencil: 0,
+            },
+        };
+        let clear_values = [color_clear_value, depth_clear_value];
+
+        let renderpass_info = vk::RenderPassBeginInfo::default()
+            .render_pass(render_pass)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            })
+            .framebuffer(framebuffer)
+            .clear_values(&clear_values);
+        self.device
+            .cmd_begin_render_pass(cmd, &renderpass_info, vk::SubpassContents::INLINE);
+
+        // pipelines use a dynamic viewport/scissor, see GraphicsPipelineInfoBuilder::enable_dynamic_viewport
+        let (viewports, scissors) = crate::helpers::default_viewports_and_scissors(extent);
+        self.device.cmd_set_viewport(cmd, 0, &viewports);
+        self.device.cmd_set_scissor(cmd, 0, &scissors);
+
+        //RENDERERS, the pass is ended even when one of them fails
+        let rendered = renderers
+            .iter()
+            .try_for_each(|renderer| renderer.render(self, cmd));
+
+        self.device.cmd_end_render_pass(cmd);
+
+        rendered
+    }
+
+    /**
+     * Same as record_render_pass with vkCmdBeginRendering on target's image_index attachments.
+     * Attachments are cleared from UNDEFINED, the color image ends in target.present_layout().
+     */
+    unsafe fn record_dynamic_rendering(
+        &self,
+        cmd: vk::CommandBuffer,
+        target: &Swapchain,
+        image_index: usize,
+        renderers: &[&dyn Renderer],
+    ) -> Result<(), EngineError> {
+        let image = target.images[image_index];
+        let image_view = target.image_views[image_index];
+        let msaa_color_image = target.msaa_color_images.get(image_index);
+        let depth_image = &target.depth_images[image_index];
+
+        let color_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(1)
+            .layer_count(1);
+        let depth_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::DEPTH)
+            .level_count(1)
+            .layer_count(1);
+
+        //LAYOUTS, previous contents are discarded
+        let to_color_attachment = |image: vk::Image| {
+            vk::ImageMemoryBarrier::default()
+                .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(color_range)
+        };
+        let mut color_barriers = vec![to_color_attachment(image)];
+        if let Some(msaa_color_image) = msaa_color_image {
+            color_barriers.push(to_color_attachment(msaa_color_image.image));
+        }
+        self.device.cmd_pipeline_barrier(
+            cmd,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &color_barriers,
+        );
+        let depth_barrier = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_access_mask(
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            )
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(depth_image.image)
+            .subresource_range(depth_range);
+        let depth_stages = vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+        self.device.cmd_pipeline_barrier(
+            cmd,
+            depth_stages,
+            depth_stages,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[depth_barrier],
+        );
+
+        //RENDERING, same clears and resolve as render_pass::create_default
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .clear_value(vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0f32, 0.0f32, 0.0f32, 1.0f32],
+                },
+            });
+        let color_attachment = match msaa_color_image {
+            // resolved at the end of the rendering, samples are discarded
+            Some(msaa_color_image) => color_attachment
+                .image_view(msaa_color_image.image_view)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                .resolve_image_view(image_view)
+                .resolve_image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+            None => color_attachment
+                .image_view(image_view)
+                .store_op(vk::AttachmentStoreOp::STORE),
+        };
+        let depth_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(depth_image.image_view)
+            .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .clear_value(vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0f32,
+                    stencil: 0,
+                },
+            });
+        let color_attachments = [color_attachment];
+        let rendering_info = vk::RenderingInfo::default()
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: target.extent,
+            })
+            .layer_count(1)
+            .color_attachments(&color_attachments)
+            .depth_attachment(&depth_attachment);
+        self.device.cmd_begin_rendering(cmd, &rendering_info);
+
+        let (viewports, scissors) = crate::helpers::default_viewports_and_scissors(target.extent);
+        self.device.cmd_set_viewport(cmd, 0, &viewports);
+        self.device.cmd_set_scissor(cmd, 0, &scissors);
+
+        //RENDERERS, the rendering is ended even when one of them fails
+        let rendered = renderers
+            .iter()
+            .try_for_each(|renderer| renderer.render(self, cmd));
+
+        self.device.cmd_end_rendering(cmd);
+
+        // presented or copied, dst stage chains with ReadbackBuffer::record_copy
+        let to_present = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .new_layout(target.present_layout())
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(color_range);
+        self.device.cmd_pipeline_barrier(
+            cmd,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_present],
+        );
+
+        rendered
+    }
+
+    // index of the FrameData used by the current (or next) draw_frame
+    pub fn frame_index(&self) -> usize {
+        self.frame_count % self.frames.len()
+    }
+
+    /**
+     * Descriptor set valid for the current frame only, allocated from its transient allocator.
+     * Usable while recording (Renderer::render), reset once the frame fence is waited again.
+     */
+    pub fn allocate_frame_descriptor_set(
+        &self,
+        layout: vk::DescriptorSetLayout,
+    ) -> Result<vk::DescriptorSet, EngineError> {
+        self.frames[self.frame_index()]
+            .descriptor_allocator
+            .borrow_mut()
+            .allocate_descriptor_set(&self.device, layout)
+    }
+
+    /**
+     * Timestamp a named scope of cmd (and its pipeline statistics when outermost), see GpuProfiler.
+     * Only recorded while draw_frame records, results land in frame_stats frames in flight later.
+     */
+    pub unsafe fn begin_profile_scope(
+        &self,
+        cmd: vk::CommandBuffer,
+        name: &'static str,
+    ) -> ProfileScope {
+        self.profiler.begin_scope(&self.device, cmd, name)
+    }
+
+    pub unsafe fn end_profile_scope(&self, cmd: vk::CommandBuffer, scope: ProfileScope) {
+        self.profiler.end_scope(&self.device, cmd, scope)
+    }
+
+    // CPU timings and GPU scopes of the last conf::PROFILER_HISTORY frames
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    // Record a copy of data into dst (TRANSFER_DST usage), executed by the next submit_uploads or draw_frame
+    pub fn upload_buffer(
+        &mut self,
+        dst: vk::Buffer,
+        dst_offset: vk::DeviceSize,
+        data: &[u8],
+    ) -> Result<(), EngineError> {
+        self.uploader.upload_buffer(
+            &self.device,
+            self.allocator.as_ref().unwrap(),
+            dst,
+            dst_offset,
+            data,
+        )
+    }
+
+    // Submit the recorded uploads without waiting, see is_upload_complete and wait_upload
+    pub fn submit_uploads(&mut self) -> Result<UploadToken, EngineError> {
+        self.uploader.submit(&self.device)
+    }
+
+    pub fn is_upload_complete(&mut self, token: UploadToken) -> Result<bool, EngineError> {
+        self.uploader
+            .is_complete(&self.device, self.allocator.as_ref().unwrap(), token)
+    }
+
+    pub fn wait_upload(&mut self, token: UploadToken) -> Result<(), EngineError> {
+        self.uploader
+            .wait(&self.device, self.allocator.as_ref().unwrap(), token)
+    }
+
+    /**
+     * Destroy resource once every frame submitted so far is complete, without stalling.
+     * Resources used after this call (e.g. by the next draw_frame) must not be enqueued yet.
+     */
+    pub fn destroy_later(&mut self, resource: impl Into<Resource>) {
+        self.deletion_queue.push(self.frame_count, resource.into());
+    }
+
+    // Wait for the device then release every enqueued resource
+    pub unsafe fn flush_deletion_queue(&mut self) {
+        // fails once the device is lost, nothing executes anymore
+        let _ = self.device.device_wait_idle();
+        for resource in self.deletion_queue.drain_all() {
+            self.release(resource);
+        }
+    }
+
+    unsafe fn release(&mut self, resource: Resource) {
+        let allocator = self.allocator.as_ref().unwrap();
+        match resource {
+            Resource::Buffer(mut buffer) => {
+                allocator.destroy_buffer(buffer.buffer, &mut buffer.allocation)
+            }
+            Resource::Image(mut image) => {
+                self.device.destroy_image_view(image.image_view, None);
+                allocator.destroy_image(image.image, &mut image.allocation);
+            }
+            Resource::Pipeline(pipeline) => self.device.destroy_pipeline(pipeline, None),
+            Resource::PipelineLayout(layout) => self.device.destroy_pipeline_layout(layout, None),
+            Resource::DescriptorSet(set) => self
+                .descriptor_allocator
+                .free_descriptor_set(&self.device, set),
+            Resource::Framebuffer(framebuffer) => {
+                self.device.destroy_framebuffer(framebuffer, None)
+            }
+            Resource::RenderPass(render_pass) => self.device.destroy_render_pass(render_pass, None),
+            Resource::Swapchain(mut swapchain) => {
+                swapchain.destroy(&self.device, allocator, &self.swapchain_loader)
+            }
+        }
     }
 
     pub unsafe fn destroy(mut self) {
-        for frame in self.frames {
+        self.teardown();
+    }
+
+    /**
+     * Destroy then create the whole engine again with the same settings, after EngineError::DeviceLost.
+     * Resources created with the previous device must be destroyed (or destroy_later) before.
+     * On error the engine is left torn down and must not be used or destroyed.
+     */
+    pub unsafe fn recreate(
+        &mut self,
+        window: &winit::window::Window,
+        debug_config: DebugConfig,
+    ) -> Result<(), EngineError> {
+        let msaa_samples = self.msaa_samples;
+        self.teardown();
+
+        *self = Engine::new(
+            self.entry.clone(),
+            window,
+            debug_config,
+            self.rendering_path,
+            self.present_mode,
+        )?;
+        self.set_msaa_samples(msaa_samples.as_raw())?;
+        log::info!("Engine recreated");
+
+        Ok(())
+    }
+
+    unsafe fn teardown(&mut self) {
+        self.flush_deletion_queue();
+
+        for frame in self.frames.drain(..) {
             frame.destroy(&self.device);
         }
+        self.profiler.destroy(&self.device);
 
         for &framebuffer in &self.framebuffers {
             self.device.destroy_framebuffer(framebuffer, None);
@@ -325,22 +962,231 @@ impl Engine {
         );
 
         self.descriptor_allocator.destroy_pools(&self.device);
+        self.uploader
+            .destroy(&self.device, self.allocator.as_ref().unwrap());
+        self.pipeline_cache.destroy(&self.device);
         self.allocator = None; //vmaDestroyAllocator(_allocator);
 
         self.device.destroy_device(None);
 
-        self.surface_loader.destroy_surface(self.surface, None);
-        self.debug_utils_loader
-            .destroy_debug_utils_messenger(self.debug_utils_messenger, None);
+        if self.surface != vk::SurfaceKHR::null() {
+            self.surface_loader.destroy_surface(self.surface, None);
+        }
+        if let Some(debug_messenger) = self.debug_messenger.take() {
+            debug_messenger.destroy();
+        }
 
         self.instance.destroy_instance(None);
     }
 
-    pub unsafe fn handle_resize(&mut self, physical_size: (u32, u32)) {
-        let new_swapchain = {
+    /**
+     * Copy the offscreen color image into host memory, RGBA8 (sRGB encoded) rows without padding.
+     * Only for an engine created with Engine::new_headless.
+     */
+    pub unsafe fn read_back(&self) -> Result<Vec<u8>, EngineError> {
+        assert!(
+            self.swapchain.is_offscreen(),
+            "read_back needs Engine::new_headless"
+        );
+
+        // last frame must be done before copying
+        self.device.wait_for_fences(
+            &self.frames.iter().map(|f| f.fence).collect::<Vec<_>>(),
+            true,
+            u64::MAX,
+        )?;
+
+        // own pool and fence, the frame pools are reset by draw_frame
+        let allocator = self.allocator.as_ref().unwrap();
+        let command_pool = {
+            let info = vk::CommandPoolCreateInfo::default()
+                .queue_family_index(self.queue_families.graphics)
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT);
+            Unwind::new(self.device.create_command_pool(&info, None)?, |pool| {
+                self.device.destroy_command_pool(pool, None)
+            })
+        };
+        let fence = Unwind::new(
+            self.device
+                .create_fence(&vk::FenceCreateInfo::default(), None)?,
+            |fence| self.device.destroy_fence(fence, None),
+        );
+        let readback = Unwind::new(
+            ReadbackBuffer::new(allocator, self.swapchain.extent)?,
+            |readback| readback.destroy(allocator),
+        );
+
+        // RECORD, freed with the pool
+        let cmd = {
+            let info = vk::CommandBufferAllocateInfo::default()
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_pool(*command_pool)
+                .command_buffer_count(1);
+            self.device.allocate_command_buffers(&info)?[0]
+        };
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        self.device.begin_command_buffer(cmd, &begin_info)?;
+        readback.record_copy(
+            &self.device,
+            cmd,
+            self.swapchain.images[0],
+            self.swapchain.present_layout(),
+        );
+        self.device.end_command_buffer(cmd)?;
+
+        // SUBMIT, only the copy is waited for
+        let command_buffers = [cmd];
+        let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+        self.device
+            .queue_submit(self.graphics_queue, &[submit_info], *fence)?;
+        self.device.wait_for_fences(&[*fence], true, u64::MAX)?;
+
+        Ok(readback.read(allocator))
+    }
+
+    // The next draw_frame copies its image, get it with take_screenshot
+    pub fn request_screenshot(&mut self) {
+        if !self.swapchain.transfer_src {
+            log::warn!("Screenshot unavailable: swapchain images can't be copied from");
+            return;
+        }
+        if !Screenshot::supports_format(self.swapchain.surface_format.format) {
+            log::warn!(
+                "Screenshot unavailable: unsupported swapchain format {:?}",
+                self.swapchain.surface_format.format
+            );
+            return;
+        }
+        self.screenshot_requested = true;
+    }
+
+    pub fn take_screenshot(&mut self) -> Option<Screenshot> {
+        self.screenshot.take()
+    }
+
+    /**
+     * Render a single frame offscreen at physical_size (e.g. a multiple of the window size) and copy it.
+     * The attachments use the swapchain format and sample count so the material pipelines stay compatible;
+     * the renderers must have been built for the same aspect ratio.
+     */
+    pub unsafe fn render_screenshot(
+        &mut self,
+        renderers: &[&dyn Renderer],
+        physical_size: (u32, u32),
+    ) -> Result<Screenshot, EngineError> {
+        self.device.device_wait_idle()?;
+
+        let allocator = self.allocator.as_ref().unwrap();
+        let format = self.swapchain.surface_format.format;
+        if !Screenshot::supports_format(format) {
+            return Err(vk::Result::ERROR_FORMAT_NOT_SUPPORTED.into());
+        }
+        // destroyed when leaving, whether the screenshot succeeded or not
+        let target = Unwind::new(
+            Swapchain::new_offscreen(
+                &self.device,
+                allocator,
+                physical_size,
+                self.msaa_samples,
+                format,
+            )?,
+            |mut target| target.destroy(&self.device, allocator, &self.swapchain_loader),
+        );
+        let render_targets = Unwind::new(
+            create_render_targets(
+                &self.device,
+                &target,
+                self.msaa_samples,
+                self.rendering_path,
+            )?,
+            |(render_pass, framebuffers)| {
+                for framebuffer in framebuffers {
+                    self.device.destroy_framebuffer(framebuffer, None);
+                }
+                self.device.destroy_render_pass(render_pass, None);
+            },
+        );
+        let (render_pass, framebuffers) = &*render_targets;
+        let readback = Unwind::new(ReadbackBuffer::new(allocator, target.extent)?, |readback| {
+            readback.destroy(allocator)
+        });
+
+        crate::helpers::immediate_submit(
+            &self.device,
+            self.frames[self.frame_index()].command_pool,
+            self.graphics_queue,
+            |cmd| {
+                match self.rendering_path {
+                    RenderingPath::RenderPass => self.record_render_pass(
+                        cmd,
+                        *render_pass,
+                        framebuffers[0],
+                        target.extent,
+                        renderers,
+                    ),
+                    RenderingPath::Dynamic => {
+                        self.record_dynamic_rendering(cmd, &target, 0, renderers)
+                    }
+                }?;
+                readback.record_copy(&self.device, cmd, target.images[0], target.present_layout());
+                Ok(())
+            },
+        )?;
+        if let Some(debug_messenger) = &self.debug_messenger {
+            debug_messenger.check();
+        }
+
+        Screenshot::from_pixels(
+            target.extent.width,
+            target.extent.height,
+            &readback.read(allocator),
+            format,
+        )
+    }
+
+    /**
+     * Recreate the swapchain for physical_size.
+     * A zero extent (minimized window) keeps the current swapchain and suspends draw_frame,
+     * the swapchain is rebuilt by the next handle_resize or draw_frame with a valid extent.
+     */
+    pub unsafe fn handle_resize(
+        &mut self,
+        physical_size: (u32, u32),
+    ) -> Result<ResizeStatus, EngineError> {
+        let extent = if self.swapchain.is_offscreen() {
+            vk::Extent2D {
+                width: physical_size.0,
+                height: physical_size.1,
+            }
+        } else {
+            let surface_support =
+                SurfaceSupport::new(self.physical_device, self.surface, &self.surface_loader)?;
+            swapchain::choose_extent(&surface_support.capabilities, physical_size)
+        };
+        if physical_size.0 == 0 || physical_size.1 == 0 || extent.width == 0 || extent.height == 0 {
+            if self.minimized_size.is_none() {
+                log::info!("Minimized, rendering suspended");
+            }
+            self.minimized_size = Some(physical_size);
+            return Ok(ResizeStatus::Minimized);
+        }
+        if self.minimized_size.take().is_some() {
+            log::info!("Restored {}x{}", extent.width, extent.height);
+        }
+
+        let new_swapchain = if self.swapchain.is_offscreen() {
+            Swapchain::new_offscreen(
+                &self.device,
+                self.allocator.as_ref().unwrap(),
+                physical_size,
+                self.msaa_samples,
+                self.swapchain.surface_format.format,
+            )?
+        } else {
             //swapchain
             let surface_support =
-                SurfaceSupport::new(self.physical_device, self.surface, &self.surface_loader);
+                SurfaceSupport::new(self.physical_device, self.surface, &self.surface_loader)?;
             swapchain::Swapchain::new(
                 &self.swapchain_loader,
                 &self.device,
@@ -350,34 +1196,133 @@ impl Engine {
                 self.surface,
                 &self.queue_families,
                 Some(self.swapchain.chain),
-            )
+                self.msaa_samples,
+                self.present_mode,
+            )?
         };
 
         let old_swapchain = std::mem::replace(&mut self.swapchain, new_swapchain);
 
-        //destroy old_swapchain
-        {
-            for &framebuffer in &self.framebuffers {
-                self.device.destroy_framebuffer(framebuffer, None)
-            }
-            self.device.destroy_render_pass(self.render_pass, None);
-            old_swapchain.destroy(
-                &self.device,
-                self.allocator.as_ref().unwrap(),
-                &self.swapchain_loader,
-            );
+        // old_swapchain may still be used by frames in flight
+        for framebuffer in std::mem::take(&mut self.framebuffers) {
+            self.destroy_later(Resource::Framebuffer(framebuffer));
+        }
+        self.destroy_later(Resource::RenderPass(self.render_pass));
+        self.destroy_later(Resource::Swapchain(Box::new(old_swapchain)));
+
+        (self.render_pass, self.framebuffers) = create_render_targets(
+            &self.device,
+            &self.swapchain,
+            self.msaa_samples,
+            self.rendering_path,
+        )?;
+
+        Ok(ResizeStatus::Resized)
+    }
+
+    // size of the current swapchain, or the one requested while minimized
+    fn requested_size(&self) -> (u32, u32) {
+        self.minimized_size
+            .unwrap_or((self.swapchain.extent.width, self.swapchain.extent.height))
+    }
+
+    /**
+     * Recreate the swapchain with a new present mode preference, returns the mode actually used
+     * (see PresentModePreference::choose for the fallbacks). Pipelines stay valid.
+     */
+    pub unsafe fn set_present_mode(
+        &mut self,
+        preference: PresentModePreference,
+    ) -> Result<vk::PresentModeKHR, EngineError> {
+        self.present_mode = preference;
+        if !self.swapchain.is_offscreen() {
+            self.handle_resize(self.requested_size())?;
+        }
+        log::info!(
+            "Present mode {:?}: {}",
+            preference,
+            present_mode_name(self.swapchain.present_mode)
+        );
+
+        Ok(self.swapchain.present_mode)
+    }
+
+    /**
+     * Clamp and apply a new MSAA sample count, returns the one actually used.
+     * Swapchain attachments, render pass and framebuffers (RenderingPath::RenderPass) are rebuilt,
+     * pipelines must be recreated by the caller (their multisample state is baked).
+     */
+    pub unsafe fn set_msaa_samples(
+        &mut self,
+        requested: u32,
+    ) -> Result<vk::SampleCountFlags, EngineError> {
+        let samples =
+            physical_device::clamp_sample_count(&self.instance, self.physical_device, requested);
+        if samples == self.msaa_samples {
+            return Ok(samples);
         }
 
-        self.render_pass =
-            render_pass::create_default(&self.device, self.swapchain.surface_format.format);
-        self.framebuffers = self
-            .swapchain
-            .get_framebuffers(&self.device, self.render_pass);
+        self.msaa_samples = samples;
+        self.handle_resize(self.requested_size())?;
+        log::info!("MSAA set to {} samples", samples.as_raw());
+
+        Ok(samples)
     }
 }
 
-fn create_instance(entry: &ash::Entry) -> ash::Instance {
-    let (_layer_names, layer_name_pointers) = conf::get_layer_names();
+// Returns the instance and whether debug_utils is enabled (validation requested and available)
+fn create_instance(
+    entry: &ash::Entry,
+    extension_names: &[*const i8],
+    debug_config: &DebugConfig,
+) -> Result<(ash::Instance, bool), EngineError> {
+    let available_layers: Vec<String> = unsafe { entry.enumerate_instance_layer_properties() }
+        .unwrap_or_default()
+        .iter()
+        .map(|layer| {
+            unsafe { std::ffi::CStr::from_ptr(layer.layer_name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    let available_extensions: Vec<String> =
+        unsafe { entry.enumerate_instance_extension_properties(None) }
+            .unwrap_or_default()
+            .iter()
+            .map(|ext| {
+                unsafe { std::ffi::CStr::from_ptr(ext.extension_name.as_ptr()) }
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+
+    for &extension_name in extension_names {
+        let extension_name = unsafe { std::ffi::CStr::from_ptr(extension_name) }.to_string_lossy();
+        if !available_extensions.iter().any(|e| *e == extension_name) {
+            return Err(EngineError::MissingExtension(extension_name.into_owned()));
+        }
+    }
+
+    let (_layer_names, layer_name_pointers) = if debug_config.validation {
+        conf::get_layer_names(&available_layers)
+    } else {
+        (vec![], vec![])
+    };
+
+    let debug_utils = debug_config.validation
+        && available_extensions
+            .iter()
+            .any(|e| *e == ash::ext::debug_utils::NAME.to_string_lossy());
+    if debug_config.validation && !debug_utils {
+        log::warn!(
+            "{} requested but not available",
+            ash::ext::debug_utils::NAME.to_string_lossy()
+        );
+    }
+    let mut extension_names = extension_names.to_vec();
+    if debug_utils {
+        extension_names.push(ash::ext::debug_utils::NAME.as_ptr());
+    }
 
     let application_name = std::ffi::CString::new(conf::APPLICATION_NAME).unwrap();
     let engine_name = std::ffi::CString::new(conf::ENGINE_NAME).unwrap();
@@ -391,42 +1336,112 @@ fn create_instance(entry: &ash::Entry) -> ash::Instance {
     let instance_create_info: vk::InstanceCreateInfo = vk::InstanceCreateInfo::default()
         .application_info(&application_info)
         .enabled_layer_names(&layer_name_pointers)
-        .enabled_extension_names(&conf::EXTENSION_NAMES);
+        .enabled_extension_names(&extension_names);
+
+    let instance = unsafe { entry.create_instance(&instance_create_info, None)? };
 
-    unsafe { entry.create_instance(&instance_create_info, None).unwrap() }
+    Ok((instance, debug_utils))
 }
 
-//DEVICES
-fn get_physical_device(instance: &ash::Instance) -> vk::PhysicalDevice {
-    let phys_devs = unsafe { instance.enumerate_physical_devices().unwrap() };
-    let phys_dev = phys_devs
-        .into_iter()
-        .find_map(|p| {
-            let properties = unsafe { instance.get_physical_device_properties(p) };
-
-            let name = unsafe { std::ffi::CStr::from_ptr(properties.device_name.as_ptr()) }
-                .to_str()
-                .unwrap();
-            match name {
-                conf::PHYSICAL_DEVICE_NAME => Some(p),
-                _ => None,
+// Surface of window, Win32 or Xlib/Wayland
+fn create_surface(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    window: &winit::window::Window,
+) -> Result<vk::SurfaceKHR, EngineError> {
+    #[cfg(target_os = "windows")]
+    let surface = {
+        let window_info = {
+            use winit::raw_window_handle::HasWindowHandle;
+
+            let hwnd = match window.window_handle().unwrap().as_raw() {
+                winit::raw_window_handle::RawWindowHandle::Win32(handle) => handle.hwnd.get(),
+                _ => panic!("Unsupported platform!"),
+            };
+
+            let hinstance = {
+                let hmodule = unsafe {
+                    windows::Win32::System::LibraryLoader::GetModuleHandleW(None).unwrap()
+                };
+
+                if hmodule.is_invalid() {
+                    panic!("Unsupported windows hinstance")
+                }
+
+                hmodule.0
+            };
+
+            vk::Win32SurfaceCreateInfoKHR::default()
+                .hwnd(hwnd as vk::HWND)
+                .hinstance(hinstance as vk::HINSTANCE)
+        };
+
+        let win_surface_loader = ash::khr::win32_surface::Instance::new(entry, instance);
+        unsafe { win_surface_loader.create_win32_surface(&window_info, None) }?
+    };
+
+    #[cfg(target_os = "linux")]
+    let surface = {
+        use winit::raw_window_handle::HasDisplayHandle;
+        use winit::raw_window_handle::HasWindowHandle;
+
+        let window_handle = window
+            .window_handle()
+            .map_err(|err| EngineError::Surface(err.to_string()))?
+            .as_raw();
+        let display_handle = window
+            .display_handle()
+            .map_err(|err| EngineError::Surface(err.to_string()))?
+            .as_raw();
+        match (window_handle, display_handle) {
+            // Xlib
+            (
+                winit::raw_window_handle::RawWindowHandle::Xlib(window_handle),
+                winit::raw_window_handle::RawDisplayHandle::Xlib(display_handle),
+            ) => {
+                let window = window_handle.window;
+                let display = display_handle
+                    .display
+                    .map(|d| d.as_ptr())
+                    .unwrap_or(std::ptr::null_mut());
+
+                let window_info = vk::XlibSurfaceCreateInfoKHR::default()
+                    .window(window)
+                    .dpy(display);
+                let surface_loader = ash::khr::xlib_surface::Instance::new(entry, instance);
+                unsafe { surface_loader.create_xlib_surface(&window_info, None) }?
             }
-        })
-        .unwrap(); //onpanic: see conf::PHYSICAL_DEVICE_NAME
+            // Wayland
+            (
+                winit::raw_window_handle::RawWindowHandle::Wayland(window_handle),
+                winit::raw_window_handle::RawDisplayHandle::Wayland(display_handle),
+            ) => {
+                let surface = window_handle.surface.as_ptr();
+                let display = display_handle.display.as_ptr();
+
+                let window_info = vk::WaylandSurfaceCreateInfoKHR::default()
+                    .surface(surface)
+                    .display(display);
+                let surface_loader = ash::khr::wayland_surface::Instance::new(entry, instance);
+                unsafe { surface_loader.create_wayland_surface(&window_info, None) }?
+            }
+            _ => return Err(EngineError::Surface("unsupported platform".to_string())),
+        }
+    };
 
-    phys_dev
+    Ok(surface)
 }
 
 //queue families
 fn create_device(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-    surface_loader: &ash::khr::surface::Instance,
-    surface: vk::SurfaceKHR,
-) -> anyhow::Result<(ash::Device, QueueFamilies)> {
-    let physical_device_queue_families =
-        QueueFamilies::new(instance, physical_device, surface_loader, surface);
-
+    physical_device_queue_families: &QueueFamilies,
+    fill_mode_non_solid: bool, // optional feature, enabled when supported
+    pipeline_statistics: bool, // optional feature, GpuProfiler statistics queries
+    dynamic_rendering: bool,   // RenderingPath::Dynamic
+    device_extension_names: &[&std::ffi::CStr],
+) -> Result<ash::Device, EngineError> {
     let queue_priorities = [1.0];
 
     let mut queue_infos = vec![vk::DeviceQueueCreateInfo::default()
@@ -440,90 +1455,92 @@ fn create_device(
                 .queue_priorities(&queue_priorities),
         );
     }
+    if let Some(transfer) = physical_device_queue_families.transfer {
+        queue_infos.push(
+            vk::DeviceQueueCreateInfo::default()
+                .queue_family_index(transfer)
+                .queue_priorities(&queue_priorities),
+        );
+    }
 
     let mut features2 = vk::PhysicalDeviceFeatures2::default();
     features2.features.shader_int64 = vk::TRUE;
+    features2.features.fill_mode_non_solid = fill_mode_non_solid as vk::Bool32;
+    features2.features.pipeline_statistics_query = pipeline_statistics as vk::Bool32;
 
     let mut buffer_device_address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures {
         buffer_device_address: vk::TRUE,
         ..Default::default()
     };
-    let device_extensions = conf::DEVICE_EXTENSION_NAMES
+    let device_extensions = device_extension_names
         .iter()
         .map(|e| e.as_ptr())
         .collect::<Vec<_>>();
 
+    let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features {
+        dynamic_rendering: dynamic_rendering as vk::Bool32,
+        ..Default::default()
+    };
+
     let device_create_info = vk::DeviceCreateInfo::default()
         .queue_create_infos(&queue_infos)
         .enabled_extension_names(device_extensions.as_slice())
         .push_next(&mut buffer_device_address_features)
+        .push_next(&mut vulkan_13_features)
         .push_next(&mut features2);
 
     let device = unsafe { instance.create_device(physical_device, &device_create_info, None)? };
 
-    Ok((device, physical_device_queue_families))
+    Ok(device)
 }
 
-//DEBUG
-fn setup_debug_utils(
-    entry: &ash::Entry,
-    instance: &ash::Instance,
-) -> (ash::ext::debug_utils::Instance, vk::DebugUtilsMessengerEXT) {
-    let debug_utils_loader = ash::ext::debug_utils::Instance::new(entry, instance);
-
-    let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
-        .message_severity(
-            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
-        )
-        .message_type(
-            // vk::DebugUtilsMessageTypeFlagsEXT::GENERAL |
-            vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
-                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
-        )
-        .pfn_user_callback(Some(vulkan_debug_utils_callback));
-    let debug_utils_messenger =
-        unsafe { debug_utils_loader.create_debug_utils_messenger(&debug_info, None) }.unwrap();
-
-    (debug_utils_loader, debug_utils_messenger)
+// Render pass and one framebuffer per target image, none with RenderingPath::Dynamic
+fn create_render_targets(
+    device: &ash::Device,
+    target: &Swapchain,
+    samples: vk::SampleCountFlags,
+    rendering_path: RenderingPath,
+) -> Result<(vk::RenderPass, Vec<vk::Framebuffer>), EngineError> {
+    match rendering_path {
+        RenderingPath::RenderPass => {
+            let render_pass = render_pass::create_default(
+                device,
+                target.surface_format.format,
+                target.present_layout(),
+                samples,
+            )?;
+            let framebuffers = target.get_framebuffers(device, render_pass)?;
+            Ok((render_pass, framebuffers))
+        }
+        RenderingPath::Dynamic => Ok((vk::RenderPass::null(), vec![])),
+    }
 }
 
-unsafe extern "system" fn vulkan_debug_utils_callback(
-    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
-    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut std::ffi::c_void,
-) -> vk::Bool32 {
-    let message = std::ffi::CStr::from_ptr((*p_callback_data).p_message);
-    let severity = format!("{:?}", message_severity).to_lowercase();
-    let ty = format!("{:?}", message_type).to_lowercase();
-    println!("[Debug][{}][{}] {:?}", severity, ty, message);
-    vk::FALSE
+fn duration_ms(duration: std::time::Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
 }
 
 fn create_present_frames(
     device: &ash::Device,
     graphics_family: u32,
     count: usize,
-) -> Vec<FrameData> {
+) -> Result<Vec<FrameData>, EngineError> {
     let mut frames = Vec::with_capacity(count);
 
     for _ in 0..count {
-        frames.push(FrameData::new(device, graphics_family));
+        frames.push(FrameData::new(device, graphics_family)?);
     }
 
-    frames
+    Ok(frames)
 }
 
 fn create_allocator(
     instance: &ash::Instance,
     device: &ash::Device,
     physical_device: vk::PhysicalDevice,
-) -> vk_mem::Allocator {
+) -> Result<vk_mem::Allocator, EngineError> {
     let mut create_info = vk_mem::AllocatorCreateInfo::new(instance, device, physical_device);
     create_info.flags = vk_mem::AllocatorCreateFlags::BUFFER_DEVICE_ADDRESS;
     create_info.vulkan_api_version = vk::API_VERSION_1_3;
-    unsafe { vk_mem::Allocator::new(create_info).unwrap() }
+    Ok(unsafe { vk_mem::Allocator::new(create_info)? })
 }
@@ -0,0 +1,398 @@
+use ash::vk;
+
+use super::error::EngineError;
+use crate::conf;
+
+// User choice of physical device, skips scoring but not requirements
+#[derive(Debug, Clone)]
+pub enum DeviceOverride {
+    Name(String),
+    Index(usize),
+}
+
+impl DeviceOverride {
+    pub fn parse(value: &str) -> Self {
+        match value.parse::<usize>() {
+            Ok(index) => Self::Index(index),
+            Err(_) => Self::Name(value.to_string()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Candidate {
+    pub physical_device: vk::PhysicalDevice,
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub score: u64,
+    pub rejections: Vec<String>, // empty when the device is suitable
+}
+
+impl Candidate {
+    pub fn is_suitable(&self) -> bool {
+        self.rejections.is_empty()
+    }
+}
+
+// Highest sample count supported by both color and depth framebuffers, not above requested
+pub fn clamp_sample_count(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    requested: u32,
+) -> vk::SampleCountFlags {
+    let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+    let supported =
+        limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+
+    [
+        vk::SampleCountFlags::TYPE_64,
+        vk::SampleCountFlags::TYPE_32,
+        vk::SampleCountFlags::TYPE_16,
+        vk::SampleCountFlags::TYPE_8,
+        vk::SampleCountFlags::TYPE_4,
+        vk::SampleCountFlags::TYPE_2,
+    ]
+    .into_iter()
+    .find(|&count| count.as_raw() <= requested && supported.contains(count))
+    .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+
+// Vulkan 1.3 dynamicRendering feature, see RenderingPath::Dynamic
+pub fn supports_dynamic_rendering(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> bool {
+    let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features::default();
+    let mut features2 = vk::PhysicalDeviceFeatures2::default().push_next(&mut vulkan_13_features);
+    unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+
+    vulkan_13_features.dynamic_rendering == vk::TRUE
+}
+
+/**
+ * Score every physical device and pick the best suitable one (or the overridden one).
+ * surface: when Some, a graphics queue family must be able to present to it.
+ * Fails with the rejection reason when no device fits, see the report logged above it.
+ */
+pub fn select(
+    instance: &ash::Instance,
+    surface: Option<(&ash::khr::surface::Instance, vk::SurfaceKHR)>,
+    device_extension_names: &[&std::ffi::CStr],
+    device_override: Option<DeviceOverride>,
+) -> Result<vk::PhysicalDevice, EngineError> {
+    let candidates: Vec<Candidate> = unsafe { instance.enumerate_physical_devices()? }
+        .into_iter()
+        .enumerate()
+        .map(|(index, physical_device)| {
+            evaluate(
+                instance,
+                physical_device,
+                index,
+                surface,
+                device_extension_names,
+            )
+        })
+        .collect();
+
+    report(&candidates);
+
+    let selected = choose(&candidates, device_override.as_ref())?;
+    log::info!("selected #{} \"{}\"", selected.index, selected.name);
+
+    Ok(selected.physical_device)
+}
+
+// Overridden candidate when it's suitable, otherwise the suitable one with the highest score
+fn choose<'a>(
+    candidates: &'a [Candidate],
+    device_override: Option<&DeviceOverride>,
+) -> Result<&'a Candidate, EngineError> {
+    let Some(device_override) = device_override else {
+        return candidates
+            .iter()
+            .filter(|candidate| candidate.is_suitable())
+            .max_by_key(|candidate| candidate.score)
+            .ok_or_else(|| {
+                EngineError::NoSuitableDevice("every physical device was rejected".to_string())
+            });
+    };
+
+    let candidate = candidates
+        .iter()
+        .find(|candidate| match device_override {
+            DeviceOverride::Index(index) => candidate.index == *index,
+            DeviceOverride::Name(name) => {
+                candidate.name.to_lowercase().contains(&name.to_lowercase())
+            }
+        })
+        .ok_or_else(|| {
+            EngineError::NoSuitableDevice(format!(
+                "physical device {:?} not found",
+                device_override
+            ))
+        })?;
+    if !candidate.is_suitable() {
+        return Err(EngineError::NoSuitableDevice(format!(
+            "physical device {:?} (\"{}\") rejected: {}",
+            device_override,
+            candidate.name,
+            candidate.rejections.join(", ")
+        )));
+    }
+
+    Ok(candidate)
+}
+
+fn evaluate(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    index: usize,
+    surface: Option<(&ash::khr::surface::Instance, vk::SurfaceKHR)>,
+    device_extension_names: &[&std::ffi::CStr],
+) -> Candidate {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let name = unsafe { std::ffi::CStr::from_ptr(properties.device_name.as_ptr()) }
+        .to_string_lossy()
+        .into_owned();
+
+    let mut rejections: Vec<String> = vec![];
+
+    // Api version
+    if properties.api_version < conf::API_VERSION {
+        rejections.push(format!(
+            "api version {}.{} < {}.{}",
+            vk::api_version_major(properties.api_version),
+            vk::api_version_minor(properties.api_version),
+            vk::api_version_major(conf::API_VERSION),
+            vk::api_version_minor(conf::API_VERSION),
+        ));
+    }
+
+    // Extensions
+    match unsafe { instance.enumerate_device_extension_properties(physical_device) } {
+        Ok(properties) => {
+            let extensions: Vec<String> = properties
+                .iter()
+                .map(|ext| {
+                    unsafe { std::ffi::CStr::from_ptr(ext.extension_name.as_ptr()) }
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+            rejections.extend(missing_extensions(&extensions, device_extension_names));
+        }
+        Err(err) => rejections.push(format!("extensions not enumerable ({})", err)),
+    }
+
+    // Features, PhysicalDeviceVulkan12Features can only be chained on 1.2 devices
+    let buffer_device_address = properties.api_version >= vk::API_VERSION_1_2 && {
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default();
+        let mut features2 =
+            vk::PhysicalDeviceFeatures2::default().push_next(&mut vulkan_12_features);
+        unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+        vulkan_12_features.buffer_device_address == vk::TRUE
+    };
+    let shader_int64 =
+        unsafe { instance.get_physical_device_features(physical_device) }.shader_int64;
+    if !buffer_device_address {
+        rejections.push("missing feature bufferDeviceAddress".to_string());
+    }
+    if shader_int64 != vk::TRUE {
+        rejections.push("missing feature shaderInt64".to_string());
+    }
+
+    // Queue families
+    let queue_families =
+        unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+    let graphics_families: Vec<u32> = queue_families
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.queue_count > 0 && c.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+        .map(|(idx, _)| idx as u32)
+        .collect();
+    if graphics_families.is_empty() {
+        rejections.push("no graphics queue family".to_string());
+    }
+
+    // Surface
+    if let Some((surface_loader, surface)) = surface {
+        let can_present = graphics_families.iter().any(|&family| {
+            unsafe {
+                surface_loader.get_physical_device_surface_support(physical_device, family, surface)
+            }
+            .unwrap_or(false)
+        });
+        if !graphics_families.is_empty() && !can_present {
+            rejections.push("no graphics queue family can present to the surface".to_string());
+        }
+
+        let formats = unsafe {
+            surface_loader.get_physical_device_surface_formats(physical_device, surface)
+        }
+        .unwrap_or_default();
+        let present_modes = unsafe {
+            surface_loader.get_physical_device_surface_present_modes(physical_device, surface)
+        }
+        .unwrap_or_default();
+        if formats.is_empty() {
+            rejections.push("no surface format".to_string());
+        }
+        if present_modes.is_empty() {
+            rejections.push("no present mode".to_string());
+        }
+    }
+
+    Candidate {
+        physical_device,
+        index,
+        name,
+        device_type: properties.device_type,
+        score: score(
+            properties.device_type,
+            properties.limits.max_image_dimension2_d,
+        ),
+        rejections,
+    }
+}
+
+// Device type first, then largest 2D image as tie breaker
+fn score(device_type: vk::PhysicalDeviceType, max_image_dimension2_d: u32) -> u64 {
+    let type_score: u64 = match device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 1,
+        _ => 0,
+    };
+    type_score * 1_000_000 + max_image_dimension2_d as u64
+}
+
+fn missing_extensions(available: &[String], required: &[&std::ffi::CStr]) -> Vec<String> {
+    required
+        .iter()
+        .map(|extension_name| extension_name.to_string_lossy())
+        .filter(|extension_name| !available.iter().any(|e| *e == *extension_name))
+        .map(|extension_name| format!("missing extension {}", extension_name))
+        .collect()
+}
+
+fn report(candidates: &[Candidate]) {
+    for candidate in candidates {
+        if candidate.is_suitable() {
+            log::info!("{}", report_line(candidate));
+        } else {
+            log::warn!("{}", report_line(candidate));
+        }
+    }
+}
+
+fn report_line(candidate: &Candidate) -> String {
+    let status = if candidate.is_suitable() {
+        format!("score={}", candidate.score)
+    } else {
+        format!("rejected: {}", candidate.rejections.join(", "))
+    };
+
+    format!(
+        "#{} \"{}\" ({:?}) {}",
+        candidate.index, candidate.name, candidate.device_type, status
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(index: usize, name: &str, score: u64, rejections: &[&str]) -> Candidate {
+        Candidate {
+            physical_device: vk::PhysicalDevice::null(),
+            index,
+            name: name.to_string(),
+            device_type: vk::PhysicalDeviceType::DISCRETE_GPU,
+            score,
+            rejections: rejections.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn override_parse() {
+        assert!(matches!(
+            DeviceOverride::parse("1"),
+            DeviceOverride::Index(1)
+        ));
+        assert!(matches!(
+            DeviceOverride::parse("RTX 4070"),
+            DeviceOverride::Name(name) if name == "RTX 4070"
+        ));
+        assert!(matches!(
+            DeviceOverride::parse("-1"),
+            DeviceOverride::Name(_)
+        ));
+    }
+
+    #[test]
+    fn score_by_type_then_image_size() {
+        let discrete = score(vk::PhysicalDeviceType::DISCRETE_GPU, 16384);
+        let integrated = score(vk::PhysicalDeviceType::INTEGRATED_GPU, 32768);
+        let cpu = score(vk::PhysicalDeviceType::CPU, 32768);
+        assert!(discrete > integrated);
+        assert!(integrated > cpu);
+        assert!(cpu > score(vk::PhysicalDeviceType::OTHER, 32768));
+        assert!(score(vk::PhysicalDeviceType::CPU, 8192) < cpu);
+    }
+
+    #[test]
+    fn choose_highest_suitable_score() {
+        let candidates = [
+            candidate(0, "llvmpipe", 1, &[]),
+            candidate(1, "Big GPU", 3, &["missing extension VK_KHR_swapchain"]),
+            candidate(2, "Small GPU", 2, &[]),
+        ];
+        assert_eq!(choose(&candidates, None).unwrap().index, 2);
+        assert!(choose(&candidates[1..2], None).is_err());
+        assert!(choose(&[], None).is_err());
+    }
+
+    #[test]
+    fn choose_override() {
+        let candidates = [
+            candidate(0, "llvmpipe (LLVM 17.0.6, 256 bits)", 1, &[]),
+            candidate(1, "Big GPU", 3, &["no present mode"]),
+            candidate(2, "Small GPU", 2, &[]),
+        ];
+        let by_name = DeviceOverride::Name("LLVMPIPE".to_string());
+        assert_eq!(choose(&candidates, Some(&by_name)).unwrap().index, 0);
+        assert_eq!(
+            choose(&candidates, Some(&DeviceOverride::Index(2)))
+                .unwrap()
+                .index,
+            2
+        );
+        // rejected or missing devices aren't replaced by another one
+        assert!(choose(&candidates, Some(&DeviceOverride::Index(1))).is_err());
+        assert!(choose(&candidates, Some(&DeviceOverride::Index(3))).is_err());
+    }
+
+    #[test]
+    fn extension_rejections() {
+        let available = ["VK_KHR_swapchain".to_string()];
+        assert!(missing_extensions(&available, &[ash::khr::swapchain::NAME]).is_empty());
+        assert_eq!(
+            missing_extensions(&available, &[ash::khr::buffer_device_address::NAME]),
+            ["missing extension VK_KHR_buffer_device_address"]
+        );
+    }
+
+    #[test]
+    fn report_lines() {
+        assert_eq!(
+            report_line(&candidate(0, "GPU", 42, &[])),
+            "#0 \"GPU\" (DISCRETE_GPU) score=42"
+        );
+        assert_eq!(
+            report_line(&candidate(1, "GPU", 42, &["a", "b"])),
+            "#1 \"GPU\" (DISCRETE_GPU) rejected: a, b"
+        );
+    }
+}
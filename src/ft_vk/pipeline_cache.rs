@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use ash::vk;
+
+use super::error::EngineError;
+use crate::conf;
+
+// VkPipelineCacheHeaderVersionOne (little endian): header_size, header_version, vendor_id, device_id, uuid
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/**
+ * Pipeline cache persisted per device in conf::PIPELINE_CACHE_DIR.
+ * A file written by another driver/device is discarded (vendor ID, device ID, cache UUID).
+ */
+pub struct PipelineCache {
+    cache: vk::PipelineCache,
+    path: PathBuf,
+}
+
+impl PipelineCache {
+    pub fn new(
+        instance: &ash::Instance,
+        device: &ash::Device,
+        physical_device: vk::PhysicalDevice,
+    ) -> Result<Self, EngineError> {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let path = PathBuf::from(conf::PIPELINE_CACHE_DIR).join(format!(
+            "pipeline_{:04x}_{:04x}.bin",
+            properties.vendor_id, properties.device_id
+        ));
+
+        let initial_data = match std::fs::read(&path) {
+            Ok(data) if is_compatible(&data, &properties) => {
+                log::info!(
+                    "Pipeline cache loaded from {:?} ({} bytes)",
+                    path,
+                    data.len()
+                );
+                data
+            }
+            Ok(_) => {
+                log::warn!(
+                    "Pipeline cache {:?} doesn't match the device, discarded",
+                    path
+                );
+                vec![]
+            }
+            Err(_) => vec![],
+        };
+
+        let create_info = vk::PipelineCacheCreateInfo::default().initial_data(&initial_data);
+        let cache = unsafe { device.create_pipeline_cache(&create_info, None) }.or_else(|err| {
+            // a corrupted file can still be rejected by the driver
+            log::warn!("Pipeline cache {:?} rejected: {}", path, err);
+            unsafe { device.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default(), None) }
+        })?;
+
+        Ok(Self { cache, path })
+    }
+
+    pub fn as_vk(&self) -> vk::PipelineCache {
+        self.cache
+    }
+
+    // written to a temporary file first so an interrupted save never leaves a truncated cache
+    pub fn save(&self, device: &ash::Device) -> std::io::Result<()> {
+        let data = unsafe { device.get_pipeline_cache_data(self.cache) }
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, &data)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        log::info!(
+            "Pipeline cache saved to {:?} ({} bytes)",
+            self.path,
+            data.len()
+        );
+        Ok(())
+    }
+
+    // saves then destroys, a failed save is only reported
+    pub unsafe fn destroy(&self, device: &ash::Device) {
+        if let Err(err) = self.save(device) {
+            log::warn!("Pipeline cache {:?} not saved: {}", self.path, err);
+        }
+        device.destroy_pipeline_cache(self.cache, None);
+    }
+}
+
+fn is_compatible(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool {
+    if data.len() < HEADER_SIZE {
+        return false;
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+    read_u32(0) as usize >= HEADER_SIZE
+        && read_u32(4) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+        && read_u32(8) == properties.vendor_id
+        && read_u32(12) == properties.device_id
+        && data[16..HEADER_SIZE] == properties.pipeline_cache_uuid
+}
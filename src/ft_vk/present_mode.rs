@@ -0,0 +1,89 @@
+use ash::vk;
+
+use crate::conf;
+
+/**
+ * Requested presentation, resolved against the surface present modes by Swapchain::new.
+ * Each preference falls back to the next mode of its list, FIFO is always supported.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentModePreference {
+    Vsync,       // FIFO
+    Immediate,   // tearing, lowest latency
+    Mailbox,     // no tearing, latest image
+    FifoRelaxed, // vsync, tears when a frame is late
+}
+
+impl Default for PresentModePreference {
+    // "vsync", "immediate", "mailbox" or "fifo_relaxed", defaults to Mailbox
+    fn default() -> Self {
+        match std::env::var(conf::PRESENT_MODE_ENV).as_deref() {
+            Ok("vsync") => Self::Vsync,
+            Ok("immediate") => Self::Immediate,
+            Ok("mailbox") | Err(_) => Self::Mailbox,
+            Ok("fifo_relaxed") => Self::FifoRelaxed,
+            Ok(value) => {
+                log::warn!(
+                    "{}={} is not a present mode (vsync, immediate, mailbox, fifo_relaxed)",
+                    conf::PRESENT_MODE_ENV,
+                    value
+                );
+                Self::Mailbox
+            }
+        }
+    }
+}
+
+impl PresentModePreference {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Vsync => Self::Immediate,
+            Self::Immediate => Self::Mailbox,
+            Self::Mailbox => Self::FifoRelaxed,
+            Self::FifoRelaxed => Self::Vsync,
+        }
+    }
+
+    fn candidates(self) -> &'static [vk::PresentModeKHR] {
+        match self {
+            Self::Vsync => &[vk::PresentModeKHR::FIFO],
+            Self::Immediate => &[
+                vk::PresentModeKHR::IMMEDIATE,
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::FIFO,
+            ],
+            Self::Mailbox => &[vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO],
+            Self::FifoRelaxed => &[vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO],
+        }
+    }
+
+    // First supported candidate, FIFO otherwise
+    pub fn choose(self, supported: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+        let present_mode = self
+            .candidates()
+            .iter()
+            .copied()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(vk::PresentModeKHR::FIFO);
+        if present_mode != self.candidates()[0] {
+            log::warn!(
+                "{:?} unsupported, presenting with {}",
+                self,
+                present_mode_name(present_mode)
+            );
+        }
+
+        present_mode
+    }
+}
+
+// Short name for the window title and logs
+pub fn present_mode_name(present_mode: vk::PresentModeKHR) -> &'static str {
+    match present_mode {
+        vk::PresentModeKHR::FIFO => "FIFO (vsync)",
+        vk::PresentModeKHR::FIFO_RELAXED => "FIFO relaxed",
+        vk::PresentModeKHR::MAILBOX => "Mailbox",
+        vk::PresentModeKHR::IMMEDIATE => "Immediate",
+        _ => "Other",
+    }
+}
@@ -0,0 +1,318 @@
+use std::cell::{Cell, RefCell};
+
+use ash::vk;
+
+use super::error::EngineError;
+
+const STATISTICS_FLAGS: vk::QueryPipelineStatisticFlags = vk::QueryPipelineStatisticFlags::from_raw(
+    vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS.as_raw()
+        | vk::QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS.as_raw()
+        | vk::QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES.as_raw()
+        | vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS.as_raw(),
+);
+
+// Counters of STATISTICS_FLAGS, in bit order as written by the device
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineStatistics {
+    pub vertex_shader_invocations: u64,
+    pub clipping_invocations: u64, // primitives entering the clipper
+    pub clipping_primitives: u64,  // primitives leaving it
+    pub fragment_shader_invocations: u64,
+}
+
+// Scope read back once its frame is complete, see GpuProfiler::collect
+#[derive(Debug, Clone, Copy)]
+pub struct ScopeResult {
+    pub name: &'static str,
+    pub depth: u32, // nesting level, 0 for the frame scope of Engine::draw_frame
+    pub gpu_ms: f32,
+    pub statistics: Option<PipelineStatistics>,
+}
+
+// Returned by GpuProfiler::begin_scope, inactive when the scope isn't recorded
+#[must_use]
+pub struct ProfileScope(Option<usize>);
+
+struct ScopeRecord {
+    name: &'static str,
+    depth: u32,
+    statistics: bool,
+    ended: bool,
+}
+
+struct FrameQueries {
+    timestamps: vk::QueryPool,         // begin and end of each scope
+    statistics: Option<vk::QueryPool>, // one per scope, outermost scopes only
+    scopes: RefCell<Vec<ScopeRecord>>,
+}
+
+/**
+ * GPU timestamps and optional pipeline statistics of named scopes.
+ * Each frame in flight owns its query pools: they are reset when the frame is recorded and read
+ * once its fence is waited again, frames in flight later, so reading never stalls.
+ * Statistics queries can't nest, only the outermost scope of a nesting gets them.
+ * Scopes past max_scopes, or begun outside begin_frame/end_frame, are not recorded.
+ */
+pub struct GpuProfiler {
+    frames: Vec<FrameQueries>, // empty when the graphics queue has no timestamps
+    max_scopes: u32,
+    timestamp_period: f32, // nanoseconds per tick
+    timestamp_mask: u64,   // timestamp_valid_bits
+
+    recording: Cell<Option<usize>>, // frame index between begin_frame and end_frame
+    depth: Cell<u32>,
+    statistics_active: Cell<bool>,
+}
+
+impl GpuProfiler {
+    pub fn new(
+        instance: &ash::Instance,
+        device: &ash::Device,
+        physical_device: vk::PhysicalDevice,
+        graphics_family: u32,
+        frame_count: usize,
+        max_scopes: u32,
+        pipeline_statistics: bool, // pipelineStatisticsQuery feature enabled
+    ) -> Result<Self, EngineError> {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let timestamp_valid_bits =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+                [graphics_family as usize]
+                .timestamp_valid_bits;
+
+        let mut profiler = Self {
+            frames: Vec::with_capacity(frame_count),
+            max_scopes,
+            timestamp_period: properties.limits.timestamp_period,
+            timestamp_mask: match timestamp_valid_bits {
+                64 => u64::MAX,
+                bits => (1u64 << bits) - 1,
+            },
+            recording: Cell::new(None),
+            depth: Cell::new(0),
+            statistics_active: Cell::new(false),
+        };
+        if timestamp_valid_bits == 0 {
+            log::warn!("GPU profiling unavailable: no timestamps on the graphics queue");
+            return Ok(profiler);
+        }
+
+        for _ in 0..frame_count {
+            let timestamps = create_query_pool(
+                device,
+                vk::QueryType::TIMESTAMP,
+                max_scopes * 2,
+                vk::QueryPipelineStatisticFlags::empty(),
+            )?;
+            let statistics = if pipeline_statistics {
+                Some(create_query_pool(
+                    device,
+                    vk::QueryType::PIPELINE_STATISTICS,
+                    max_scopes,
+                    STATISTICS_FLAGS,
+                )?)
+            } else {
+                None
+            };
+            profiler.frames.push(FrameQueries {
+                timestamps,
+                statistics,
+                scopes: RefCell::new(vec![]),
+            });
+        }
+
+        Ok(profiler)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    /**
+     * Results of the scopes recorded by the previous use of frame_index, in recording order.
+     * The frame fence must have been waited. Empty when nothing was recorded.
+     */
+    pub unsafe fn collect(
+        &mut self,
+        device: &ash::Device,
+        frame_index: usize,
+    ) -> Result<Vec<ScopeResult>, EngineError> {
+        let Some(frame) = self.frames.get(frame_index) else {
+            return Ok(vec![]);
+        };
+        let scopes = std::mem::take(&mut *frame.scopes.borrow_mut());
+        if scopes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut timestamps = vec![0u64; scopes.len() * 2];
+        match device.get_query_pool_results(
+            frame.timestamps,
+            0,
+            &mut timestamps,
+            vk::QueryResultFlags::TYPE_64,
+        ) {
+            Ok(()) => {}
+            // a scope wasn't ended, the frame is dropped
+            Err(vk::Result::NOT_READY) => return Ok(vec![]),
+            Err(error) => return Err(error.into()),
+        }
+
+        let mut results = Vec::with_capacity(scopes.len());
+        for (index, scope) in scopes.iter().enumerate() {
+            if !scope.ended {
+                continue;
+            }
+            let ticks =
+                timestamps[index * 2 + 1].wrapping_sub(timestamps[index * 2]) & self.timestamp_mask;
+
+            let statistics = match (frame.statistics, scope.statistics) {
+                (Some(pool), true) => {
+                    let mut statistics = [PipelineStatistics::default()];
+                    device.get_query_pool_results(
+                        pool,
+                        index as u32,
+                        &mut statistics,
+                        vk::QueryResultFlags::TYPE_64,
+                    )?;
+                    Some(statistics[0])
+                }
+                _ => None,
+            };
+
+            results.push(ScopeResult {
+                name: scope.name,
+                depth: scope.depth,
+                gpu_ms: (ticks as f64 * self.timestamp_period as f64 / 1_000_000.0) as f32,
+                statistics,
+            });
+        }
+
+        Ok(results)
+    }
+
+    // Reset the queries of frame_index, recorded outside any render pass before the first scope
+    pub unsafe fn begin_frame(
+        &mut self,
+        device: &ash::Device,
+        cmd: vk::CommandBuffer,
+        frame_index: usize,
+    ) {
+        let Some(frame) = self.frames.get(frame_index) else {
+            return;
+        };
+        frame.scopes.borrow_mut().clear();
+        device.cmd_reset_query_pool(cmd, frame.timestamps, 0, self.max_scopes * 2);
+        if let Some(statistics) = frame.statistics {
+            device.cmd_reset_query_pool(cmd, statistics, 0, self.max_scopes);
+        }
+
+        self.recording.set(Some(frame_index));
+        self.depth.set(0);
+        self.statistics_active.set(false);
+    }
+
+    pub fn end_frame(&mut self) {
+        if self.depth.get() != 0 {
+            log::warn!("{} profile scopes not ended", self.depth.get());
+        }
+        self.recording.set(None);
+    }
+
+    /**
+     * Timestamp the start of a named scope, end it with end_scope in the same command buffer.
+     * With pipeline statistics the outermost scope also begins a query: it must then end in the
+     * same render pass instance (or outside any) as it began.
+     */
+    pub unsafe fn begin_scope(
+        &self,
+        device: &ash::Device,
+        cmd: vk::CommandBuffer,
+        name: &'static str,
+    ) -> ProfileScope {
+        let Some(frame) = self.recording.get().map(|index| &self.frames[index]) else {
+            return ProfileScope(None);
+        };
+        let mut scopes = frame.scopes.borrow_mut();
+        if scopes.len() as u32 >= self.max_scopes {
+            return ProfileScope(None);
+        }
+
+        let index = scopes.len();
+        let statistics = frame.statistics.is_some() && !self.statistics_active.get();
+        device.cmd_write_timestamp(
+            cmd,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            frame.timestamps,
+            index as u32 * 2,
+        );
+        if let (Some(pool), true) = (frame.statistics, statistics) {
+            device.cmd_begin_query(cmd, pool, index as u32, vk::QueryControlFlags::empty());
+            self.statistics_active.set(true);
+        }
+
+        scopes.push(ScopeRecord {
+            name,
+            depth: self.depth.get(),
+            statistics,
+            ended: false,
+        });
+        self.depth.set(self.depth.get() + 1);
+
+        ProfileScope(Some(index))
+    }
+
+    pub unsafe fn end_scope(
+        &self,
+        device: &ash::Device,
+        cmd: vk::CommandBuffer,
+        scope: ProfileScope,
+    ) {
+        let (Some(index), Some(frame)) = (
+            scope.0,
+            self.recording.get().map(|index| &self.frames[index]),
+        ) else {
+            return;
+        };
+        let mut scopes = frame.scopes.borrow_mut();
+        let record = &mut scopes[index];
+
+        if let (Some(pool), true) = (frame.statistics, record.statistics) {
+            device.cmd_end_query(cmd, pool, index as u32);
+            self.statistics_active.set(false);
+        }
+        device.cmd_write_timestamp(
+            cmd,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            frame.timestamps,
+            index as u32 * 2 + 1,
+        );
+
+        record.ended = true;
+        self.depth.set(self.depth.get().saturating_sub(1));
+    }
+
+    pub unsafe fn destroy(&mut self, device: &ash::Device) {
+        for frame in self.frames.drain(..) {
+            device.destroy_query_pool(frame.timestamps, None);
+            if let Some(statistics) = frame.statistics {
+                device.destroy_query_pool(statistics, None);
+            }
+        }
+    }
+}
+
+fn create_query_pool(
+    device: &ash::Device,
+    query_type: vk::QueryType,
+    query_count: u32,
+    pipeline_statistics: vk::QueryPipelineStatisticFlags,
+) -> Result<vk::QueryPool, EngineError> {
+    let info = vk::QueryPoolCreateInfo::default()
+        .query_type(query_type)
+        .query_count(query_count)
+        .pipeline_statistics(pipeline_statistics);
+
+    Ok(unsafe { device.create_query_pool(&info, None)? })
+}
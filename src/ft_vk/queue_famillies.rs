@@ -1,8 +1,11 @@
 use ash::vk;
 
+use super::EngineError;
+
 pub struct QueueFamilies {
     pub graphics: u32,
     pub present: u32,
+    pub transfer: Option<u32>, // transfer only family (no graphics), used by the Uploader
 }
 
 impl QueueFamilies {
@@ -11,7 +14,7 @@ impl QueueFamilies {
         physical_device: vk::PhysicalDevice,
         surface_loader: &ash::khr::surface::Instance,
         surface: vk::SurfaceKHR,
-    ) -> Self {
+    ) -> Result<Self, EngineError> {
         let queuefamilyproperties =
             unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
@@ -28,7 +31,7 @@ impl QueueFamilies {
                                 surface,
                             )
                         }
-                        .unwrap()
+                        .unwrap_or(false)
                     {
                         acc_q_graphics_idx = Some(c_idx);
                         acc_q_present_idx = Some(c_idx);
@@ -44,9 +47,52 @@ impl QueueFamilies {
             },
         );
 
-        Self {
-            graphics: q_graphics_idx.unwrap().try_into().unwrap(),
-            present: q_present_idx.unwrap().try_into().unwrap(),
-        }
+        let (Some(q_graphics_idx), Some(q_present_idx)) = (q_graphics_idx, q_present_idx) else {
+            return Err(EngineError::NoSuitableDevice(
+                "no graphics queue family with present support".to_string(),
+            ));
+        };
+
+        Ok(Self {
+            graphics: q_graphics_idx as u32,
+            present: q_present_idx as u32,
+            transfer: find_transfer_family(&queuefamilyproperties),
+        })
     }
+
+    // No surface: present is the graphics family
+    pub fn new_headless(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Result<Self, EngineError> {
+        let queuefamilyproperties =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+
+        let q_graphics_idx = queuefamilyproperties
+            .iter()
+            .position(|c| c.queue_count > 0 && c.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+            .ok_or_else(|| EngineError::NoSuitableDevice("no graphics queue family".to_string()))?
+            as u32;
+
+        Ok(Self {
+            graphics: q_graphics_idx,
+            present: q_graphics_idx,
+            transfer: find_transfer_family(&queuefamilyproperties),
+        })
+    }
+}
+
+// Family with TRANSFER but no GRAPHICS, preferably no COMPUTE either (DMA engine)
+fn find_transfer_family(properties: &[vk::QueueFamilyProperties]) -> Option<u32> {
+    let transfer_only = |excluded: vk::QueueFlags| {
+        properties.iter().position(|c| {
+            c.queue_count > 0
+                && c.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                && !c.queue_flags.intersects(excluded)
+        })
+    };
+
+    transfer_only(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+        .or_else(|| transfer_only(vk::QueueFlags::GRAPHICS))
+        .map(|index| index as u32)
 }
@@ -0,0 +1,616 @@
+// EngineError::Shader with a formatted reason, bail returns it
+macro_rules! shader_error {
+    ($($arg:tt)*) => {
+        crate::ft_vk::EngineError::Shader(format!($($arg)*))
+    };
+}
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err(shader_error!($($arg)*))
+    };
+}
+
+mod spirv;
+
+use std::collections::HashMap;
+
+use ash::vk;
+
+use spirv::{decoration, execution_model, storage_class, Module, Type};
+
+use super::EngineError;
+
+#[derive(Debug, Clone)]
+pub struct MemberLayout {
+    pub name: String,
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructLayout {
+    pub name: String,
+    pub size: u32,                 // end of the last member, without trailing padding
+    pub array_stride: Option<u32>, // when the struct is an array element
+    pub members: Vec<MemberLayout>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub descriptor_count: u32,
+    pub stage: vk::ShaderStageFlags,
+}
+
+/**
+ * Interface of a single SPIR-V module.
+ * structs holds every struct reachable from the push constants, the descriptors
+ * and the buffer references (GL_EXT_buffer_reference), by type name.
+ */
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+    pub stage: vk::ShaderStageFlags,
+    pub push_constants: Option<StructLayout>,
+    pub bindings: Vec<Binding>,
+    pub structs: HashMap<String, StructLayout>,
+}
+
+impl ShaderReflection {
+    pub fn from_file(filename: &str) -> Result<Self, EngineError> {
+        let words = std::fs::File::open(filename)
+            .and_then(|mut shader_file| ash::util::read_spv(&mut shader_file))
+            .map_err(|err| shader_error!("{}: {}", filename, err))?;
+
+        Self::from_words(&words).map_err(|err| match err {
+            EngineError::Shader(reason) => shader_error!("{}: {}", filename, reason),
+            err => err,
+        })
+    }
+
+    pub fn from_words(words: &[u32]) -> Result<Self, EngineError> {
+        let module = Module::parse(words)?;
+
+        let stage = match module.execution_model {
+            Some(execution_model::VERTEX) => vk::ShaderStageFlags::VERTEX,
+            Some(execution_model::FRAGMENT) => vk::ShaderStageFlags::FRAGMENT,
+            Some(execution_model::GL_COMPUTE) => vk::ShaderStageFlags::COMPUTE,
+            Some(model) => bail!("unsupported execution model {}", model),
+            None => bail!("no entry point"),
+        };
+
+        let mut reflection = Self {
+            stage,
+            push_constants: None,
+            bindings: vec![],
+            structs: HashMap::new(),
+        };
+
+        for variable in &module.variables {
+            let pointee = match module.get_type(variable.pointer_type)? {
+                Type::Pointer { pointee, .. } => *pointee,
+                _ => bail!("variable {} is not a pointer", variable.id),
+            };
+
+            match variable.storage_class {
+                storage_class::PUSH_CONSTANT => {
+                    reflection.push_constants = Some(reflection.struct_layout(&module, pointee)?);
+                }
+                storage_class::UNIFORM
+                | storage_class::STORAGE_BUFFER
+                | storage_class::UNIFORM_CONSTANT => {
+                    let (Some(set), Some(binding)) = (
+                        module.decoration(variable.id, decoration::DESCRIPTOR_SET),
+                        module.decoration(variable.id, decoration::BINDING),
+                    ) else {
+                        continue;
+                    };
+
+                    let (element, descriptor_count) = match module.get_type(pointee)? {
+                        Type::Array { element, length } => (*element, *length),
+                        Type::RuntimeArray { element } => (*element, 0),
+                        _ => (pointee, 1),
+                    };
+                    let descriptor_type =
+                        descriptor_type(&module, variable.storage_class, element)?;
+                    // buffer blocks end up in structs
+                    if let Type::Struct { .. } = module.get_type(element)? {
+                        reflection.struct_layout(&module, element)?;
+                    }
+
+                    reflection.bindings.push(Binding {
+                        set: set[0],
+                        binding: binding[0],
+                        descriptor_type,
+                        descriptor_count,
+                        stage,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(reflection)
+    }
+
+    // records every nested struct (and the ones behind buffer references) in self.structs
+    fn struct_layout(&mut self, module: &Module, id: u32) -> Result<StructLayout, EngineError> {
+        let Type::Struct { members } = module.get_type(id)? else {
+            bail!("type {} is not a struct", id);
+        };
+
+        let mut layout = StructLayout {
+            name: module.name(id),
+            size: 0,
+            array_stride: None,
+            members: vec![],
+        };
+
+        for (index, &member_type) in members.iter().enumerate() {
+            let index = index as u32;
+            let offset = module
+                .member_decoration(id, index, decoration::OFFSET)
+                .map(|literals| literals[0])
+                .ok_or_else(|| shader_error!("{}.{} has no offset", layout.name, index))?;
+            let size = self.type_size(module, member_type, Some((id, index)))?;
+
+            layout.size = layout.size.max(offset + size);
+            layout.members.push(MemberLayout {
+                name: module
+                    .member_names
+                    .get(&(id, index))
+                    .cloned()
+                    .unwrap_or_default(),
+                offset,
+            });
+        }
+
+        if !self.structs.contains_key(&layout.name) {
+            self.structs.insert(layout.name.clone(), layout.clone());
+        }
+
+        Ok(layout)
+    }
+
+    fn type_size(
+        &mut self,
+        module: &Module,
+        id: u32,
+        member: Option<(u32, u32)>, // matrix stride/majorness are member decorations
+    ) -> Result<u32, EngineError> {
+        let size = match module.get_type(id)?.clone() {
+            Type::Bool => 4,
+            Type::Int { width } | Type::Float { width } => width / 8,
+            Type::Vector { component, count } => count * self.type_size(module, component, None)?,
+            Type::Matrix { column, count } => {
+                let matrix_stride = member.and_then(|(struct_id, index)| {
+                    module.member_decoration(struct_id, index, decoration::MATRIX_STRIDE)
+                });
+                let row_major = member.is_some_and(|(struct_id, index)| {
+                    module
+                        .member_decoration(struct_id, index, decoration::ROW_MAJOR)
+                        .is_some()
+                });
+                let rows = match module.get_type(column)? {
+                    Type::Vector { count, .. } => *count,
+                    _ => bail!("matrix column {} is not a vector", column),
+                };
+
+                match matrix_stride {
+                    Some(stride) if row_major => rows * stride[0],
+                    Some(stride) => count * stride[0],
+                    None => count * self.type_size(module, column, None)?,
+                }
+            }
+            Type::Array { element, length } => length * self.array_stride(module, id, element)?,
+            Type::RuntimeArray { element } => {
+                self.array_stride(module, id, element)?;
+                0
+            }
+            Type::Struct { .. } => self.struct_layout(module, id)?.size,
+            Type::Pointer {
+                storage_class: storage_class::PHYSICAL_STORAGE_BUFFER,
+                pointee,
+            } => {
+                // buffer reference, the pointed block is only reflected once
+                if let Type::Struct { .. } = module.get_type(pointee)? {
+                    if !self.structs.contains_key(&module.name(pointee)) {
+                        self.struct_layout(module, pointee)?;
+                    }
+                }
+                8
+            }
+            _ => 0,
+        };
+
+        Ok(size)
+    }
+
+    fn array_stride(&mut self, module: &Module, id: u32, element: u32) -> Result<u32, EngineError> {
+        let stride = match module.decoration(id, decoration::ARRAY_STRIDE) {
+            Some(stride) => stride[0],
+            None => self.type_size(module, element, None)?,
+        };
+
+        if let Type::Struct { .. } = module.get_type(element)? {
+            let mut layout = self.struct_layout(module, element)?;
+            layout.array_stride = Some(stride);
+            self.structs.insert(layout.name.clone(), layout);
+        }
+
+        Ok(stride)
+    }
+}
+
+fn descriptor_type(
+    module: &Module,
+    storage_class: u32,
+    element: u32,
+) -> Result<vk::DescriptorType, EngineError> {
+    let descriptor_type = match module.get_type(element)? {
+        Type::Struct { .. } if storage_class == storage_class::STORAGE_BUFFER => {
+            vk::DescriptorType::STORAGE_BUFFER
+        }
+        // SPIR-V < 1.3 declares storage buffers as Uniform + BufferBlock
+        Type::Struct { .. }
+            if module
+                .decoration(element, decoration::BUFFER_BLOCK)
+                .is_some() =>
+        {
+            vk::DescriptorType::STORAGE_BUFFER
+        }
+        Type::Struct { .. } => vk::DescriptorType::UNIFORM_BUFFER,
+        Type::SampledImage => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        Type::Image { sampled: 2 } => vk::DescriptorType::STORAGE_IMAGE,
+        Type::Image { .. } => vk::DescriptorType::SAMPLED_IMAGE,
+        Type::Sampler => vk::DescriptorType::SAMPLER,
+        other => bail!("unsupported descriptor type {:?}", other),
+    };
+
+    Ok(descriptor_type)
+}
+
+// Single range covering every stage block, stages are merged
+pub fn push_constant_ranges(reflections: &[ShaderReflection]) -> Vec<vk::PushConstantRange> {
+    let mut range: Option<vk::PushConstantRange> = None;
+
+    for reflection in reflections {
+        let Some(block) = &reflection.push_constants else {
+            continue;
+        };
+        let start = block.members.iter().map(|m| m.offset).min().unwrap_or(0);
+
+        range = Some(match range {
+            None => vk::PushConstantRange::default()
+                .stage_flags(reflection.stage)
+                .offset(start)
+                .size(block.size - start),
+            Some(range) => {
+                let offset = range.offset.min(start);
+                let end = (range.offset + range.size).max(block.size);
+                range
+                    .stage_flags(range.stage_flags | reflection.stage)
+                    .offset(offset)
+                    .size(end - offset)
+            }
+        });
+    }
+
+    range.into_iter().collect()
+}
+
+// Bindings of a set across stages, a binding used by several stages gets their union
+pub fn descriptor_set_layout_bindings(
+    reflections: &[ShaderReflection],
+    set: u32,
+) -> Result<Vec<vk::DescriptorSetLayoutBinding<'static>>, EngineError> {
+    let mut bindings: Vec<vk::DescriptorSetLayoutBinding> = vec![];
+
+    for binding in reflections
+        .iter()
+        .flat_map(|reflection| &reflection.bindings)
+        .filter(|binding| binding.set == set)
+    {
+        match bindings.iter_mut().find(|b| b.binding == binding.binding) {
+            Some(existing) if existing.descriptor_type != binding.descriptor_type => bail!(
+                "set {} binding {} is {:?} and {:?} depending on the stage",
+                set,
+                binding.binding,
+                existing.descriptor_type,
+                binding.descriptor_type
+            ),
+            Some(existing) => existing.stage_flags |= binding.stage,
+            None => bindings.push(
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(binding.binding)
+                    .descriptor_type(binding.descriptor_type)
+                    .descriptor_count(binding.descriptor_count)
+                    .stage_flags(binding.stage),
+            ),
+        }
+    }
+
+    bindings.sort_by_key(|b| b.binding);
+    Ok(bindings)
+}
+
+pub fn create_descriptor_set_layout(
+    device: &ash::Device,
+    reflections: &[ShaderReflection],
+    set: u32,
+) -> Result<vk::DescriptorSetLayout, EngineError> {
+    let bindings = descriptor_set_layout_bindings(reflections, set)?;
+    let info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+
+    Ok(unsafe { device.create_descriptor_set_layout(&info, None)? })
+}
+
+/**
+ * Size and field offsets of a #[repr(C)] rust type, see rust_layout!
+ * fields are compared in declaration order with the shader members,
+ * extra trailing fields (explicit padding) are ignored.
+ */
+#[derive(Debug)]
+pub struct RustLayout {
+    pub name: &'static str,
+    pub size: usize,
+    pub fields: Vec<(&'static str, usize)>,
+}
+
+macro_rules! rust_layout {
+    ($type:ty { $($field:ident),* $(,)? }) => {
+        $crate::ft_vk::reflection::RustLayout {
+            name: stringify!($type),
+            size: std::mem::size_of::<$type>(),
+            fields: vec![$((stringify!($field), std::mem::offset_of!($type, $field))),*],
+        }
+    };
+}
+pub(crate) use rust_layout;
+
+// Every difference between the shader struct and the rust type, empty when they match
+pub fn check_layout(shader: &StructLayout, rust: &RustLayout) -> Vec<String> {
+    let mut mismatches = vec![];
+
+    for (index, member) in shader.members.iter().enumerate() {
+        match rust.fields.get(index) {
+            None => mismatches.push(format!(
+                "{}.{} (offset {}) has no field in {}",
+                shader.name, member.name, member.offset, rust.name
+            )),
+            Some(&(field, offset)) if offset as u32 != member.offset => mismatches.push(format!(
+                "{}.{} is at offset {} but {}.{} is at offset {}",
+                shader.name, member.name, member.offset, rust.name, field, offset
+            )),
+            _ => {}
+        }
+    }
+
+    if (rust.size as u32) < shader.size {
+        mismatches.push(format!(
+            "{} is {} bytes but {} needs {}",
+            rust.name, rust.size, shader.name, shader.size
+        ));
+    }
+    if let Some(stride) = shader.array_stride {
+        if stride != rust.size as u32 {
+            mismatches.push(format!(
+                "{} array stride is {} but {} is {} bytes",
+                shader.name, stride, rust.name, rust.size
+            ));
+        }
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spirv::{op, MAGIC};
+
+    fn instruction(words: &mut Vec<u32>, opcode: u16, operands: &[u32]) {
+        words.push((operands.len() as u32 + 1) << 16 | opcode as u32);
+        words.extend_from_slice(operands);
+    }
+
+    fn string(value: &str) -> Vec<u32> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(bytes.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+
+    /**
+     * Module close to mesh_dba.vert/mesh.frag:
+     * push_constant Constants { mat4 model; mat3 normal_matrix; uint64_t address; }
+     * set 0 binding 0 Params { float shininess; vec4 colors[]; }
+     */
+    fn module(execution_model: u32, push_constants: bool, params_storage_class: u32) -> Vec<u32> {
+        let mut words = vec![MAGIC, 0x0001_0500, 0, 16, 0];
+        let mut emit = |opcode: u16, operands: &[u32]| instruction(&mut words, opcode, operands);
+
+        emit(
+            op::ENTRY_POINT,
+            &[[execution_model, 1].as_slice(), &string("main")].concat(),
+        );
+        emit(op::NAME, &[[8].as_slice(), &string("Constants")].concat());
+        emit(
+            op::MEMBER_NAME,
+            &[[8, 0].as_slice(), &string("model")].concat(),
+        );
+        emit(
+            op::MEMBER_NAME,
+            &[[8, 1].as_slice(), &string("normal_matrix")].concat(),
+        );
+        emit(
+            op::MEMBER_NAME,
+            &[[8, 2].as_slice(), &string("address")].concat(),
+        );
+        emit(op::NAME, &[[13].as_slice(), &string("Params")].concat());
+        emit(op::MEMBER_DECORATE, &[8, 0, decoration::OFFSET, 0]);
+        emit(op::MEMBER_DECORATE, &[8, 0, decoration::MATRIX_STRIDE, 16]);
+        emit(op::MEMBER_DECORATE, &[8, 1, decoration::OFFSET, 64]);
+        emit(op::MEMBER_DECORATE, &[8, 1, decoration::MATRIX_STRIDE, 16]);
+        emit(op::MEMBER_DECORATE, &[8, 2, decoration::OFFSET, 112]);
+        emit(op::DECORATE, &[12, decoration::ARRAY_STRIDE, 16]);
+        emit(op::MEMBER_DECORATE, &[13, 0, decoration::OFFSET, 0]);
+        emit(op::MEMBER_DECORATE, &[13, 1, decoration::OFFSET, 16]);
+        emit(op::DECORATE, &[15, decoration::DESCRIPTOR_SET, 0]);
+        emit(op::DECORATE, &[15, decoration::BINDING, 0]);
+        emit(op::TYPE_FLOAT, &[2, 32]);
+        emit(op::TYPE_VECTOR, &[3, 2, 4]);
+        emit(op::TYPE_VECTOR, &[4, 2, 3]);
+        emit(op::TYPE_MATRIX, &[5, 3, 4]);
+        emit(op::TYPE_MATRIX, &[6, 4, 3]);
+        emit(op::TYPE_INT, &[7, 64, 0]);
+        emit(op::TYPE_STRUCT, &[8, 5, 6, 7]);
+        emit(op::TYPE_POINTER, &[9, storage_class::PUSH_CONSTANT, 8]);
+        if push_constants {
+            emit(op::VARIABLE, &[9, 10, storage_class::PUSH_CONSTANT]);
+        }
+        emit(op::TYPE_RUNTIME_ARRAY, &[12, 3]);
+        emit(op::TYPE_STRUCT, &[13, 2, 12]);
+        emit(op::TYPE_POINTER, &[14, params_storage_class, 13]);
+        emit(op::VARIABLE, &[14, 15, params_storage_class]);
+
+        words
+    }
+
+    fn vertex() -> ShaderReflection {
+        let words = module(execution_model::VERTEX, true, storage_class::STORAGE_BUFFER);
+        ShaderReflection::from_words(&words).unwrap()
+    }
+
+    fn fragment() -> ShaderReflection {
+        let words = module(
+            execution_model::FRAGMENT,
+            false,
+            storage_class::STORAGE_BUFFER,
+        );
+        ShaderReflection::from_words(&words).unwrap()
+    }
+
+    #[test]
+    fn reflect_push_constants() {
+        let vert = vertex();
+        let push_constants = vert.push_constants.unwrap();
+
+        assert_eq!(vert.stage, vk::ShaderStageFlags::VERTEX);
+        assert_eq!(push_constants.name, "Constants");
+        assert_eq!(push_constants.size, 120);
+        let members: Vec<(&str, u32)> = push_constants
+            .members
+            .iter()
+            .map(|member| (member.name.as_str(), member.offset))
+            .collect();
+        assert_eq!(
+            members,
+            [("model", 0), ("normal_matrix", 64), ("address", 112)]
+        );
+        assert!(fragment().push_constants.is_none());
+    }
+
+    #[test]
+    fn reflect_bindings() {
+        let vert = vertex();
+
+        assert_eq!(vert.bindings.len(), 1);
+        let binding = &vert.bindings[0];
+        assert_eq!((binding.set, binding.binding), (0, 0));
+        assert_eq!(binding.descriptor_type, vk::DescriptorType::STORAGE_BUFFER);
+        assert_eq!(binding.descriptor_count, 1);
+
+        let params = &vert.structs["Params"];
+        assert_eq!(params.size, 16); // runtime array is unsized
+        assert_eq!(params.members[1].offset, 16);
+    }
+
+    #[test]
+    fn reject_unknown_module() {
+        assert!(ShaderReflection::from_words(&[MAGIC, 0x0001_0500, 0, 1, 0]).is_err());
+        assert!(ShaderReflection::from_words(&[0; 5]).is_err());
+    }
+
+    #[test]
+    fn merge_stage_bindings() {
+        let bindings = descriptor_set_layout_bindings(&[vertex(), fragment()], 0).unwrap();
+
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(
+            bindings[0].stage_flags,
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT
+        );
+        assert!(descriptor_set_layout_bindings(&[vertex()], 1)
+            .unwrap()
+            .is_empty());
+
+        let uniform = module(execution_model::FRAGMENT, false, storage_class::UNIFORM);
+        let uniform = ShaderReflection::from_words(&uniform).unwrap();
+        assert_eq!(
+            uniform.bindings[0].descriptor_type,
+            vk::DescriptorType::UNIFORM_BUFFER
+        );
+        assert!(descriptor_set_layout_bindings(&[vertex(), uniform], 0).is_err());
+    }
+
+    #[test]
+    fn merge_push_constant_ranges() {
+        let ranges = push_constant_ranges(&[vertex(), fragment()]);
+
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].stage_flags, vk::ShaderStageFlags::VERTEX);
+        assert_eq!((ranges[0].offset, ranges[0].size), (0, 120));
+        assert!(push_constant_ranges(&[fragment()]).is_empty());
+    }
+
+    #[test]
+    fn rust_layout_offsets() {
+        #[allow(dead_code)] // only measured
+        #[repr(C)]
+        struct Constants {
+            model: [f32; 16],
+            normal_matrix: [[f32; 4]; 3],
+            address: u64,
+        }
+        #[allow(dead_code)]
+        #[repr(C)]
+        struct Packed {
+            model: [f32; 16],
+            normal_matrix: [[f32; 3]; 3],
+            address: u64,
+        }
+
+        let push_constants = vertex().push_constants.unwrap();
+        let rust = rust_layout!(Constants {
+            model,
+            normal_matrix,
+            address
+        });
+        assert_eq!(rust.fields[2], ("address", 112));
+        assert!(check_layout(&push_constants, &rust).is_empty());
+
+        // mat3 without the vec4 padding, address lands at 104 and the type is too small
+        let mismatches = check_layout(
+            &push_constants,
+            &rust_layout!(Packed {
+                model,
+                normal_matrix,
+                address
+            }),
+        );
+        assert_eq!(mismatches.len(), 2);
+        assert!(mismatches[0].contains("offset 112"));
+
+        let missing = check_layout(&push_constants, &rust_layout!(Constants { model }));
+        assert_eq!(missing.len(), 2);
+
+        let array = StructLayout {
+            array_stride: Some(128),
+            ..push_constants
+        };
+        assert_eq!(check_layout(&array, &rust).len(), 1);
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use crate::ft_vk::EngineError;
+
+// Subset of the SPIR-V specification needed for reflection
+pub const MAGIC: u32 = 0x0723_0203;
+
+pub mod op {
+    pub const NAME: u16 = 5;
+    pub const MEMBER_NAME: u16 = 6;
+    pub const ENTRY_POINT: u16 = 15;
+    pub const TYPE_BOOL: u16 = 20;
+    pub const TYPE_INT: u16 = 21;
+    pub const TYPE_FLOAT: u16 = 22;
+    pub const TYPE_VECTOR: u16 = 23;
+    pub const TYPE_MATRIX: u16 = 24;
+    pub const TYPE_IMAGE: u16 = 25;
+    pub const TYPE_SAMPLER: u16 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u16 = 27;
+    pub const TYPE_ARRAY: u16 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u16 = 29;
+    pub const TYPE_STRUCT: u16 = 30;
+    pub const TYPE_POINTER: u16 = 32;
+    pub const CONSTANT: u16 = 43;
+    pub const VARIABLE: u16 = 59;
+    pub const DECORATE: u16 = 71;
+    pub const MEMBER_DECORATE: u16 = 72;
+}
+
+pub mod decoration {
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ROW_MAJOR: u32 = 4;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+pub mod storage_class {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const UNIFORM: u32 = 2;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+    pub const PHYSICAL_STORAGE_BUFFER: u32 = 5349;
+}
+
+pub mod execution_model {
+    pub const VERTEX: u32 = 0;
+    pub const FRAGMENT: u32 = 4;
+    pub const GL_COMPUTE: u32 = 5;
+}
+
+#[derive(Debug, Clone)]
+pub enum Type {
+    Bool,
+    Int { width: u32 },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { storage_class: u32, pointee: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub id: u32,
+    pub pointer_type: u32,
+    pub storage_class: u32,
+}
+
+// Parsed module, ids are kept as in the binary
+#[derive(Debug, Default)]
+pub struct Module {
+    pub execution_model: Option<u32>,
+    pub names: HashMap<u32, String>,
+    pub member_names: HashMap<(u32, u32), String>,
+    pub decorations: HashMap<u32, HashMap<u32, Vec<u32>>>,
+    pub member_decorations: HashMap<(u32, u32), HashMap<u32, Vec<u32>>>,
+    pub types: HashMap<u32, Type>,
+    pub constants: HashMap<u32, u32>, // 32 bits integer constants (array lengths)
+    pub variables: Vec<Variable>,
+}
+
+impl Module {
+    pub fn parse(words: &[u32]) -> Result<Self, EngineError> {
+        if words.len() < 5 || words[0] != MAGIC {
+            bail!("not a SPIR-V module");
+        }
+
+        let mut module = Module::default();
+        let mut cursor = 5;
+        while cursor < words.len() {
+            let word_count = (words[cursor] >> 16) as usize;
+            let opcode = (words[cursor] & 0xffff) as u16;
+            if word_count == 0 || cursor + word_count > words.len() {
+                bail!("truncated instruction at word {}", cursor);
+            }
+            module.parse_instruction(opcode, &words[cursor + 1..cursor + word_count])?;
+            cursor += word_count;
+        }
+
+        Ok(module)
+    }
+
+    fn parse_instruction(&mut self, opcode: u16, operands: &[u32]) -> Result<(), EngineError> {
+        let operand = |index: usize| {
+            operands
+                .get(index)
+                .copied()
+                .ok_or_else(|| shader_error!("missing operand {} of opcode {}", index, opcode))
+        };
+
+        match opcode {
+            op::NAME => {
+                self.names.insert(operand(0)?, parse_string(&operands[1..]));
+            }
+            op::MEMBER_NAME => {
+                self.member_names
+                    .insert((operand(0)?, operand(1)?), parse_string(&operands[2..]));
+            }
+            op::ENTRY_POINT => {
+                // a single entry point per module is assumed (glslc output)
+                self.execution_model.get_or_insert(operand(0)?);
+            }
+            op::TYPE_BOOL => {
+                self.types.insert(operand(0)?, Type::Bool);
+            }
+            op::TYPE_INT => {
+                self.types
+                    .insert(operand(0)?, Type::Int { width: operand(1)? });
+            }
+            op::TYPE_FLOAT => {
+                self.types
+                    .insert(operand(0)?, Type::Float { width: operand(1)? });
+            }
+            op::TYPE_VECTOR => {
+                let (component, count) = (operand(1)?, operand(2)?);
+                self.types
+                    .insert(operand(0)?, Type::Vector { component, count });
+            }
+            op::TYPE_MATRIX => {
+                let (column, count) = (operand(1)?, operand(2)?);
+                self.types
+                    .insert(operand(0)?, Type::Matrix { column, count });
+            }
+            op::TYPE_IMAGE => {
+                // result, sampled type, dim, depth, arrayed, ms, sampled
+                self.types.insert(
+                    operand(0)?,
+                    Type::Image {
+                        sampled: operand(6)?,
+                    },
+                );
+            }
+            op::TYPE_SAMPLER => {
+                self.types.insert(operand(0)?, Type::Sampler);
+            }
+            op::TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0)?, Type::SampledImage);
+            }
+            op::TYPE_ARRAY => {
+                let length = *self
+                    .constants
+                    .get(&operand(2)?)
+                    .ok_or_else(|| shader_error!("array length is not a constant"))?;
+                let element = operand(1)?;
+                self.types
+                    .insert(operand(0)?, Type::Array { element, length });
+            }
+            op::TYPE_RUNTIME_ARRAY => {
+                let element = operand(1)?;
+                self.types
+                    .insert(operand(0)?, Type::RuntimeArray { element });
+            }
+            op::TYPE_STRUCT => {
+                let members = operands[1..].to_vec();
+                self.types.insert(operand(0)?, Type::Struct { members });
+            }
+            op::TYPE_POINTER => {
+                let (storage_class, pointee) = (operand(1)?, operand(2)?);
+                self.types.insert(
+                    operand(0)?,
+                    Type::Pointer {
+                        storage_class,
+                        pointee,
+                    },
+                );
+            }
+            op::CONSTANT => {
+                // result type, result, value (only the low word matters for lengths)
+                self.constants.insert(operand(1)?, operand(2)?);
+            }
+            op::VARIABLE => {
+                self.variables.push(Variable {
+                    pointer_type: operand(0)?,
+                    id: operand(1)?,
+                    storage_class: operand(2)?,
+                });
+            }
+            op::DECORATE => {
+                self.decorations
+                    .entry(operand(0)?)
+                    .or_default()
+                    .insert(operand(1)?, operands[2..].to_vec());
+            }
+            op::MEMBER_DECORATE => {
+                self.member_decorations
+                    .entry((operand(0)?, operand(1)?))
+                    .or_default()
+                    .insert(operand(2)?, operands[3..].to_vec());
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn decoration(&self, id: u32, decoration: u32) -> Option<&[u32]> {
+        self.decorations
+            .get(&id)
+            .and_then(|decorations| decorations.get(&decoration))
+            .map(|literals| literals.as_slice())
+    }
+
+    pub fn member_decoration(&self, id: u32, member: u32, decoration: u32) -> Option<&[u32]> {
+        self.member_decorations
+            .get(&(id, member))
+            .and_then(|decorations| decorations.get(&decoration))
+            .map(|literals| literals.as_slice())
+    }
+
+    pub fn get_type(&self, id: u32) -> Result<&Type, EngineError> {
+        self.types
+            .get(&id)
+            .ok_or_else(|| shader_error!("unknown type id {}", id))
+    }
+
+    pub fn name(&self, id: u32) -> String {
+        self.names.get(&id).cloned().unwrap_or_default()
+    }
+}
+
+// nul terminated UTF-8, packed little endian in words
+fn parse_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&byte| byte != 0)
+        .collect();
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_invalid() {
+        assert!(Module::parse(&[]).is_err());
+        assert!(Module::parse(&[0xdead_beef, 0x0001_0500, 0, 1, 0]).is_err());
+        // OpTypeBool announcing 3 words with a single one left
+        assert!(Module::parse(&[MAGIC, 0x0001_0500, 0, 2, 0, 3 << 16 | 20]).is_err());
+        // zero word count would loop forever
+        assert!(Module::parse(&[MAGIC, 0x0001_0500, 0, 2, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn parse_names_and_types() {
+        let words = [
+            MAGIC,
+            0x0001_0500,
+            0,
+            3,
+            0,
+            // OpName %1 "Light"
+            4 << 16 | op::NAME as u32,
+            1,
+            u32::from_le_bytes(*b"Ligh"),
+            u32::from_le_bytes(*b"t\0\0\0"),
+            // OpTypeFloat %2 32
+            3 << 16 | op::TYPE_FLOAT as u32,
+            2,
+            32,
+            // OpTypeVector %3 %2 4
+            4 << 16 | op::TYPE_VECTOR as u32,
+            3,
+            2,
+            4,
+        ];
+        let module = Module::parse(&words).unwrap();
+
+        assert_eq!(module.name(1), "Light");
+        assert_eq!(module.name(2), "");
+        assert!(matches!(module.get_type(2), Ok(Type::Float { width: 32 })));
+        assert!(matches!(
+            module.get_type(3),
+            Ok(Type::Vector {
+                component: 2,
+                count: 4
+            })
+        ));
+        assert!(module.get_type(4).is_err());
+    }
+
+    #[test]
+    fn parse_string_stops_at_nul() {
+        let words = [u32::from_le_bytes(*b"main"), 0];
+        assert_eq!(parse_string(&words), "main");
+        assert_eq!(parse_string(&[u32::from_le_bytes(*b"ab\0c")]), "ab");
+    }
+}
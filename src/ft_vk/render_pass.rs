@@ -1,26 +1,43 @@
 use ash::vk;
 
-pub fn create_default(device: &ash::Device, format: vk::Format) -> vk::RenderPass {
+use super::error::EngineError;
+
+// attachments: color, depth, then the resolve target when samples > 1 (see Swapchain::get_framebuffers)
+pub fn create_default(
+    device: &ash::Device,
+    format: vk::Format,
+    final_layout: vk::ImageLayout, // PRESENT_SRC_KHR or TRANSFER_SRC_OPTIMAL when offscreen
+    samples: vk::SampleCountFlags,
+) -> Result<vk::RenderPass, EngineError> {
+    let multisampled = samples != vk::SampleCountFlags::TYPE_1;
+
     //COLOR
     let color_attachment = vk::AttachmentDescription::default()
         .format(format)
-        .samples(vk::SampleCountFlags::TYPE_1)
+        .samples(samples)
         .load_op(vk::AttachmentLoadOp::CLEAR)
-        .store_op(vk::AttachmentStoreOp::STORE)
         .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
         .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-        .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+        .initial_layout(vk::ImageLayout::UNDEFINED);
+    let color_attachment = if multisampled {
+        // resolved at the end of the subpass, samples are discarded
+        color_attachment
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+    } else {
+        color_attachment
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .final_layout(final_layout)
+    };
 
     let color_attachment_ref = vk::AttachmentReference::default()
         .attachment(0) //index link to renderpass.attachments
         .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
 
     //DEPTH
-    let depth_format = vk::Format::D32_SFLOAT; // duplicated in swapchain::create_depth_image
     let depth_attachement = vk::AttachmentDescription::default()
-        .format(depth_format)
-        .samples(vk::SampleCountFlags::TYPE_1)
+        .format(super::swapchain::DEPTH_FORMAT)
+        .samples(samples)
         .load_op(vk::AttachmentLoadOp::CLEAR)
         .store_op(vk::AttachmentStoreOp::STORE)
         .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
@@ -32,12 +49,33 @@ pub fn create_default(device: &ash::Device, format: vk::Format) -> vk::RenderPas
         .attachment(1)
         .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
 
+    //RESOLVE
+    let resolve_attachment = vk::AttachmentDescription::default()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(final_layout);
+
+    let resolve_attachment_ref = vk::AttachmentReference::default()
+        .attachment(2)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
     //SUBPASS
     let color_attachments = [color_attachment_ref];
+    let resolve_attachments = [resolve_attachment_ref];
     let subpass_desc = vk::SubpassDescription::default()
         .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
         .color_attachments(&color_attachments)
         .depth_stencil_attachment(&depth_attachment_ref);
+    let subpass_desc = if multisampled {
+        subpass_desc.resolve_attachments(&resolve_attachments)
+    } else {
+        subpass_desc
+    };
 
     let color_dependency = vk::SubpassDependency::default()
         .src_subpass(vk::SUBPASS_EXTERNAL)
@@ -63,12 +101,16 @@ pub fn create_default(device: &ash::Device, format: vk::Format) -> vk::RenderPas
 
     // RENDER_PASS
     let subpasses = [subpass_desc];
-    let attachments = [color_attachment, depth_attachement];
+    let attachments = if multisampled {
+        vec![color_attachment, depth_attachement, resolve_attachment]
+    } else {
+        vec![color_attachment, depth_attachement]
+    };
     let dependencies = [color_dependency, depth_dependency];
     let render_pass_info = vk::RenderPassCreateInfo::default()
         .subpasses(&subpasses)
         .attachments(&attachments)
         .dependencies(&dependencies);
 
-    unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    Ok(unsafe { device.create_render_pass(&render_pass_info, None)? })
 }
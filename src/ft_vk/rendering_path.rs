@@ -0,0 +1,43 @@
+use crate::conf;
+
+/**
+ * How Engine::draw_frame begins rendering, chosen at engine creation.
+ * Dynamic uses vkCmdBeginRendering (Vulkan 1.3 dynamicRendering) with explicit layout barriers,
+ * no VkRenderPass nor VkFramebuffer is created (Engine::render_pass is null).
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingPath {
+    RenderPass,
+    Dynamic,
+}
+
+impl Default for RenderingPath {
+    // "render_pass" or "dynamic", defaults to RenderPass
+    fn default() -> Self {
+        match std::env::var(conf::RENDERING_PATH_ENV).as_deref() {
+            Ok("render_pass") | Err(_) => Self::RenderPass,
+            Ok("dynamic") => Self::Dynamic,
+            Ok(value) => {
+                log::warn!(
+                    "{}={} is not a rendering path (render_pass, dynamic)",
+                    conf::RENDERING_PATH_ENV,
+                    value
+                );
+                Self::RenderPass
+            }
+        }
+    }
+}
+
+impl RenderingPath {
+    // Dynamic falls back to RenderPass when the device lacks dynamicRendering
+    pub fn resolve(self, dynamic_rendering: bool) -> Self {
+        if self == Self::Dynamic && !dynamic_rendering {
+            log::warn!("dynamicRendering unsupported, using render passes");
+            return Self::RenderPass;
+        }
+        log::info!("Rendering path: {:?}", self);
+
+        self
+    }
+}
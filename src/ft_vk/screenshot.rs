@@ -0,0 +1,279 @@
+use std::path::{Path, PathBuf};
+
+use ash::vk;
+use vk_mem::Alloc;
+
+use super::EngineError;
+use crate::helpers::image::{self, ImageFormat};
+
+// Frame copied to host memory, RGB8 (sRGB encoded) rows without padding
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<u8>,
+}
+
+impl Screenshot {
+    /**
+     * 4 bytes per pixel in the color attachment format, BGRA formats are swizzled.
+     * Other formats (10 bits, float, ...) are rejected, see supports_format.
+     */
+    pub fn from_pixels(
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        format: vk::Format,
+    ) -> Result<Self, EngineError> {
+        let [r, g, b] = rgb_offsets(format).ok_or(vk::Result::ERROR_FORMAT_NOT_SUPPORTED)?;
+        let rgb = pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[r], pixel[g], pixel[b]])
+            .collect();
+
+        Ok(Self { width, height, rgb })
+    }
+
+    pub fn supports_format(format: vk::Format) -> bool {
+        rgb_offsets(format).is_some()
+    }
+
+    // Written as <dir>/screenshot_<utc timestamp>.<extension>, returns the file path
+    pub fn save(&self, dir: &Path, format: ImageFormat) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("screenshot_{}.{}", timestamp(), format.extension()));
+        image::write_rgb(&path, format, self.width, self.height, &self.rgb)?;
+
+        Ok(path)
+    }
+}
+
+/**
+ * Host visible buffer receiving a copy of a color image.
+ * The copy is recorded in a command buffer, read once its submission is complete.
+ */
+pub struct ReadbackBuffer {
+    buffer: vk::Buffer,
+    allocation: vk_mem::Allocation,
+    extent: vk::Extent2D,
+}
+
+impl ReadbackBuffer {
+    // 4 bytes per pixel formats only
+    pub fn new(allocator: &vk_mem::Allocator, extent: vk::Extent2D) -> Result<Self, EngineError> {
+        let buffer_info = vk::BufferCreateInfo::default()
+            .size((extent.width * extent.height * 4) as vk::DeviceSize)
+            .usage(vk::BufferUsageFlags::TRANSFER_DST);
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_RANDOM
+                | vk_mem::AllocationCreateFlags::MAPPED,
+            usage: vk_mem::MemoryUsage::Auto,
+            ..Default::default()
+        };
+        let (buffer, allocation) =
+            unsafe { allocator.create_buffer(&buffer_info, &allocation_info) }?;
+
+        Ok(Self {
+            buffer,
+            allocation,
+            extent,
+        })
+    }
+
+    /**
+     * Copy image after the render pass wrote it, image is in layout before and after the copy.
+     * Transitions through TRANSFER_SRC_OPTIMAL when layout is another one (e.g. PRESENT_SRC_KHR).
+     */
+    pub unsafe fn record_copy(
+        &self,
+        device: &ash::Device,
+        cmd: vk::CommandBuffer,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+    ) {
+        let subresource_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(1)
+            .layer_count(1);
+
+        // make the render pass writes visible
+        let to_transfer = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .old_layout(layout)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range);
+        device.cmd_pipeline_barrier(
+            cmd,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_transfer],
+        );
+
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .layer_count(1),
+            )
+            .image_extent(vk::Extent3D {
+                depth: 1,
+                ..self.extent.into()
+            });
+        device.cmd_copy_image_to_buffer(
+            cmd,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            self.buffer,
+            &[region],
+        );
+
+        // host read once the submission fence is signaled
+        let to_host = vk::BufferMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(self.buffer)
+            .size(vk::WHOLE_SIZE);
+        let back = (layout != vk::ImageLayout::TRANSFER_SRC_OPTIMAL).then(|| {
+            vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+                .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .new_layout(layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(subresource_range)
+        });
+        device.cmd_pipeline_barrier(
+            cmd,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST | vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[to_host],
+            back.as_slice(),
+        );
+    }
+
+    // Pixels as in the image format, the copy must be complete
+    pub unsafe fn read(&self, allocator: &vk_mem::Allocator) -> Vec<u8> {
+        let size = (self.extent.width * self.extent.height * 4) as usize;
+
+        allocator
+            .invalidate_allocation(&self.allocation, 0, vk::WHOLE_SIZE)
+            .unwrap();
+        let data_ptr = allocator.get_allocation_info(&self.allocation).mapped_data as *const u8;
+
+        std::slice::from_raw_parts(data_ptr, size).to_vec()
+    }
+
+    pub unsafe fn destroy(mut self, allocator: &vk_mem::Allocator) {
+        allocator.destroy_buffer(self.buffer, &mut self.allocation);
+    }
+}
+
+// Byte offsets of red, green and blue in a pixel of the 8 bits RGBA/BGRA formats
+fn rgb_offsets(format: vk::Format) -> Option<[usize; 3]> {
+    match format {
+        vk::Format::R8G8B8A8_SRGB
+        | vk::Format::R8G8B8A8_UNORM
+        | vk::Format::A8B8G8R8_SRGB_PACK32 // packed ABGR is RGBA in little endian memory
+        | vk::Format::A8B8G8R8_UNORM_PACK32 => Some([0, 1, 2]),
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => Some([2, 1, 0]),
+        _ => None,
+    }
+}
+
+// UTC, YYYYMMDD_HHMMSS_mmm
+fn timestamp() -> String {
+    let elapsed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    format_timestamp(elapsed)
+}
+
+// elapsed since the unix epoch
+fn format_timestamp(elapsed: std::time::Duration) -> String {
+    let seconds = elapsed.as_secs();
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // civil date from days since 1970-01-01 (proleptic gregorian calendar)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153; // March is 0
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}_{:02}{:02}{:02}_{:03}",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_pixels_swizzle() {
+        let pixels = [1, 2, 3, 255, 4, 5, 6, 255];
+
+        let rgba = Screenshot::from_pixels(2, 1, &pixels, vk::Format::R8G8B8A8_SRGB).unwrap();
+        assert_eq!(rgba.rgb, [1, 2, 3, 4, 5, 6]);
+        let bgra = Screenshot::from_pixels(2, 1, &pixels, vk::Format::B8G8R8A8_UNORM).unwrap();
+        assert_eq!(bgra.rgb, [3, 2, 1, 6, 5, 4]);
+    }
+
+    #[test]
+    fn from_pixels_rejects_other_formats() {
+        for format in [
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::Format::R8G8B8_SRGB,
+        ] {
+            assert!(!Screenshot::supports_format(format));
+            assert_eq!(
+                Screenshot::from_pixels(1, 1, &[0; 8], format).err(),
+                Some(EngineError::Vulkan(vk::Result::ERROR_FORMAT_NOT_SUPPORTED))
+            );
+        }
+    }
+
+    #[test]
+    fn timestamp_civil_date() {
+        let at = |seconds: u64, millis: u64| {
+            format_timestamp(
+                std::time::Duration::from_secs(seconds) + std::time::Duration::from_millis(millis),
+            )
+        };
+
+        assert_eq!(at(0, 0), "19700101_000000_000");
+        assert_eq!(at(951_782_400, 0), "20000229_000000_000"); // leap day of a century
+        assert_eq!(at(1_700_000_000, 123), "20231114_221320_123");
+        assert_eq!(at(4_107_542_399, 999), "21000228_235959_999"); // 2100 isn't leap
+        assert_eq!(at(253_402_300_799, 0), "99991231_235959_000");
+    }
+}
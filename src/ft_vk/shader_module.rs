@@ -1,13 +1,20 @@
 use ash::vk;
 
+use super::EngineError;
+
 pub struct ShaderModule;
 
 impl ShaderModule {
-    pub fn create_from_file(device: &ash::Device, filename: &str) -> vk::ShaderModule {
-        let mut shader_file = std::fs::File::open(filename).unwrap();
-        let shader_code = ash::util::read_spv(&mut shader_file).unwrap();
+    // EngineError::Shader when filename is missing or isn't SPIR-V
+    pub fn create_from_file(
+        device: &ash::Device,
+        filename: &str,
+    ) -> Result<vk::ShaderModule, EngineError> {
+        let shader_code = std::fs::File::open(filename)
+            .and_then(|mut shader_file| ash::util::read_spv(&mut shader_file))
+            .map_err(|err| EngineError::Shader(format!("{}: {}", filename, err)))?;
 
         let createinfo = ash::vk::ShaderModuleCreateInfo::default().code(&shader_code);
-        unsafe { device.create_shader_module(&createinfo, None).unwrap() }
+        Ok(unsafe { device.create_shader_module(&createinfo, None)? })
     }
 }
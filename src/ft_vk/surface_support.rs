@@ -1,6 +1,6 @@
 use ash::vk;
 
-use crate::conf;
+use super::EngineError;
 
 pub struct SurfaceSupport {
     pub capabilities: vk::SurfaceCapabilitiesKHR,
@@ -13,59 +13,21 @@ impl SurfaceSupport {
         physical_device: vk::PhysicalDevice,
         surface: vk::SurfaceKHR,
         surface_loader: &ash::khr::surface::Instance,
-    ) -> Self {
+    ) -> Result<Self, EngineError> {
         let capabilities = unsafe {
-            surface_loader
-                .get_physical_device_surface_capabilities(physical_device, surface)
-                .unwrap()
+            surface_loader.get_physical_device_surface_capabilities(physical_device, surface)?
         };
         let present_modes = unsafe {
-            surface_loader
-                .get_physical_device_surface_present_modes(physical_device, surface)
-                .unwrap()
+            surface_loader.get_physical_device_surface_present_modes(physical_device, surface)?
         };
         let formats = unsafe {
-            surface_loader
-                .get_physical_device_surface_formats(physical_device, surface)
-                .unwrap()
+            surface_loader.get_physical_device_surface_formats(physical_device, surface)?
         };
 
-        Self {
+        Ok(Self {
             capabilities,
             present_modes,
             formats,
-        }
-    }
-
-    pub fn is_physical_device_compatible(
-        &self,
-        instance: &ash::Instance,
-        physical_device: vk::PhysicalDevice,
-    ) -> bool {
-        let extensions: Vec<String> = unsafe {
-            instance
-                .enumerate_device_extension_properties(physical_device)
-                .unwrap()
-                .iter()
-                .map(|ext| {
-                    let c_str = std::ffi::CStr::from_ptr(ext.extension_name.as_ptr());
-                    c_str.to_string_lossy().into_owned()
-                })
-                .collect()
-        };
-
-        if conf::DEVICE_EXTENSION_NAMES
-            .iter()
-            .all(|e| extensions.contains(&e.to_str().map(|s| s.to_string()).unwrap()))
-        {
-            if conf::DEVICE_EXTENSION_NAMES.contains(&ash::khr::swapchain::NAME)
-                && self.present_modes.is_empty()
-                || self.formats.is_empty()
-            {
-                return false;
-            }
-        }
-
-        true
+        })
     }
 }
@@ -4,18 +4,30 @@ use ash::vk;
 use vk_mem::Alloc;
 
 use super::{
-    allocated_image::AllocatedImage, queue_famillies::QueueFamilies,
-    surface_support::SurfaceSupport,
+    allocated_image::AllocatedImage, error::EngineError, present_mode::PresentModePreference,
+    queue_famillies::QueueFamilies, surface_support::SurfaceSupport,
 };
 
+pub const DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT; // render pass, pipelines and depth images
+
 pub struct Swapchain {
     pub extent: vk::Extent2D,
     pub surface_format: vk::SurfaceFormatKHR,
-    pub chain: vk::SwapchainKHR,
+    pub chain: vk::SwapchainKHR, // null when offscreen
     pub min_image_count: u32,
+    pub present_mode: vk::PresentModeKHR, // active mode, FIFO when offscreen
+    pub transfer_src: bool,               // images can be copied from (screenshots)
 
     // RENDERING
+    pub images: Vec<vk::Image>,
     pub image_views: Vec<vk::ImageView>,
+    pub render_semaphores: Vec<vk::Semaphore>, // per image: signaled by the submit, waited by the present
+    pub images_in_flight: Vec<vk::Fence>, // per image: fence of the last frame rendering into it (null if none)
+    offscreen_images: Vec<AllocatedImage>, // color images owned by an offscreen chain
+
+    // MSAA, color images are resolved into images, empty when samples is TYPE_1
+    pub samples: vk::SampleCountFlags,
+    pub msaa_color_images: Vec<AllocatedImage>,
 
     // DEPTH
     pub depth_images: Vec<AllocatedImage>,
@@ -31,12 +43,23 @@ impl Swapchain {
         surface: vk::SurfaceKHR,
         queue_families: &QueueFamilies,
         old_swapchain: Option<vk::SwapchainKHR>,
-    ) -> Self {
-        let surface_format = choose_surface_format(&surface_support.formats);
+        samples: vk::SampleCountFlags,
+        present_mode: PresentModePreference,
+    ) -> Result<Self, EngineError> {
+        let surface_format = choose_surface_format(&surface_support.formats)?;
         let extent = choose_extent(&surface_support.capabilities, physical_size);
 
         // Swapchain
-        let present_mode = choose_present_mode(&surface_support.present_modes);
+        let present_mode = present_mode.choose(&surface_support.present_modes);
+        let transfer_src = surface_support
+            .capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC);
+        let image_usage = if transfer_src {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
         let queue_family_indices = [queue_families.graphics, queue_families.present];
 
         // Determine optimal image count
@@ -62,7 +85,7 @@ impl Swapchain {
                 .image_color_space(surface_format.color_space)
                 .image_extent(extent)
                 .image_array_layers(1)
-                .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+                .image_usage(image_usage)
                 .pre_transform(surface_support.capabilities.current_transform)
                 .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
                 .present_mode(present_mode)
@@ -83,18 +106,19 @@ impl Swapchain {
                 create_info
             };
 
-            unsafe {
-                swapchain_loader
-                    .create_swapchain(&create_info, None)
-                    .unwrap()
-            }
+            unsafe { swapchain_loader.create_swapchain(&create_info, None)? }
         };
 
         // Images
-        let images: Vec<vk::Image> =
-            unsafe { swapchain_loader.get_swapchain_images(swapchain).unwrap() };
+        let images: Vec<vk::Image> = unsafe { swapchain_loader.get_swapchain_images(swapchain)? };
         let mut image_views: Vec<vk::ImageView> = vec![];
+        let mut render_semaphores: Vec<vk::Semaphore> = vec![];
         let mut depth_images: Vec<AllocatedImage> = vec![];
+        let mut msaa_color_images: Vec<AllocatedImage> = vec![];
+        let image_extent = vk::Extent3D {
+            depth: 1,
+            ..extent.into()
+        };
 
         for &image in &images {
             let image_view_info = vk::ImageViewCreateInfo::default()
@@ -113,28 +137,116 @@ impl Swapchain {
                         .level_count(1)
                         .layer_count(1),
                 );
-            let image_view = unsafe { device.create_image_view(&image_view_info, None).unwrap() };
+            let image_view = unsafe { device.create_image_view(&image_view_info, None)? };
+
+            let depth_image = create_depth_image(device, allocator, image_extent, samples)?;
+            if samples != vk::SampleCountFlags::TYPE_1 {
+                msaa_color_images.push(create_msaa_color_image(
+                    device,
+                    allocator,
+                    image_extent,
+                    surface_format.format,
+                    samples,
+                )?);
+            }
 
-            let depth_image = create_depth_image(
-                device,
-                allocator,
-                vk::Extent3D {
-                    depth: 1,
-                    ..extent.into()
-                },
-            );
+            let render_semaphore =
+                unsafe { device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)? };
 
             image_views.push(image_view);
+            render_semaphores.push(render_semaphore);
             depth_images.push(depth_image);
         }
 
-        Self {
+        Ok(Self {
             chain: swapchain,
             extent,
             surface_format,
+            images_in_flight: vec![vk::Fence::null(); images.len()],
+            images,
             image_views,
+            render_semaphores,
+            offscreen_images: vec![],
             min_image_count,
+            present_mode,
+            transfer_src,
+            samples,
+            msaa_color_images,
             depth_images,
+        })
+    }
+
+    // Single color image rendered without a surface, read back with Engine::read_back
+    pub fn new_offscreen(
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+        physical_size: (u32, u32),
+        samples: vk::SampleCountFlags,
+        format: vk::Format,
+    ) -> Result<Self, EngineError> {
+        let surface_format = vk::SurfaceFormatKHR {
+            format,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        };
+        let extent = vk::Extent2D {
+            width: physical_size.0,
+            height: physical_size.1,
+        };
+        let image_extent = vk::Extent3D {
+            depth: 1,
+            ..extent.into()
+        };
+
+        let color_image = create_attachment_image(
+            device,
+            allocator,
+            image_extent,
+            surface_format.format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+            vk::SampleCountFlags::TYPE_1,
+        )?;
+        let depth_image = create_depth_image(device, allocator, image_extent, samples)?;
+        let msaa_color_images = if samples != vk::SampleCountFlags::TYPE_1 {
+            vec![create_msaa_color_image(
+                device,
+                allocator,
+                image_extent,
+                surface_format.format,
+                samples,
+            )?]
+        } else {
+            vec![]
+        };
+
+        Ok(Self {
+            chain: vk::SwapchainKHR::null(),
+            extent,
+            surface_format,
+            images: vec![color_image.image],
+            image_views: vec![color_image.image_view],
+            render_semaphores: vec![], // nothing is presented
+            images_in_flight: vec![vk::Fence::null()],
+            offscreen_images: vec![color_image],
+            min_image_count: 1,
+            present_mode: vk::PresentModeKHR::FIFO,
+            transfer_src: true,
+            samples,
+            msaa_color_images,
+            depth_images: vec![depth_image],
+        })
+    }
+
+    pub fn is_offscreen(&self) -> bool {
+        self.chain == vk::SwapchainKHR::null()
+    }
+
+    // layout of the color images once the render pass ends
+    pub fn present_layout(&self) -> vk::ImageLayout {
+        if self.is_offscreen() {
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+        } else {
+            vk::ImageLayout::PRESENT_SRC_KHR
         }
     }
 
@@ -142,35 +254,64 @@ impl Swapchain {
         self.extent.width as f32 / self.extent.height as f32
     }
 
-    //self dropped because shoud not be used more
+    // handles are left dangling, only used when the engine is torn down (see Engine::recreate)
     pub fn destroy(
-        self,
+        &mut self,
         device: &ash::Device,
         allocator: &vk_mem::Allocator,
         swapchain_loader: &ash::khr::swapchain::Device,
     ) {
-        for &image_view in &self.image_views {
+        for image_view in self.image_views.drain(..) {
             unsafe { device.destroy_image_view(image_view, None) };
         }
-        for depth_image in self.depth_images {
+        for render_semaphore in self.render_semaphores.drain(..) {
+            unsafe { device.destroy_semaphore(render_semaphore, None) };
+        }
+        for mut offscreen_image in self.offscreen_images.drain(..) {
+            unsafe {
+                allocator.destroy_image(offscreen_image.image, &mut offscreen_image.allocation)
+            }
+        }
+        for mut msaa_color_image in self.msaa_color_images.drain(..) {
+            unsafe {
+                device.destroy_image_view(msaa_color_image.image_view, None);
+                allocator.destroy_image(msaa_color_image.image, &mut msaa_color_image.allocation)
+            }
+        }
+        for depth_image in self.depth_images.drain(..) {
             unsafe { device.destroy_image_view(depth_image.image_view, None) };
 
             let mut depth_image = depth_image;
             unsafe { allocator.destroy_image(depth_image.image, &mut depth_image.allocation) }
         }
-        unsafe { swapchain_loader.destroy_swapchain(self.chain, None) };
+        if self.chain != vk::SwapchainKHR::null() {
+            unsafe { swapchain_loader.destroy_swapchain(self.chain, None) };
+        }
     }
 
     pub fn get_framebuffers(
         &self,
         device: &ash::Device,
         render_pass: vk::RenderPass,
-    ) -> Vec<vk::Framebuffer> {
+    ) -> Result<Vec<vk::Framebuffer>, EngineError> {
         let mut framebuffers = Vec::with_capacity(self.image_views.len());
 
         //When rendering, the swapchain will give us the index of the image to render into, so we will use the framebuffer of the same index.
-        for (&image_view, depth_image) in self.image_views.iter().zip(self.depth_images.iter()) {
-            let attachments = [image_view, depth_image.image_view];
+        for (index, (&image_view, depth_image)) in self
+            .image_views
+            .iter()
+            .zip(self.depth_images.iter())
+            .enumerate()
+        {
+            // matches render_pass::create_default attachments order
+            let attachments = match self.msaa_color_images.get(index) {
+                Some(msaa_color_image) => vec![
+                    msaa_color_image.image_view,
+                    depth_image.image_view,
+                    image_view,
+                ],
+                None => vec![image_view, depth_image.image_view],
+            };
             let framebuffer_info = vk::FramebufferCreateInfo::default()
                 .render_pass(render_pass)
                 .attachments(&attachments)
@@ -178,36 +319,30 @@ impl Swapchain {
                 .height(self.extent.height)
                 .layers(1);
 
-            let framebuffer =
-                unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() };
+            let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None)? };
             framebuffers.push(framebuffer);
         }
 
-        framebuffers
+        Ok(framebuffers)
     }
 }
 
-fn choose_surface_format(formats: &Vec<vk::SurfaceFormatKHR>) -> vk::SurfaceFormatKHR {
+fn choose_surface_format(
+    formats: &Vec<vk::SurfaceFormatKHR>,
+) -> Result<vk::SurfaceFormatKHR, EngineError> {
     formats
         .iter()
         .find(|p| {
             p.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
                 && p.format == vk::Format::B8G8R8A8_SRGB
         })
+        .or(formats.first())
         .cloned()
-        .unwrap_or_else(|| formats.get(0).cloned().unwrap())
+        .ok_or_else(|| EngineError::Surface("no surface format".to_string()))
 }
 
-// MAILBOX ?? FIFO
-fn choose_present_mode(presents: &Vec<vk::PresentModeKHR>) -> vk::PresentModeKHR {
-    if presents.contains(&vk::PresentModeKHR::MAILBOX) {
-        return vk::PresentModeKHR::MAILBOX;
-    }
-
-    vk::PresentModeKHR::FIFO
-}
-
-fn choose_extent(
+// current_extent when the surface defines it (0x0 while minimized on some platforms), else the clamped window size
+pub fn choose_extent(
     capabilities: &vk::SurfaceCapabilitiesKHR,
     (width, height): (u32, u32),
 ) -> vk::Extent2D {
@@ -231,14 +366,52 @@ fn create_depth_image(
     device: &ash::Device,
     allocator: &vk_mem::Allocator,
     extent: vk::Extent3D,
-) -> AllocatedImage {
-    let format = vk::Format::D32_SFLOAT;
+    samples: vk::SampleCountFlags,
+) -> Result<AllocatedImage, EngineError> {
+    create_attachment_image(
+        device,
+        allocator,
+        extent,
+        DEPTH_FORMAT,
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        vk::ImageAspectFlags::DEPTH,
+        samples,
+    )
+}
+
+// only rendered to and resolved, never stored
+fn create_msaa_color_image(
+    device: &ash::Device,
+    allocator: &vk_mem::Allocator,
+    extent: vk::Extent3D,
+    format: vk::Format,
+    samples: vk::SampleCountFlags,
+) -> Result<AllocatedImage, EngineError> {
+    create_attachment_image(
+        device,
+        allocator,
+        extent,
+        format,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+        vk::ImageAspectFlags::COLOR,
+        samples,
+    )
+}
 
+fn create_attachment_image(
+    device: &ash::Device,
+    allocator: &vk_mem::Allocator,
+    extent: vk::Extent3D,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+    aspect_mask: vk::ImageAspectFlags,
+    samples: vk::SampleCountFlags,
+) -> Result<AllocatedImage, EngineError> {
     let (image, allocation) = {
         let image_create_info = vk::ImageCreateInfo::default()
             .image_type(vk::ImageType::TYPE_2D)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .samples(samples)
+            .usage(usage)
             .extent(extent)
             .format(format)
             .mip_levels(1)
@@ -248,11 +421,7 @@ fn create_depth_image(
             required_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
             ..Default::default()
         };
-        unsafe {
-            allocator
-                .create_image(&image_create_info, &allocation_create_info)
-                .unwrap()
-        }
+        unsafe { allocator.create_image(&image_create_info, &allocation_create_info)? }
     };
 
     let image_view = {
@@ -262,22 +431,18 @@ fn create_depth_image(
             .view_type(vk::ImageViewType::TYPE_2D)
             .subresource_range(
                 vk::ImageSubresourceRange::default()
-                    .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                    .aspect_mask(aspect_mask)
                     .level_count(1)
                     .layer_count(1),
             );
-        unsafe {
-            device
-                .create_image_view(&image_view_create_info, None)
-                .unwrap()
-        }
+        unsafe { device.create_image_view(&image_view_create_info, None)? }
     };
 
-    AllocatedImage {
+    Ok(AllocatedImage {
         image,
         image_view,
         extent,
         allocation,
         format,
-    }
+    })
 }
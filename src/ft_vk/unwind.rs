@@ -0,0 +1,72 @@
+use std::ops::{Deref, DerefMut};
+
+/**
+ * Value destroyed when dropped, unless it is taken back with into_inner.
+ * Engine::build and render_screenshot wrap the objects they create so an early return destroys
+ * them in reverse order.
+ */
+pub struct Unwind<T, F: FnOnce(T)> {
+    value: Option<T>,
+    destroy: Option<F>,
+}
+
+impl<T, F: FnOnce(T)> Unwind<T, F> {
+    pub fn new(value: T, destroy: F) -> Self {
+        Self {
+            value: Some(value),
+            destroy: Some(destroy),
+        }
+    }
+
+    // keeps the value alive, destroy is dropped without being called
+    pub fn into_inner(mut self) -> T {
+        self.destroy = None;
+        self.value.take().unwrap()
+    }
+}
+
+impl<T, F: FnOnce(T)> Deref for Unwind<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().unwrap()
+    }
+}
+
+impl<T, F: FnOnce(T)> DerefMut for Unwind<T, F> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().unwrap()
+    }
+}
+
+impl<T, F: FnOnce(T)> Drop for Unwind<T, F> {
+    fn drop(&mut self) {
+        if let (Some(value), Some(destroy)) = (self.value.take(), self.destroy.take()) {
+            destroy(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn unwind_in_reverse_order() {
+        let destroyed = RefCell::new(vec![]);
+        let build = |fail: bool| -> Result<(u32, u32), ()> {
+            let first = Unwind::new(1, |value| destroyed.borrow_mut().push(value));
+            let second = Unwind::new(*first + 1, |value| destroyed.borrow_mut().push(value));
+            if fail {
+                return Err(());
+            }
+            Ok((first.into_inner(), second.into_inner()))
+        };
+
+        assert_eq!(build(false), Ok((1, 2)));
+        assert!(destroyed.borrow().is_empty());
+        assert_eq!(build(true), Err(()));
+        assert_eq!(*destroyed.borrow(), [2, 1]);
+    }
+}
@@ -0,0 +1,487 @@
+use std::collections::VecDeque;
+
+use ash::vk;
+use vk_mem::Alloc;
+
+use super::{allocated_buffer::AllocatedBuffer, error::EngineError, QueueFamilies};
+
+const STAGING_ALIGNMENT: vk::DeviceSize = 16; // copy offsets, covers every texel size
+
+// Submission of the uploads recorded so far, see Uploader::is_complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadToken(u64);
+
+struct Submission {
+    token: u64,
+    fence: vk::Fence,
+    semaphore: Option<vk::Semaphore>, // transfer -> graphics, dedicated transfer family only
+    command_buffers: Vec<(vk::CommandPool, vk::CommandBuffer)>,
+    ring_tail: u64, // staging ring is free up to here once complete
+    dedicated_staging: Vec<AllocatedBuffer>, // uploads bigger than the ring
+}
+
+/**
+ * Batched buffer uploads.
+ * Data is packed in a ring allocated staging buffer and the copies are recorded in a single
+ * command buffer, submitted by submit without waiting for the device.
+ * With a dedicated transfer queue family the copies run on it and the destination buffers are
+ * released to the graphics family, then acquired by a graphics queue submission.
+ * Later graphics submissions are ordered after the copies, the token only tells when the
+ * staging memory is reused and the upload is done.
+ */
+pub struct Uploader {
+    transfer_family: u32,
+    graphics_family: u32,
+    transfer_queue: vk::Queue,
+    graphics_queue: vk::Queue,
+    transfer_pool: vk::CommandPool,
+    graphics_pool: vk::CommandPool, // acquire barriers, same as transfer_pool without a transfer family
+
+    staging: AllocatedBuffer, // persistently mapped ring
+    staging_ptr: *mut u8,
+    head: u64, // monotonic, offset in the ring is head % size
+    tail: u64,
+
+    recording: Option<vk::CommandBuffer>,
+    pending_buffers: Vec<vk::Buffer>, // destinations of the recording command buffer
+    pending_staging: Vec<AllocatedBuffer>,
+
+    in_flight: VecDeque<Submission>,
+    next_token: u64,
+    completed: u64,
+}
+
+impl Uploader {
+    pub fn new(
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+        queue_families: &QueueFamilies,
+        graphics_queue: vk::Queue,
+        staging_size: vk::DeviceSize,
+    ) -> Result<Self, EngineError> {
+        let transfer_family = queue_families.transfer.unwrap_or(queue_families.graphics);
+        let transfer_queue = match queue_families.transfer {
+            Some(family) => unsafe { device.get_device_queue(family, 0) },
+            None => graphics_queue,
+        };
+
+        let create_pool = |family: u32| {
+            let info = vk::CommandPoolCreateInfo::default()
+                .queue_family_index(family)
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT);
+            unsafe { device.create_command_pool(&info, None) }
+        };
+        let transfer_pool = create_pool(transfer_family)?;
+        let graphics_pool = if queue_families.transfer.is_some() {
+            create_pool(queue_families.graphics)?
+        } else {
+            transfer_pool
+        };
+
+        let staging = create_staging_buffer(allocator, staging_size)?;
+        let staging_ptr = allocator
+            .get_allocation_info(&staging.allocation)
+            .mapped_data as *mut u8;
+        if staging_ptr.is_null() {
+            let mut staging = staging;
+            unsafe {
+                allocator.destroy_buffer(staging.buffer, &mut staging.allocation);
+                if graphics_pool != transfer_pool {
+                    device.destroy_command_pool(graphics_pool, None);
+                }
+                device.destroy_command_pool(transfer_pool, None);
+            }
+            return Err(EngineError::Vulkan(vk::Result::ERROR_MEMORY_MAP_FAILED));
+        }
+
+        if queue_families.transfer.is_some() {
+            log::info!(
+                "Uploads on dedicated transfer queue family {}",
+                transfer_family
+            );
+        }
+
+        Ok(Self {
+            transfer_family,
+            graphics_family: queue_families.graphics,
+            transfer_queue,
+            graphics_queue,
+            transfer_pool,
+            graphics_pool,
+            staging,
+            staging_ptr,
+            head: 0,
+            tail: 0,
+            recording: None,
+            pending_buffers: vec![],
+            pending_staging: vec![],
+            in_flight: VecDeque::new(),
+            next_token: 1,
+            completed: 0,
+        })
+    }
+
+    fn dedicated_family(&self) -> bool {
+        self.transfer_family != self.graphics_family
+    }
+
+    /**
+     * Copy data into dst at dst_offset, dst needs TRANSFER_DST usage and EXCLUSIVE sharing.
+     * The copy is recorded now and executed by the next submit.
+     */
+    pub fn upload_buffer(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+        dst: vk::Buffer,
+        dst_offset: vk::DeviceSize,
+        data: &[u8],
+    ) -> Result<(), EngineError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let size = data.len() as vk::DeviceSize;
+
+        let (src, src_offset) = if size <= self.staging.buffer_size {
+            let offset = self.allocate(device, allocator, size)?;
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    data.as_ptr(),
+                    self.staging_ptr.add(offset as usize),
+                    data.len(),
+                );
+                allocator.flush_allocation(&self.staging.allocation, offset, size)?;
+            }
+            (self.staging.buffer, offset)
+        } else {
+            // bigger than the whole ring, staged on its own
+            let staging = create_staging_buffer(allocator, size)?;
+            unsafe {
+                let data_ptr = allocator
+                    .get_allocation_info(&staging.allocation)
+                    .mapped_data;
+                std::ptr::copy_nonoverlapping(data.as_ptr(), data_ptr as *mut u8, data.len());
+                allocator.flush_allocation(&staging.allocation, 0, size)?;
+            }
+            let src = staging.buffer;
+            self.pending_staging.push(staging);
+            (src, 0)
+        };
+
+        let cmd = self.command_buffer(device)?;
+        let region = vk::BufferCopy::default()
+            .src_offset(src_offset)
+            .dst_offset(dst_offset)
+            .size(size);
+        unsafe { device.cmd_copy_buffer(cmd, src, dst, &[region]) };
+
+        if !self.pending_buffers.contains(&dst) {
+            self.pending_buffers.push(dst);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Submit the recorded uploads, never waits for the device.
+     * Without recorded uploads the token of the previous submission is returned.
+     */
+    pub fn submit(&mut self, device: &ash::Device) -> Result<UploadToken, EngineError> {
+        let Some(cmd) = self.recording.take() else {
+            return Ok(UploadToken(self.next_token - 1));
+        };
+        let buffers = std::mem::take(&mut self.pending_buffers);
+        let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }?;
+
+        let mut submission = Submission {
+            token: self.next_token,
+            fence,
+            semaphore: None,
+            command_buffers: vec![(self.transfer_pool, cmd)],
+            ring_tail: self.head,
+            dedicated_staging: std::mem::take(&mut self.pending_staging),
+        };
+        self.next_token += 1;
+
+        unsafe {
+            if self.dedicated_family() {
+                // release on the transfer family
+                let release = self.ownership_barriers(
+                    &buffers,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::empty(),
+                );
+                device.cmd_pipeline_barrier(
+                    cmd,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &release,
+                    &[],
+                );
+                device.end_command_buffer(cmd)?;
+
+                let semaphore =
+                    device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?;
+                let command_buffers = [cmd];
+                let signal_semaphores = [semaphore];
+                let submit_info = vk::SubmitInfo::default()
+                    .command_buffers(&command_buffers)
+                    .signal_semaphores(&signal_semaphores);
+                device.queue_submit(self.transfer_queue, &[submit_info], vk::Fence::null())?;
+
+                // acquire on the graphics family, later graphics submissions are ordered after it
+                let acquire_cmd = begin_command_buffer(device, self.graphics_pool)?;
+                let acquire = self.ownership_barriers(
+                    &buffers,
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::MEMORY_READ,
+                );
+                device.cmd_pipeline_barrier(
+                    acquire_cmd,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &acquire,
+                    &[],
+                );
+                device.end_command_buffer(acquire_cmd)?;
+
+                let command_buffers = [acquire_cmd];
+                let wait_stages = [vk::PipelineStageFlags::ALL_COMMANDS];
+                let submit_info = vk::SubmitInfo::default()
+                    .command_buffers(&command_buffers)
+                    .wait_semaphores(&signal_semaphores)
+                    .wait_dst_stage_mask(&wait_stages);
+                device.queue_submit(self.graphics_queue, &[submit_info], fence)?;
+
+                submission.semaphore = Some(semaphore);
+                submission
+                    .command_buffers
+                    .push((self.graphics_pool, acquire_cmd));
+            } else {
+                // copies visible to every later command of the queue
+                let barrier = vk::MemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::MEMORY_READ);
+                device.cmd_pipeline_barrier(
+                    cmd,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::DependencyFlags::empty(),
+                    &[barrier],
+                    &[],
+                    &[],
+                );
+                device.end_command_buffer(cmd)?;
+
+                let command_buffers = [cmd];
+                let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+                device.queue_submit(self.transfer_queue, &[submit_info], fence)?;
+            }
+        }
+
+        let token = UploadToken(submission.token);
+        self.in_flight.push_back(submission);
+        Ok(token)
+    }
+
+    // Polls the submissions, never blocks
+    pub fn is_complete(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+        token: UploadToken,
+    ) -> Result<bool, EngineError> {
+        self.retire_completed(device, allocator)?;
+        Ok(token.0 <= self.completed)
+    }
+
+    // Blocks until token is complete, submits the recorded uploads when token is not submitted yet
+    pub fn wait(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+        token: UploadToken,
+    ) -> Result<(), EngineError> {
+        if token.0 >= self.next_token {
+            self.submit(device)?;
+        }
+        while token.0 > self.completed {
+            let Some(submission) = self.in_flight.front() else {
+                break;
+            };
+            unsafe { device.wait_for_fences(&[submission.fence], true, u64::MAX) }?;
+            self.retire_completed(device, allocator)?;
+        }
+
+        Ok(())
+    }
+
+    // Recycles the staging memory and command buffers of completed submissions
+    pub fn retire_completed(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+    ) -> Result<(), EngineError> {
+        while let Some(submission) = self.in_flight.front() {
+            if !unsafe { device.get_fence_status(submission.fence) }? {
+                break;
+            }
+            let submission = self.in_flight.pop_front().unwrap();
+            self.release(device, allocator, submission);
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_mem::Allocator) {
+        if let Some(cmd) = self.recording.take() {
+            unsafe { device.free_command_buffers(self.transfer_pool, &[cmd]) };
+        }
+        self.pending_buffers.clear();
+        for mut staging in self.pending_staging.drain(..) {
+            unsafe { allocator.destroy_buffer(staging.buffer, &mut staging.allocation) };
+        }
+        while let Some(submission) = self.in_flight.pop_front() {
+            // fails once the device is lost, nothing executes anymore
+            let _ = unsafe { device.wait_for_fences(&[submission.fence], true, u64::MAX) };
+            self.release(device, allocator, submission);
+        }
+
+        unsafe {
+            allocator.destroy_buffer(self.staging.buffer, &mut self.staging.allocation);
+            if self.graphics_pool != self.transfer_pool {
+                device.destroy_command_pool(self.graphics_pool, None);
+            }
+            device.destroy_command_pool(self.transfer_pool, None);
+        }
+    }
+
+    fn release(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+        submission: Submission,
+    ) {
+        unsafe {
+            device.destroy_fence(submission.fence, None);
+            if let Some(semaphore) = submission.semaphore {
+                device.destroy_semaphore(semaphore, None);
+            }
+            for (pool, cmd) in submission.command_buffers {
+                device.free_command_buffers(pool, &[cmd]);
+            }
+            for mut staging in submission.dedicated_staging {
+                allocator.destroy_buffer(staging.buffer, &mut staging.allocation);
+            }
+        }
+        self.tail = submission.ring_tail;
+        self.completed = submission.token;
+    }
+
+    // Ring offset of size free bytes, waits for the oldest submissions when the ring is full
+    fn allocate(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+        size: vk::DeviceSize,
+    ) -> Result<vk::DeviceSize, EngineError> {
+        let capacity = self.staging.buffer_size;
+        let size = size.next_multiple_of(STAGING_ALIGNMENT);
+
+        let mut offset = self.head % capacity;
+        if offset + size > capacity {
+            // not split across the end, skip the remaining bytes
+            self.head += capacity - offset;
+            offset = 0;
+        }
+
+        while self.head + size - self.tail > capacity {
+            if self.in_flight.is_empty() {
+                // the ring is only held by the recording command buffer
+                self.submit(device)?;
+            }
+            match self.in_flight.pop_front() {
+                Some(submission) => {
+                    unsafe { device.wait_for_fences(&[submission.fence], true, u64::MAX) }?;
+                    self.release(device, allocator, submission);
+                }
+                // nothing in use, only the skipped bytes
+                None => self.tail = self.head,
+            }
+        }
+
+        self.head += size;
+        Ok(offset)
+    }
+
+    fn command_buffer(&mut self, device: &ash::Device) -> Result<vk::CommandBuffer, EngineError> {
+        if let Some(cmd) = self.recording {
+            return Ok(cmd);
+        }
+        let cmd = unsafe { begin_command_buffer(device, self.transfer_pool) }?;
+        self.recording = Some(cmd);
+        Ok(cmd)
+    }
+
+    fn ownership_barriers<'a>(
+        &self,
+        buffers: &[vk::Buffer],
+        src_access: vk::AccessFlags,
+        dst_access: vk::AccessFlags,
+    ) -> Vec<vk::BufferMemoryBarrier<'a>> {
+        buffers
+            .iter()
+            .map(|&buffer| {
+                vk::BufferMemoryBarrier::default()
+                    .src_access_mask(src_access)
+                    .dst_access_mask(dst_access)
+                    .src_queue_family_index(self.transfer_family)
+                    .dst_queue_family_index(self.graphics_family)
+                    .buffer(buffer)
+                    .size(vk::WHOLE_SIZE)
+            })
+            .collect()
+    }
+}
+
+unsafe fn begin_command_buffer(
+    device: &ash::Device,
+    pool: vk::CommandPool,
+) -> Result<vk::CommandBuffer, EngineError> {
+    let allocation_info = vk::CommandBufferAllocateInfo::default()
+        .level(vk::CommandBufferLevel::PRIMARY)
+        .command_pool(pool)
+        .command_buffer_count(1);
+    let cmd = device.allocate_command_buffers(&allocation_info)?[0];
+    let begin_info =
+        vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+    device.begin_command_buffer(cmd, &begin_info)?;
+    Ok(cmd)
+}
+
+fn create_staging_buffer(
+    allocator: &vk_mem::Allocator,
+    size: vk::DeviceSize,
+) -> Result<AllocatedBuffer, EngineError> {
+    let buffer_info = vk::BufferCreateInfo::default()
+        .size(size)
+        .usage(vk::BufferUsageFlags::TRANSFER_SRC);
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE
+            | vk_mem::AllocationCreateFlags::MAPPED,
+        usage: vk_mem::MemoryUsage::Auto,
+        ..Default::default()
+    };
+    let (buffer, allocation) = unsafe { allocator.create_buffer(&buffer_info, &allocation_info) }?;
+
+    Ok(AllocatedBuffer {
+        buffer,
+        device_address: None,
+        buffer_size: size,
+        allocation,
+    })
+}
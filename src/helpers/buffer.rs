@@ -1,7 +1,7 @@
 use ash::vk;
 use vk_mem::Alloc;
 
-use crate::ft_vk::allocated_buffer::AllocatedBuffer;
+use crate::ft_vk::{allocated_buffer::AllocatedBuffer, Engine, EngineError};
 
 use super::{arr_to_bytes, print_bytes_in_hex, struct_to_bytes};
 
@@ -20,89 +20,41 @@ impl<T> Loadable for &[T] {
     }
 }
 
+// Buffer created now, data is uploaded by the next Engine::submit_uploads or draw_frame
 pub fn load_buffer(
-    device: &ash::Device,
-    allocator: &mut vk_mem::Allocator,
-    command_pool: vk::CommandPool,
-    cmd: vk::CommandBuffer,
-    graphics_queue: vk::Queue,
+    engine: &mut Engine,
     data: impl Loadable,
-) -> AllocatedBuffer {
+) -> Result<AllocatedBuffer, EngineError> {
     let data = data.as_bytes();
 
-    let mut staging_buffer = load_staging_buffer(allocator, data);
-
-    let (buffer, buffer_size, allocation) = create_buffer(allocator, data.len() as vk::DeviceSize);
-
-    copy_buffer(
-        device,
-        command_pool,
-        graphics_queue,
-        staging_buffer.buffer,
-        buffer,
-        buffer_size,
-    );
-
-    unsafe {
-        allocator.destroy_buffer(staging_buffer.buffer, &mut staging_buffer.allocation);
-    }
+    let (buffer, buffer_size, allocation) = create_buffer(
+        engine.allocator.as_ref().unwrap(),
+        data.len() as vk::DeviceSize,
+    )?;
+    engine.upload_buffer(buffer, 0, data)?;
 
     // is driven by create_buffer allocation
     let device_address = {
         let device_address_info = vk::BufferDeviceAddressInfo::default().buffer(buffer);
-        unsafe { device.get_buffer_device_address(&device_address_info) }
+        unsafe {
+            engine
+                .device
+                .get_buffer_device_address(&device_address_info)
+        }
     };
 
-    AllocatedBuffer {
+    Ok(AllocatedBuffer {
         buffer,
         device_address: Some(device_address),
         buffer_size,
         allocation,
-    }
-}
-
-pub fn load_staging_buffer(allocator: &vk_mem::Allocator, data: &[u8]) -> AllocatedBuffer {
-    let buffer_size = data.len() as vk::DeviceSize;
-    let buffer_create_info = vk::BufferCreateInfo::default()
-        .size(buffer_size)
-        .usage(vk::BufferUsageFlags::TRANSFER_SRC);
-
-    let allocation_create_info = vk_mem::AllocationCreateInfo {
-        flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE
-            | vk_mem::AllocationCreateFlags::MAPPED,
-        usage: vk_mem::MemoryUsage::Auto,
-        ..vk_mem::AllocationCreateInfo::default()
-    };
-
-    let (buffer, mut allocation) = unsafe {
-        allocator
-            .create_buffer(&buffer_create_info, &allocation_create_info)
-            .unwrap()
-    };
-
-    unsafe {
-        let allocation_info = allocator.get_allocation_info(&allocation);
-        let data_ptr = allocation_info.mapped_data;
-
-        if data_ptr.is_null() {
-            panic!("Mapped data pointer is null");
-        }
-
-        std::ptr::copy_nonoverlapping(data.as_ptr(), data_ptr as *mut u8, data.len());
-    }
-
-    AllocatedBuffer {
-        allocation,
-        buffer,
-        buffer_size,
-        device_address: None,
-    }
+    })
 }
 
 pub fn create_buffer(
     allocator: &vk_mem::Allocator,
     buffer_size: vk::DeviceSize,
-) -> (vk::Buffer, vk::DeviceSize, vk_mem::Allocation) {
+) -> Result<(vk::Buffer, vk::DeviceSize, vk_mem::Allocation), EngineError> {
     let buffer_info = vk::BufferCreateInfo::default()
         .size(buffer_size as vk::DeviceSize)
         .usage(
@@ -118,19 +70,15 @@ pub fn create_buffer(
         ..Default::default()
     };
 
-    let (buffer, allocation) = unsafe {
-        allocator
-            .create_buffer(&buffer_info, &allocation_info)
-            .unwrap()
-    };
+    let (buffer, allocation) = unsafe { allocator.create_buffer(&buffer_info, &allocation_info)? };
 
-    (buffer, buffer_size, allocation)
+    Ok((buffer, buffer_size, allocation))
 }
 
 pub fn create_index_buffer(
     allocator: &mut vk_mem::Allocator,
     buffer_size: vk::DeviceSize,
-) -> (vk::Buffer, vk::DeviceSize, vk_mem::Allocation) {
+) -> Result<(vk::Buffer, vk::DeviceSize, vk_mem::Allocation), EngineError> {
     let buffer_info = vk::BufferCreateInfo::default()
         .size(buffer_size as vk::DeviceSize)
         .usage(vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST);
@@ -141,46 +89,7 @@ pub fn create_index_buffer(
         ..vk_mem::AllocationCreateInfo::default()
     };
 
-    let (buffer, allocation) = unsafe {
-        allocator
-            .create_buffer(&buffer_info, &allocation_info)
-            .unwrap()
-    };
-
-    (buffer, buffer_size, allocation)
-}
-
-pub fn copy_buffer(
-    device: &ash::Device,
-    command_pool: vk::CommandPool,
-    queue: vk::Queue,
-    src_buffer: vk::Buffer,
-    dst_buffer: vk::Buffer,
-    size: vk::DeviceSize,
-) {
-    let allocation_info = vk::CommandBufferAllocateInfo::default()
-        .level(vk::CommandBufferLevel::PRIMARY)
-        .command_pool(command_pool)
-        .command_buffer_count(1);
-
-    // RECORD
-    let command_buffer = unsafe { device.allocate_command_buffers(&allocation_info) }.unwrap()[0];
-    let begin_info =
-        vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-    unsafe { device.begin_command_buffer(command_buffer, &begin_info) }.unwrap();
-    let copy_region = vk::BufferCopy::default()
-        .src_offset(0)
-        .dst_offset(0)
-        .size(size);
-    unsafe { device.cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &[copy_region]) };
-    unsafe { device.end_command_buffer(command_buffer) }.unwrap();
-
-    // SEND
-    let command_buffers = [command_buffer];
-    let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
-    unsafe { device.queue_submit(queue, &[submit_info], vk::Fence::null()) }.unwrap();
-
-    unsafe { device.queue_wait_idle(queue) }.unwrap(); // !warn wait idle
+    let (buffer, allocation) = unsafe { allocator.create_buffer(&buffer_info, &allocation_info)? };
 
-    unsafe { device.free_command_buffers(command_pool, &[command_buffer]) }
+    Ok((buffer, buffer_size, allocation))
 }
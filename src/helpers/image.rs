@@ -0,0 +1,312 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ppm => "ppm",
+            Self::Png => "png",
+        }
+    }
+
+    // From the extension of path, None when it's neither .ppm nor .png
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        [Self::Ppm, Self::Png]
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+}
+
+// RGB8 rows without padding
+pub fn write_rgb(
+    path: &Path,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    rgb: &[u8],
+) -> std::io::Result<()> {
+    match format {
+        ImageFormat::Ppm => {
+            let mut file = BufWriter::new(File::create(path)?);
+            write!(file, "P6\n{} {}\n255\n", width, height)?;
+            file.write_all(rgb)?;
+            file.flush()
+        }
+        ImageFormat::Png => write_png(path, width, height, rgb),
+    }
+}
+
+// Binary PPM (P6) as written by write_rgb, returns (width, height, rgb)
+pub fn read_ppm(path: &Path) -> std::io::Result<(u32, u32, Vec<u8>)> {
+    let mut data = vec![];
+    File::open(path)?.read_to_end(&mut data)?;
+
+    parse_ppm(&data)
+}
+
+fn parse_ppm(data: &[u8]) -> std::io::Result<(u32, u32, Vec<u8>)> {
+    let invalid = |reason: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, reason);
+
+    // header: magic, width, height, maxval separated by whitespaces
+    let mut fields: Vec<&str> = vec![];
+    let mut cursor = 0;
+    while fields.len() < 4 {
+        while cursor < data.len() && data[cursor].is_ascii_whitespace() {
+            cursor += 1;
+        }
+        let start = cursor;
+        while cursor < data.len() && !data[cursor].is_ascii_whitespace() {
+            cursor += 1;
+        }
+        if start == cursor {
+            return Err(invalid("truncated ppm header"));
+        }
+        fields.push(std::str::from_utf8(&data[start..cursor]).map_err(|_| invalid("header"))?);
+    }
+    cursor += 1; // single whitespace before pixels
+
+    if fields[0] != "P6" || fields[3] != "255" {
+        return Err(invalid("only 8 bits P6 ppm are supported"));
+    }
+    let width: u32 = fields[1].parse().map_err(|_| invalid("width"))?;
+    let height: u32 = fields[2].parse().map_err(|_| invalid("height"))?;
+
+    let rgb = data
+        .get(cursor..cursor + (width * height * 3) as usize)
+        .ok_or_else(|| invalid("truncated ppm pixels"))?;
+
+    Ok((width, height, rgb.to_vec()))
+}
+
+// Largest per channel difference between two images of the same size, None when sizes differ
+pub fn max_difference(a: &[u8], b: &[u8]) -> Option<u8> {
+    if a.len() != b.len() {
+        return None;
+    }
+
+    Some(
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0),
+    )
+}
+
+/**
+ * 8 bits RGB PNG, no filter and stored (uncompressed) deflate blocks:
+ * files are about the size of a PPM but readable everywhere.
+ */
+pub fn write_png(path: &Path, width: u32, height: u32, rgb: &[u8]) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    // IHDR: size, bit depth 8, color type 2 (RGB), deflate, adaptive filtering, no interlace
+    let mut header = vec![];
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_png_chunk(&mut file, b"IHDR", &header)?;
+
+    // every scanline starts with its filter type (0, none)
+    let row_size = width as usize * 3;
+    let mut scanlines = Vec::with_capacity((row_size + 1) * height as usize);
+    for row in rgb.chunks_exact(row_size) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    write_png_chunk(&mut file, b"IDAT", &zlib_stored(&scanlines))?;
+    write_png_chunk(&mut file, b"IEND", &[])?;
+
+    file.flush()
+}
+
+fn write_png_chunk(file: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    file.write_all(&(data.len() as u32).to_be_bytes())?;
+    file.write_all(kind)?;
+    file.write_all(data)?;
+
+    let crc = crc32(crc32(0, kind), data);
+    file.write_all(&crc.to_be_bytes())
+}
+
+// zlib stream made of stored deflate blocks (at most 65535 bytes each)
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    out.extend_from_slice(&[0x78, 0x01]); // deflate, 32K window, no dictionary
+
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]); // empty final block
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8); // BFINAL, BTYPE 00
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+// continues a crc (0 to start), PNG/zlib polynomial
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 bytes is the largest run that can't overflow before the modulo
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ppm_round_trip() {
+        let rgb: Vec<u8> = (0..2 * 3 * 3).map(|value| value as u8 * 10).collect();
+        let path =
+            std::env::temp_dir().join(format!("scop42_round_trip_{}.ppm", std::process::id()));
+
+        write_rgb(&path, ImageFormat::Ppm, 2, 3, &rgb).unwrap();
+        let read = read_ppm(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.unwrap(), (2, 3, rgb));
+    }
+
+    #[test]
+    fn parse_ppm_header_whitespaces() {
+        assert_eq!(
+            parse_ppm(b"P6 1\n\n1 255\n\x01\x02\x03").unwrap(),
+            (1, 1, vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn parse_ppm_rejects_invalid() {
+        assert!(parse_ppm(b"P3\n1 1\n255\n1 2 3").is_err()); // ascii
+        assert!(parse_ppm(b"P6\n1 1\n65535\n\x00\x01\x00\x02\x00\x03").is_err()); // 16 bits
+        assert!(parse_ppm(b"P6\n2 2\n255\n\x01\x02\x03").is_err()); // truncated pixels
+        assert!(parse_ppm(b"P6\n2").is_err()); // truncated header
+    }
+
+    #[test]
+    fn max_difference_per_channel() {
+        assert_eq!(max_difference(&[10, 20, 30], &[12, 20, 25]), Some(5));
+        assert_eq!(max_difference(&[], &[]), Some(0));
+        assert_eq!(max_difference(&[1, 2], &[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(
+            ImageFormat::from_path(Path::new("out/frame.PNG")),
+            Some(ImageFormat::Png)
+        );
+        assert_eq!(
+            ImageFormat::from_path(Path::new("frame.ppm")),
+            Some(ImageFormat::Ppm)
+        );
+        assert_eq!(ImageFormat::from_path(Path::new("frame.jpg")), None);
+        assert_eq!(ImageFormat::from_path(Path::new("frame")), None);
+    }
+
+    #[test]
+    fn crc32_known_answer() {
+        assert_eq!(crc32(0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf4_3926);
+        assert_eq!(crc32(0, b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(0, b""), 0);
+    }
+
+    #[test]
+    fn adler32_known_answer() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(b""), 1);
+        // long enough to need the modulo
+        assert_eq!(adler32(&[0xff; 6000]), 0xa497_59ea);
+    }
+
+    #[test]
+    fn zlib_stored_blocks() {
+        assert_eq!(
+            zlib_stored(&[]),
+            [0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+        );
+
+        let data: Vec<u8> = (0..70_000).map(|value| value as u8).collect();
+        let stream = zlib_stored(&data);
+        assert_eq!(stream.len(), 2 + 5 + 65_535 + 5 + 4_465 + 4);
+        assert_eq!(stream[2..7], [0, 0xff, 0xff, 0, 0]);
+        assert_eq!(stream[7 + 65_535..12 + 65_535], [1, 0x71, 0x11, 0x8e, 0xee]);
+        assert_eq!(stream[7..7 + 65_535], data[..65_535]);
+        assert_eq!(stream[stream.len() - 4..], adler32(&data).to_be_bytes());
+    }
+
+    #[test]
+    fn png_chunks() {
+        let path = std::env::temp_dir().join(format!("scop42_chunks_{}.png", std::process::id()));
+        write_rgb(&path, ImageFormat::Png, 2, 1, &[255, 0, 0, 0, 0, 255]).unwrap();
+        let png = std::fs::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        let png = png.unwrap();
+
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+        let mut chunks = vec![];
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc, crc32(crc32(0, kind), data));
+            chunks.push((kind.to_vec(), data.to_vec()));
+            rest = &rest[12 + len..];
+        }
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].0, b"IHDR");
+        assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        assert_eq!(chunks[1].0, b"IDAT");
+        assert_eq!(chunks[1].1, zlib_stored(&[0, 255, 0, 0, 0, 0, 255]));
+        assert_eq!(chunks[2], (b"IEND".to_vec(), vec![]));
+    }
+}
@@ -2,9 +2,11 @@ use ash::vk;
 use vk_mem::Alloc;
 
 pub mod buffer;
+pub mod image;
+pub mod logger;
 mod pipeline;
 
-use crate::ft_vk::allocated_buffer::AllocatedBuffer;
+use crate::ft_vk::{allocated_buffer::AllocatedBuffer, EngineError};
 
 pub fn arr_to_bytes<T>(arr: &[T]) -> &[u8] {
     let size = std::mem::size_of::<T>() * arr.len();
@@ -40,3 +42,39 @@ pub fn default_viewports_and_scissors(
     let scissors = vec![scissor];
     return (viewports, scissors);
 }
+
+// Record and submit a one-off command buffer, blocks until the queue is idle
+pub fn immediate_submit(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+    queue: vk::Queue,
+    record: impl FnOnce(vk::CommandBuffer) -> Result<(), EngineError>,
+) -> Result<(), EngineError> {
+    let allocation_info = vk::CommandBufferAllocateInfo::default()
+        .level(vk::CommandBufferLevel::PRIMARY)
+        .command_pool(command_pool)
+        .command_buffer_count(1);
+    let command_buffer = unsafe { device.allocate_command_buffers(&allocation_info) }?[0];
+
+    let submitted = (|| unsafe {
+        // RECORD
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        device.begin_command_buffer(command_buffer, &begin_info)?;
+        record(command_buffer)?;
+        device.end_command_buffer(command_buffer)?;
+
+        // SEND
+        let command_buffers = [command_buffer];
+        let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+        device.queue_submit(queue, &[submit_info], vk::Fence::null())?;
+
+        device.queue_wait_idle(queue)?; // !warn wait idle
+        Ok(())
+    })();
+
+    // freed on failure too, nothing is pending once the queue is idle or the submit failed
+    unsafe { device.free_command_buffers(command_pool, &[command_buffer]) };
+
+    submitted
+}
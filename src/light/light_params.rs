@@ -0,0 +1,41 @@
+use glam::{Mat4, Vec3};
+
+/**
+ * struct LightParams
+ * data is intended to be use with Vulkan directly (std430)
+ * _pad for glsl rounding the struct size up to a vec4
+ */
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Params {
+    pub position: Vec3,
+    pub kind: u32,
+    pub direction: Vec3,
+    pub range: f32,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub inner_cutoff: f32, // cos(inner_angle)
+    pub outer_cutoff: f32, // cos(outer_angle)
+    pub _pad: [f32; 2],
+}
+
+// Params.kind values, must match mesh.frag
+pub const KIND_DIRECTIONAL: u32 = 0;
+pub const KIND_POINT: u32 = 1;
+pub const KIND_SPOT: u32 = 2;
+
+/**
+ * struct SceneHeader
+ * written at the start of the scene buffer, lights[] starts at the next vec4
+ * view_projection is read by the vertex stage, the rest by the fragment stage
+ */
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Header {
+    pub view_projection: Mat4,
+    pub camera_position: Vec3,
+    pub count: u32,
+    pub camera_near: f32,
+    pub camera_far: f32,
+    pub _pad: [f32; 2],
+}
@@ -0,0 +1,245 @@
+mod light_params;
+
+use std::cell::Cell;
+
+use ash::vk;
+use ecs::{storage::ComponentsStorage, Entity};
+use glam::{Mat4, Quat, Vec3};
+
+use crate::{
+    components::{DirectionalLight, PointLight, Position, Rotation, SpotLight},
+    ft_vk::{
+        allocated_buffer::AllocatedBuffer, descriptor_write::DescriptorWriter, Engine, EngineError,
+    },
+    helpers::{arr_to_bytes, buffer::create_buffer, struct_to_bytes},
+};
+
+pub use light_params::{Header, Params, KIND_DIRECTIONAL, KIND_POINT, KIND_SPOT};
+
+// Camera part of the scene buffer
+#[derive(Debug, Clone, Copy)]
+pub struct SceneCamera {
+    pub view_projection: Mat4,
+    pub position: Vec3,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for SceneCamera {
+    fn default() -> Self {
+        Self {
+            view_projection: Mat4::IDENTITY,
+            position: Vec3::ZERO,
+            near: 0.1f32,
+            far: 1.0f32,
+        }
+    }
+}
+
+// Resource filled by the lights system, consumed by the renderer
+#[derive(Debug, Default)]
+pub struct Lights(pub Vec<Params>);
+
+// One storage buffer per frame so camera and lights can be rewritten while previous frames are in flight
+pub struct SceneLights {
+    pub descriptor_sets: Vec<vk::DescriptorSet>,
+    buffers: Vec<AllocatedBuffer>,
+    max_lights: usize,
+    overflow_logged: Cell<bool>, // warn once about the lights above max_lights
+}
+
+impl SceneLights {
+    pub fn new(
+        engine: &mut Engine,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        max_lights: usize,
+    ) -> Result<Self, EngineError> {
+        let buffer_size = (std::mem::size_of::<Header>()
+            + max_lights * std::mem::size_of::<Params>()) as vk::DeviceSize;
+
+        let mut buffers = Vec::with_capacity(engine.frames.len());
+        let mut descriptor_sets = Vec::with_capacity(engine.frames.len());
+
+        for _ in 0..engine.frames.len() {
+            let (buffer, buffer_size, allocation) =
+                create_buffer(engine.allocator.as_ref().unwrap(), buffer_size)?;
+            let descriptor_set = engine
+                .descriptor_allocator
+                .allocate_descriptor_set(&engine.device, descriptor_set_layout)?;
+
+            DescriptorWriter::new()
+                .write_buffer(
+                    0,
+                    buffer,
+                    vk::WHOLE_SIZE,
+                    0,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                )?
+                .update_set(&engine.device, descriptor_set);
+
+            buffers.push(AllocatedBuffer {
+                buffer,
+                device_address: None,
+                buffer_size,
+                allocation,
+            });
+            descriptor_sets.push(descriptor_set);
+        }
+
+        let scene_lights = Self {
+            descriptor_sets,
+            buffers,
+            max_lights,
+            overflow_logged: Cell::new(false),
+        };
+        // buffers are not zero initialized
+        for frame_index in 0..scene_lights.buffers.len() {
+            scene_lights.write(
+                engine.allocator.as_ref().unwrap(),
+                frame_index,
+                &SceneCamera::default(),
+                &[],
+            )?;
+        }
+
+        Ok(scene_lights)
+    }
+
+    // frame_index buffer must not be in use by the gpu (its frame fence is signaled)
+    pub fn write(
+        &self,
+        allocator: &vk_mem::Allocator,
+        frame_index: usize,
+        camera: &SceneCamera,
+        lights: &[Params],
+    ) -> Result<(), EngineError> {
+        let (lights, dropped) = fit_lights(lights, self.max_lights);
+        if dropped > 0 && !self.overflow_logged.replace(true) {
+            log::warn!(
+                "{} lights above the limit of {} are not drawn",
+                dropped,
+                self.max_lights
+            );
+        }
+        let header = Header {
+            view_projection: camera.view_projection,
+            camera_position: camera.position,
+            count: lights.len() as u32,
+            camera_near: camera.near,
+            camera_far: camera.far,
+            ..Default::default()
+        };
+        let buffer = &self.buffers[frame_index];
+
+        unsafe {
+            let data_ptr = allocator.get_allocation_info(&buffer.allocation).mapped_data as *mut u8;
+            if data_ptr.is_null() {
+                return Err(EngineError::Vulkan(vk::Result::ERROR_MEMORY_MAP_FAILED));
+            }
+
+            let header = struct_to_bytes(&header);
+            std::ptr::copy_nonoverlapping(header.as_ptr(), data_ptr, header.len());
+            let lights = arr_to_bytes(lights);
+            std::ptr::copy_nonoverlapping(
+                lights.as_ptr(),
+                data_ptr.add(std::mem::size_of::<Header>()),
+                lights.len(),
+            );
+        }
+
+        allocator.flush_allocation(&buffer.allocation, 0, vk::WHOLE_SIZE)?;
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, allocator: &vk_mem::Allocator) {
+        for mut buffer in self.buffers.drain(..) {
+            unsafe { allocator.destroy_buffer(buffer.buffer, &mut buffer.allocation) };
+        }
+    }
+}
+
+// Lights that fit in the scene buffer and the count of those dropped past max_lights
+fn fit_lights(lights: &[Params], max_lights: usize) -> (&[Params], usize) {
+    let count = lights.len().min(max_lights);
+    (&lights[..count], lights.len() - count)
+}
+
+// Collect every light component into gpu params, lights without Position/Rotation use the origin/identity
+pub fn gather(components: &ComponentsStorage) -> Vec<Params> {
+    let position = |entity: &Entity| {
+        components
+            .get_component::<Position>(entity)
+            .map(|position| position.0)
+            .unwrap_or(Vec3::ZERO)
+    };
+    let direction = |entity: &Entity| {
+        components
+            .get_component::<Rotation>(entity)
+            .map(|rotation| rotation.0)
+            .unwrap_or(Quat::IDENTITY)
+            * Vec3::NEG_Z
+    };
+
+    let mut lights: Vec<Params> = vec![];
+
+    if let Some(storage) = components.get_component_storage::<DirectionalLight>() {
+        lights.extend(storage.iter().map(|(entity, light)| Params {
+            kind: KIND_DIRECTIONAL,
+            direction: direction(entity),
+            color: light.color,
+            intensity: light.intensity,
+            ..Default::default()
+        }));
+    }
+
+    if let Some(storage) = components.get_component_storage::<PointLight>() {
+        lights.extend(storage.iter().map(|(entity, light)| Params {
+            kind: KIND_POINT,
+            position: position(entity),
+            color: light.color,
+            intensity: light.intensity,
+            range: light.range,
+            ..Default::default()
+        }));
+    }
+
+    if let Some(storage) = components.get_component_storage::<SpotLight>() {
+        lights.extend(storage.iter().map(|(entity, light)| Params {
+            kind: KIND_SPOT,
+            position: position(entity),
+            direction: direction(entity),
+            color: light.color,
+            intensity: light.intensity,
+            range: light.range,
+            inner_cutoff: light.inner_angle.to_radians().cos(),
+            outer_cutoff: light.outer_angle.to_radians().cos(),
+            ..Default::default()
+        }));
+    }
+
+    lights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lights_past_the_limit_are_dropped() {
+        let lights: Vec<Params> = (0..5)
+            .map(|index| Params {
+                intensity: index as f32,
+                ..Default::default()
+            })
+            .collect();
+
+        let (fitted, dropped) = fit_lights(&lights, 3);
+        assert_eq!(dropped, 2);
+        let intensities: Vec<f32> = fitted.iter().map(|light| light.intensity).collect();
+        assert_eq!(intensities, [0.0, 1.0, 2.0]);
+
+        let (fitted, dropped) = fit_lights(&lights[..2], 3);
+        assert_eq!((fitted.len(), dropped), (2, 0));
+    }
+}
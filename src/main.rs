@@ -2,9 +2,11 @@
 
 mod components;
 mod conf;
+mod debug_draw;
 mod ft_vk;
 mod helpers;
 mod input;
+mod light;
 pub mod material;
 mod material_asset;
 mod mesh;
@@ -13,18 +15,19 @@ mod mesh_constants;
 pub mod obj_asset;
 mod physics;
 mod renderer;
+mod scene_resources;
 mod traits;
 mod vertex;
 mod window;
 
 use std::{
-    path::{self, Path},
+    path::Path,
     rc::Rc,
     time::{Duration, Instant},
 };
 
 use anyhow::Ok;
-use ash::vk::{self};
+use debug_draw::DebugDraw;
 use ecs::{
     component::Component,
     entity::Entity,
@@ -35,25 +38,28 @@ use ecs::{
     world::{self, World},
 };
 use ft_vk::{
+    debug::DebugConfig,
     descriptor_allocator::DescriptorAllocator,
     descriptor_set_layout::{self, DescriptorSetLayoutCreateInfoBuilder},
     PipelineLayout,
 };
 use glam::{Mat4, Quat, Vec3, Vec3Swizzles};
 use input::recorder::InputRecorder;
-use material::Material;
+use material::{Material, ShadingModes};
 use material_asset::MaterialAsset;
 use mesh::Mesh;
 use mesh_asset::MeshAsset;
 use mesh_constants::MeshConstants;
 use obj_asset::{ObjAssetBuilder, ObjRaw};
-use renderer::MeshRenderer;
+use renderer::RenderQueue;
+use scene_resources::SceneResources;
 use vertex::Vertex;
 use winit::{dpi::PhysicalSize, event_loop::EventLoop, keyboard::KeyCode};
 
 use crate::{
     components::{
-        camera, physics_body::integrate, rotation, Camera, PhysicsBody, Position, Rotation,
+        camera, physics_body::integrate, rotation, Camera, DirectionalLight, MeshRenderable,
+        PhysicsBody, PointLight, Position, Rotation, Scale, SpotLight,
     },
     input::{input::InputEnum, recorder, recorder_to_queue},
     material::Pipeline,
@@ -64,318 +70,75 @@ use crate::{
 
 fn main() -> anyhow::Result<()> {
     std::env::set_var("RUST_BACKTRACE", "full");
+    helpers::logger::init(conf::LOG_LEVEL);
 
     let entry = unsafe { ash::Entry::load()? };
 
+    // shaders must agree with MeshConstants, material/light params and Vertex
+    material::validate_shaders()?;
+    renderer::validate_debug_shaders()?;
+
+    if let Some(output) = conf::get_flag(conf::HEADLESS_FLAG) {
+        let reference = conf::get_flag(conf::REFERENCE_FLAG);
+        return run_headless(
+            entry,
+            Path::new(&output),
+            reference.as_deref().map(Path::new),
+        );
+    }
+
     //window
     let event_loop = winit::event_loop::EventLoop::new()?;
     let window = winit::window::WindowBuilder::new()
-        .with_title("Hello window!")
+        .with_title(conf::WINDOW_TITLE)
         .with_inner_size(winit::dpi::LogicalSize::new(
             conf::WINDOW_WIDTH,
             conf::WINDOW_HEIGHT,
         ))
         .build(&event_loop)?;
 
-    let mut render_engine = ft_vk::Engine::new(entry, &window);
+    let mut render_engine = ft_vk::Engine::new(
+        entry,
+        &window,
+        DebugConfig::default(),
+        ft_vk::RenderingPath::default(),
+        ft_vk::PresentModePreference::default(),
+    )?;
+    window.set_title(&window_title(&render_engine));
     let mut physics_engine = physics::Engine {
         frame_time_acc: Duration::ZERO,
         last_update: Instant::now(),
     };
 
     // assets
-    let obj = {
-        let obj_path = Path::new("resources/teapot2.obj");
-        ObjRaw::load_from_file(&obj_path).optimise_positions()
-    };
-    let obj_asset = ObjAssetBuilder::new(&obj).build();
-    let material_libs = obj_asset::load_materials(&obj);
-
-    // mesh
-    let mesh_asset = MeshAsset::from_obj(&obj_asset);
-    let mut mesh = {
-        let mut mesh = Mesh {
-            asset: &mesh_asset,
-            index_buffer: None,
-            vertex_buffer: None,
-        };
-        mesh.load(
-            &render_engine.device,
-            render_engine.allocator.as_mut().unwrap(),
-            render_engine.graphics_queue,
-            render_engine.frames[0].command_buffer,
-            render_engine.frames[0].command_pool,
-        );
-        mesh
-    };
-    let material_asset: MaterialAsset = material_libs
-        .values()
-        .flat_map(|mat_lib| mat_lib.materials.values())
-        .next()
-        .unwrap() //thrown when no material is defined in obj file, should be fallback to a default material (ambient 1.0)
-        .clone()
-        .into();
-    let material_set_layout = material::descriptor_set_layout(&render_engine.device);
-    // pipeline_layout
-    let push_constant_ranges = [
-        // scene constants (render_matrix / mesh_buffer_address)
-        vk::PushConstantRange::default()
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
-            .size(std::mem::size_of::<MeshConstants>() as u32),
-    ];
-    let pipeline_layout = PipelineLayout::<MeshConstants> {
-        layout: {
-            let set_layouts = [material_set_layout];
-            let info = vk::PipelineLayoutCreateInfo::default()
-                .push_constant_ranges(&push_constant_ranges)
-                .set_layouts(&set_layouts);
-            unsafe {
-                render_engine
-                    .device
-                    .create_pipeline_layout(&info, None)
-                    .unwrap()
-            }
-        },
-        _marker: std::marker::PhantomData,
-    };
-    // material
-    let material = Material::new(&mut render_engine, &material_asset, material_set_layout)
-        .load_pipeline(
-            &render_engine.device,
-            render_engine.render_pass,
-            render_engine.swapchain.extent,
-            &pipeline_layout,
-        );
-
-    let mut world = {
-        let mut world = World::new();
-
-        //Systems
-        {}
-        // Origin entity
-        {
-            world.spawn(Some(Entity::Origin));
-            world
-                .components
-                .add_component::<Position>(&Entity::Origin, Position(Vec3::ZERO));
-        }
-        // Resources
-        {
-            world.resources.add(InputRecorder::new());
-        }
-        // Camera entity
-        {
-            world.spawn(Some(ecs::Entity::Camera)).unwrap();
-            world.components.add_component(
-                &Entity::Camera,
-                components::Position(Vec3::ZERO.with_z(5.0f32)),
-            );
-            world
-                .components
-                .add_component(&Entity::Camera, components::Rotation(Quat::IDENTITY));
-            world.components.add_component(
-                &Entity::Camera,
-                components::Camera {
-                    aspect_ratio: render_engine.swapchain.aspect_ratio(),
-                    fov: 90.0f32,
-                    near: 0.1f32,
-                    far: 200.0f32,
-                    // mode: camera::Mode::Follow {
-                    //     target: Entity::Origin,
-                    //     yaw: 0.0f32,
-                    //     pitch: 0.0f32,
-                    // },
-                    mode: camera::Mode::Free,
-                },
-            );
-            world.components.add_component(
-                &Entity::Camera,
-                components::PhysicsBody {
-                    acceleration: Vec3::ZERO,
-                    velocity: Vec3::ZERO,
-                    angular_acceleration: Vec3::ZERO,
-                    angular_velocity: Vec3::ZERO,
-                    integrate: Some(Box::new(|entity, world| {
-                        impl<F> IntegrateFn for F
-                        where
-                            F: FnMut(Duration),
-                        {
-                            fn integrate(&mut self, dt: Duration) {
-                                self(dt)
-                            }
-                        };
+    let (mesh_asset, material_assets) = load_assets();
 
-                        Box::new(|dt: Duration| {
-                            let cam = unsafe {
-                                world
-                                    .as_unsafe_mut()
-                                    .components
-                                    .get_component_mut::<Camera>(entity)
-                                    .unwrap()
-                            };
-                            let position = unsafe {
-                                world
-                                    .as_unsafe_mut()
-                                    .components
-                                    .get_component_mut::<Position>(entity)
-                                    .unwrap()
-                            };
-                            let body = unsafe {
-                                world
-                                    .as_unsafe_mut()
-                                    .components
-                                    .get_component_mut::<PhysicsBody>(entity)
-                                    .unwrap()
-                            };
-
-                            match &mut cam.mode {
-                                camera::Mode::LookAt { target, yaw, pitch } => {
-                                    let target_position = unsafe {
-                                        world
-                                            .as_unsafe_mut()
-                                            .components
-                                            .get_component::<Position>(&target)
-                                            .unwrap()
-                                    };
-                                    *yaw = (*yaw + body.velocity.x * dt.as_secs_f32());
-                                    *pitch = (*pitch + body.velocity.y * dt.as_secs_f32()).clamp(
-                                        -std::f32::consts::FRAC_PI_2 + 0.001f32,
-                                        std::f32::consts::FRAC_PI_2 - 0.001f32,
-                                    );
-                                    let mut distance = Vec3::ZERO.with_z(
-                                        (position.0.distance(target_position.0)
-                                            + body.velocity.z * dt.as_secs_f32())
-                                        .max(0.01f32),
-                                    );
-                                    let yaw_rot = Quat::from_axis_angle(Vec3::Y, *yaw);
-                                    distance = yaw_rot * distance;
-                                    let right = distance.cross(Vec3::Y).normalize();
-                                    let pitch_rot = Quat::from_axis_angle(right, *pitch);
-                                    distance = pitch_rot * distance;
-
-                                    position.0 = target_position.0 + distance;
-                                }
-                                camera::Mode::Free => {
-                                    let rotation = unsafe {
-                                        world
-                                            .as_unsafe_mut()
-                                            .components
-                                            .get_component_mut::<Rotation>(entity)
-                                            .unwrap()
-                                    };
-
-                                    let yaw = body.angular_velocity.y * dt.as_secs_f32();
-                                    let pitch = body.angular_velocity.x * dt.as_secs_f32();
-
-                                    let yaw_rot = Quat::from_axis_angle(Vec3::Y, yaw); // world Y axis
-                                    let right = rotation.0 * Vec3::X; // camera's local right
-                                    let pitch_rot = Quat::from_axis_angle(right, pitch);
+    // meshes, materials, lights and debug buffers, rebuilt when the device is lost
+    let mut scene = SceneResources::new(
+        &mut render_engine,
+        &mesh_asset,
+        &material_assets,
+        ShadingModes::default(),
+    )?;
+    // mesh and material buffers, drawing doesn't wait for it
+    let mut assets_upload = Some(render_engine.submit_uploads()?);
 
-                                    rotation.0 = (yaw_rot * pitch_rot) * rotation.0;
-
-                                    // --- MOVEMENT (local to world space) ---
-                                    let world_velocity = rotation.0 * body.velocity;
-                                    position.0 += world_velocity * dt.as_secs_f32();
-                                }
-                                _ => {}
-                            };
-                        })
-                    })),
-                },
-            );
-
-            world.components.add_component(
-                &Entity::Camera,
-                components::Input(Rc::new(|entity, world| {
-                    let input_recorder = world
-                        .resources
-                        .get::<InputRecorder>()
-                        .ok()
-                        .flatten()
-                        .unwrap();
-                    let physics_body = world
-                        .components
-                        .get_component_mut::<PhysicsBody>(&entity)
-                        .unwrap();
-
-                    let mut velocity = Vec3::ZERO;
-                    let mut angular_velocity = Vec3::ZERO;
-
-                    match input_recorder.last(&KeyCode::KeyW) {
-                        Some(InputEnum::Down(_)) => {
-                            velocity += Vec3::NEG_Z;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::KeyS) {
-                        Some(InputEnum::Down(_)) => {
-                            velocity += Vec3::Z;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::KeyA) {
-                        Some(InputEnum::Down(_)) => {
-                            velocity += Vec3::NEG_X;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::KeyD) {
-                        Some(InputEnum::Down(_)) => {
-                            velocity += Vec3::X;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::Space) {
-                        Some(InputEnum::Down(_)) => {
-                            velocity += Vec3::Y;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::ControlLeft) {
-                        Some(InputEnum::Down(_)) => {
-                            velocity += Vec3::NEG_Y;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::ArrowUp) {
-                        Some(InputEnum::Down(_)) => {
-                            angular_velocity += Vec3::X;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::ArrowDown) {
-                        Some(InputEnum::Down(_)) => {
-                            angular_velocity += Vec3::NEG_X;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::ArrowLeft) {
-                        Some(InputEnum::Down(_)) => {
-                            angular_velocity += Vec3::Y;
-                        }
-                        _ => {}
-                    }
-                    match input_recorder.last(&KeyCode::ArrowRight) {
-                        Some(InputEnum::Down(_)) => {
-                            angular_velocity += Vec3::NEG_Y;
-                        }
-                        _ => {}
-                    }
-
-                    physics_body.velocity = velocity;
-                    physics_body.angular_velocity = angular_velocity;
-                })),
-            );
-        }
-        world
-    };
+    let mut world = create_world(
+        render_engine.swapchain.aspect_ratio(),
+        scene.materials.len(),
+    );
+    let mut engine_lost = false; // recovery failed, nothing left to draw with or destroy
 
     {
-        // closure data
-        let mut material = Some(material);
-
         // loop logic
+        let mut require_recreate = false; // after EngineError::DeviceLost
         let mut require_resize: Option<window::Size> = None;
+        let mut require_msaa: Option<u32> = None; // requested sample count
+        let mut require_present_mode: Option<ft_vk::PresentModePreference> = None;
+        let mut shading_modes = ShadingModes::default();
+        let mut require_shading_modes: Option<ShadingModes> = None;
+        let mut require_screenshot: Option<u32> = None; // scale of the window size
+        let mut cull_mode = ash::vk::CullModeFlags::BACK; // dynamic, toggled without a rebuild
         let mut last_update = std::time::Instant::now();
 
         event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
@@ -383,9 +146,14 @@ fn main() -> anyhow::Result<()> {
             .run(
                 |event: winit::event::Event<_>,
                  elwt: &winit::event_loop::EventLoopWindowTarget<_>| {
+                    if engine_lost {
+                        return;
+                    }
                     match event {
                         winit::event::Event::LoopExiting => {
-                            unsafe { render_engine.device.device_wait_idle() }.unwrap();
+                            if let Err(err) = unsafe { render_engine.device.device_wait_idle() } {
+                                log::error!("Device wait idle failed: {}", err);
+                            }
                         }
                         // DEVICE
                         winit::event::Event::DeviceEvent { event, .. } => match event {
@@ -395,87 +163,91 @@ fn main() -> anyhow::Result<()> {
                         winit::event::Event::WindowEvent { event, .. } => match event {
                             // WINDOW
                             winit::event::WindowEvent::RedrawRequested => {
-                                let render_matrix = {
-                                    let position = world
-                                        .components
-                                        .get_component::<Position>(&Entity::Camera)
-                                        .unwrap();
-                                    let rotation = world
-                                        .components
-                                        .get_component::<Rotation>(&Entity::Camera)
-                                        .unwrap_or(&Rotation(Quat::IDENTITY));
-                                    let camera = world
-                                        .components
-                                        .get_component::<Camera>(&Entity::Camera)
-                                        .unwrap();
-
-                                    let view = {
-                                        match &camera.mode {
-                                            camera::Mode::LookAt { target, .. } => {
-                                                let target_position = world
-                                                    .components
-                                                    .get_component::<Position>(target)
-                                                    .unwrap();
-
-                                                glam::Mat4::look_at_rh(
-                                                    position.0,
-                                                    target_position.0,
-                                                    glam::Vec3::Y,
-                                                )
-                                            }
-                                            camera::Mode::Free => {
-                                                (glam::Mat4::from_translation(position.0)
-                                                    * glam::Mat4::from_quat(rotation.0))
-                                                .inverse()
-                                            }
-                                            _ => Mat4::IDENTITY,
+                                if let Some(token) = assets_upload {
+                                    match render_engine.is_upload_complete(token) {
+                                        Result::Ok(true) => {
+                                            log::info!("Assets uploaded");
+                                            assets_upload = None;
                                         }
-                                    };
+                                        Result::Ok(false) => {}
+                                        Err(err) => require_recreate = on_engine_error(err, elwt),
+                                    }
+                                }
+                                let scene_camera = scene_camera(&world);
 
-                                    let projection = glam::Mat4::perspective_rh(
-                                        camera.fov.to_radians(),
-                                        camera.aspect_ratio,
-                                        camera.near,
-                                        camera.far,
-                                    );
+                                debug_draw_system(&mut world, &scene.meshes);
 
-                                    let fix_upside = glam::Mat4 {
-                                        y_axis: glam::Vec4::NEG_Y,
-                                        ..glam::Mat4::IDENTITY
-                                    };
+                                let lights = world
+                                    .resources
+                                    .get::<light::Lights>()
+                                    .ok()
+                                    .flatten()
+                                    .unwrap();
+                                let debug_draw =
+                                    world.resources.get::<DebugDraw>().ok().flatten().unwrap();
+                                let debug_pass = scene
+                                    .debug_renderer
+                                    .frame(debug_draw, scene_camera.view_projection);
 
-                                    projection * fix_upside * view
-                                };
+                                let mut render_queue =
+                                    RenderQueue::new(&scene.scene_lights, &lights.0, scene_camera);
+                                render_queue.cull_mode = cull_mode;
+                                render_system(
+                                    &world,
+                                    &mut render_queue,
+                                    &scene.meshes,
+                                    &scene.materials,
+                                    scene.default_material(),
+                                    &scene.pipeline_layout,
+                                );
 
-                                let renderer = MeshRenderer {
-                                    material: material.as_ref().unwrap(),
-                                    mesh: &mesh,
-                                    pipeline_layout: &pipeline_layout,
-                                    push_constants: {
-                                        Some(MeshConstants {
-                                            render_matrix,
-                                            vertex_buffer: mesh
-                                                .vertex_buffer
-                                                .as_ref()
-                                                .unwrap()
-                                                .device_address
-                                                .as_ref()
-                                                .unwrap(),
-                                        })
-                                    },
-                                };
+                                if let Some(scale) = require_screenshot.take() {
+                                    if scale == 1 {
+                                        render_engine.request_screenshot();
+                                    } else {
+                                        let extent = render_engine.swapchain.extent;
+                                        match unsafe {
+                                            render_engine.render_screenshot(
+                                                &[&render_queue, &debug_pass],
+                                                (extent.width * scale, extent.height * scale),
+                                            )
+                                        } {
+                                            Result::Ok(screenshot) => save_screenshot(&screenshot),
+                                            Err(err) => {
+                                                require_recreate = on_engine_error(err, elwt);
+                                            }
+                                        }
+                                    }
+                                }
 
-                                match unsafe { render_engine.draw_frame(&renderer) } {
-                                    Err(vk::Result::ERROR_OUT_OF_DATE_KHR)
-                                    | Err(vk::Result::SUBOPTIMAL_KHR) => {
+                                let frame_result = unsafe {
+                                    render_engine.draw_frame(&[&render_queue, &debug_pass])
+                                };
+                                if let Some(debug_draw) =
+                                    world.resources.get_mut::<DebugDraw>().ok().flatten()
+                                {
+                                    debug_draw.log_text_anchors();
+                                    debug_draw.clear();
+                                }
+                                if let Some(screenshot) = render_engine.take_screenshot() {
+                                    save_screenshot(&screenshot);
+                                }
+                                match frame_result {
+                                    Result::Ok(
+                                        ft_vk::FrameStatus::OutOfDate
+                                        | ft_vk::FrameStatus::Suboptimal,
+                                    ) => {
                                         let window_size = window.inner_size();
                                         require_resize = Some(window::Size {
                                             width: window_size.width,
                                             height: window_size.height,
                                         });
                                     }
-                                    Err(e) => panic!("{:?}", e),
-                                    _ => {}
+                                    // redrawn on the next input or resize event
+                                    Result::Ok(
+                                        ft_vk::FrameStatus::Drawn | ft_vk::FrameStatus::Minimized,
+                                    ) => {}
+                                    Err(err) => require_recreate = on_engine_error(err, elwt),
                                 }
                             }
                             winit::event::WindowEvent::Resized(new_size) => {
@@ -492,22 +264,108 @@ fn main() -> anyhow::Result<()> {
                                     winit::event::KeyEvent {
                                         physical_key: winit::keyboard::PhysicalKey::Code(code),
                                         state,
+                                        repeat,
                                         ..
                                     },
                                 ..
                             } => {
+                                // M: cycle MSAA 1 -> 2 -> 4 -> 8 -> 1
+                                if code == KeyCode::KeyM
+                                    && state == winit::event::ElementState::Pressed
+                                    && !repeat
+                                {
+                                    let samples = render_engine.msaa_samples.as_raw();
+                                    require_msaa = Some(if samples >= 8 { 1 } else { samples * 2 });
+                                }
+                                // V: cycle present mode vsync -> immediate -> mailbox -> fifo relaxed
+                                if code == KeyCode::KeyV
+                                    && state == winit::event::ElementState::Pressed
+                                    && !repeat
+                                {
+                                    require_present_mode = Some(render_engine.present_mode.next());
+                                }
+                                // F: cycle shaded -> wireframe -> points -> shaded + wireframe
+                                if code == KeyCode::KeyF
+                                    && state == winit::event::ElementState::Pressed
+                                    && !repeat
+                                {
+                                    require_shading_modes = Some(ShadingModes {
+                                        display: shading_modes.display.next(),
+                                        ..shading_modes
+                                    });
+                                }
+                                // C: toggle back face culling
+                                if code == KeyCode::KeyC
+                                    && state == winit::event::ElementState::Pressed
+                                    && !repeat
+                                {
+                                    cull_mode = if cull_mode == ash::vk::CullModeFlags::BACK {
+                                        ash::vk::CullModeFlags::NONE
+                                    } else {
+                                        ash::vk::CullModeFlags::BACK
+                                    };
+                                    log::info!("Cull mode: {:?}", cull_mode);
+                                }
+                                // P: screenshot of the window, O: at conf::SCREENSHOT_SCALE times its size
+                                if (code == KeyCode::KeyP || code == KeyCode::KeyO)
+                                    && state == winit::event::ElementState::Pressed
+                                    && !repeat
+                                {
+                                    require_screenshot = Some(if code == KeyCode::KeyP {
+                                        1
+                                    } else {
+                                        conf::SCREENSHOT_SCALE
+                                    });
+                                }
+                                // F1..F5: debug grid, origin axes, AABBs, vertex normals, lights
+                                if state == winit::event::ElementState::Pressed && !repeat {
+                                    if let Some(debug_draw) =
+                                        world.resources.get_mut::<DebugDraw>().ok().flatten()
+                                    {
+                                        let toggles = &mut debug_draw.toggles;
+                                        match code {
+                                            KeyCode::F1 => toggles.grid = !toggles.grid,
+                                            KeyCode::F2 => {
+                                                toggles.origin_axes = !toggles.origin_axes
+                                            }
+                                            KeyCode::F3 => toggles.aabbs = !toggles.aabbs,
+                                            KeyCode::F4 => toggles.normals = !toggles.normals,
+                                            KeyCode::F5 => toggles.lights = !toggles.lights,
+                                            _ => {}
+                                        }
+                                    }
+                                }
+                                // T: log the rolling frame timings and GPU scopes
+                                if code == KeyCode::KeyT
+                                    && state == winit::event::ElementState::Pressed
+                                    && !repeat
+                                {
+                                    log::info!("Frame stats\n{}", render_engine.frame_stats());
+                                }
+                                // G: cycle debug shading none -> normals -> uv -> depth -> ids
+                                if code == KeyCode::KeyG
+                                    && state == winit::event::ElementState::Pressed
+                                    && !repeat
+                                {
+                                    require_shading_modes = Some(ShadingModes {
+                                        debug: shading_modes.debug.next(),
+                                        ..shading_modes
+                                    });
+                                }
+
                                 let recorder = world
                                     .resources
                                     .get_mut::<InputRecorder>()
                                     .ok()
                                     .flatten()
                                     .unwrap();
+                                // key repeats are already down
                                 match state {
                                     winit::event::ElementState::Pressed => {
-                                        recorder.press(code, Instant::now());
+                                        let _ = recorder.press(code, Instant::now());
                                     }
                                     winit::event::ElementState::Released => {
-                                        recorder.release(code, Instant::now());
+                                        let _ = recorder.release(code, Instant::now());
                                     }
                                 };
                                 window.request_redraw();
@@ -518,77 +376,552 @@ fn main() -> anyhow::Result<()> {
                     };
 
                     if let Some(new_size) = require_resize {
-                        on_resize(
-                            &mut material,
-                            &pipeline_layout,
-                            &mut world,
-                            &mut render_engine,
-                            new_size,
-                        );
+                        if let Err(err) = on_resize(&mut world, &mut render_engine, new_size) {
+                            require_recreate = on_engine_error(err, elwt);
+                        }
                         require_resize = None;
                     }
+                    if let Some(samples) = require_msaa {
+                        if let Err(err) =
+                            on_msaa_change(&mut scene, &mut render_engine, samples, shading_modes)
+                        {
+                            require_recreate = on_engine_error(err, elwt);
+                        }
+                        require_msaa = None;
+                    }
+                    if let Some(preference) = require_present_mode {
+                        if let Err(err) = unsafe { render_engine.set_present_mode(preference) } {
+                            require_recreate = on_engine_error(err, elwt);
+                        }
+                        window.set_title(&window_title(&render_engine));
+                        require_present_mode = None;
+                    }
+                    if let Some(new_shading_modes) = require_shading_modes {
+                        match scene.reload_pipelines(&mut render_engine, new_shading_modes) {
+                            Result::Ok(()) => {
+                                log::info!("Shading: {:?}", new_shading_modes);
+                                shading_modes = new_shading_modes;
+                            }
+                            Err(err) => require_recreate = on_engine_error(err, elwt),
+                        }
+                        require_shading_modes = None;
+                    }
+                    if require_recreate {
+                        let token = match unsafe {
+                            recover_device_lost(
+                                &mut render_engine,
+                                &mut scene,
+                                &window,
+                                &mesh_asset,
+                                &material_assets,
+                                shading_modes,
+                            )
+                        } {
+                            Result::Ok(token) => token,
+                            Err(err) => {
+                                log::error!("Device lost, recovery failed: {}", err);
+                                engine_lost = true;
+                                elwt.exit();
+                                return;
+                            }
+                        };
+                        assets_upload = Some(token);
+                        window.set_title(&window_title(&render_engine));
+                        world
+                            .components
+                            .get_component_mut::<components::Camera>(&Entity::Camera)
+                            .unwrap()
+                            .aspect_ratio = render_engine.swapchain.aspect_ratio();
+                        window.request_redraw();
+                        require_recreate = false;
+                    }
 
                     // Loop logic
                     process_input(&mut world);
                     physics_system(&mut world, &mut physics_engine);
+                    lights_system(&mut world);
                 },
             )
             .unwrap();
+    }
 
-        material
-            .unwrap()
-            .unload_pipeline(&render_engine.device)
-            .destroy(render_engine.allocator.as_ref().unwrap());
+    // Clean, a failed recovery already destroyed the scene and the engine
+    if !engine_lost {
+        scene.destroy(&mut render_engine);
+        unsafe { render_engine.destroy() };
     }
 
-    // Clean
+    Ok(())
+}
+
+// Teapot mesh and its materials, empty when no material is defined in the obj file
+fn load_assets() -> (MeshAsset<Vertex>, Vec<MaterialAsset>) {
+    let obj = {
+        let obj_path = Path::new("resources/teapot2.obj");
+        ObjRaw::load_from_file(&obj_path).optimise_positions()
+    };
+    let obj_asset = ObjAssetBuilder::new(&obj).build();
+    let material_libs = obj_asset::load_materials(&obj);
+
+    let mesh_asset = MeshAsset::from_obj(&obj_asset);
+    let material_assets = material_libs
+        .values()
+        .flat_map(|mat_lib| mat_lib.materials.values())
+        .map(|material| material.clone().into())
+        .collect(); // see SceneResources::default_material
+
+    (mesh_asset, material_assets)
+}
+
+/**
+ * Render the startup scene once without a window (conf::WINDOW_WIDTH x conf::WINDOW_HEIGHT)
+ * and write it to output (.ppm or .png). When a reference PPM is given, fails if any channel
+ * differs from it by more than conf::HEADLESS_TOLERANCE.
+ */
+fn run_headless(entry: ash::Entry, output: &Path, reference: Option<&Path>) -> anyhow::Result<()> {
+    let mut render_engine = ft_vk::Engine::new_headless(
+        entry,
+        (conf::WINDOW_WIDTH, conf::WINDOW_HEIGHT),
+        DebugConfig::default(),
+        ft_vk::RenderingPath::default(),
+    )?;
+
+    let (mesh_asset, material_assets) = load_assets();
+    let mut scene = SceneResources::new(
+        &mut render_engine,
+        &mesh_asset,
+        &material_assets,
+        ShadingModes::default(),
+    )?;
+    let upload = render_engine.submit_uploads()?;
+    render_engine.wait_upload(upload)?;
+
+    let mut world = create_world(
+        render_engine.swapchain.aspect_ratio(),
+        scene.materials.len(),
+    );
+    lights_system(&mut world);
+
+    let screenshot = {
+        let lights = world
+            .resources
+            .get::<light::Lights>()
+            .ok()
+            .flatten()
+            .unwrap();
+        let mut render_queue =
+            RenderQueue::new(&scene.scene_lights, &lights.0, scene_camera(&world));
+        render_system(
+            &world,
+            &mut render_queue,
+            &scene.meshes,
+            &scene.materials,
+            scene.default_material(),
+            &scene.pipeline_layout,
+        );
+
+        unsafe { render_engine.draw_frame(&[&render_queue])? };
+        let extent = render_engine.swapchain.extent;
+        ft_vk::Screenshot::from_pixels(
+            extent.width,
+            extent.height,
+            &unsafe { render_engine.read_back()? },
+            conf::HEADLESS_FORMAT,
+        )?
+    };
+
+    scene.destroy(&mut render_engine);
+    unsafe { render_engine.destroy() };
+
+    let format = helpers::image::ImageFormat::from_path(output)
+        .ok_or_else(|| anyhow::anyhow!("{:?}: expected a .ppm or .png output", output))?;
+    helpers::image::write_rgb(
+        output,
+        format,
+        screenshot.width,
+        screenshot.height,
+        &screenshot.rgb,
+    )?;
+    log::info!("Headless frame written to {:?}", output);
+
+    if let Some(reference) = reference {
+        let (width, height, rgb) = helpers::image::read_ppm(reference)?;
+        if (width, height) != (screenshot.width, screenshot.height) {
+            anyhow::bail!(
+                "{:?} is {}x{}, rendered {}x{}",
+                reference,
+                width,
+                height,
+                screenshot.width,
+                screenshot.height
+            );
+        }
+        let difference = helpers::image::max_difference(&screenshot.rgb, &rgb).unwrap_or(u8::MAX);
+        if difference > conf::HEADLESS_TOLERANCE {
+            anyhow::bail!(
+                "{:?} differs from {:?} by up to {} per channel",
+                output,
+                reference,
+                difference
+            );
+        }
+        log::info!("Matches {:?} (max difference {})", reference, difference);
+    }
+
+    Ok(())
+}
+
+fn save_screenshot(screenshot: &ft_vk::Screenshot) {
+    match screenshot.save(
+        std::path::Path::new(conf::SCREENSHOT_DIR),
+        conf::SCREENSHOT_FORMAT,
+    ) {
+        Result::Ok(path) => log::info!(
+            "Screenshot {}x{} saved to {:?}",
+            screenshot.width,
+            screenshot.height,
+            path
+        ),
+        Err(err) => log::error!("Screenshot not saved: {}", err),
+    }
+}
+
+// Startup scene: origin, teapot, sun and a free camera driven by the keyboard
+fn create_world(aspect_ratio: f32, material_count: usize) -> World {
+    let mut world = World::new();
+
+    //Systems
+    {}
+    // Origin entity
     {
-        unsafe {
-            render_engine
-                .device
-                .destroy_descriptor_set_layout(material_set_layout, None)
-        };
+        world.spawn(Some(Entity::Origin));
+        world
+            .components
+            .add_component::<Position>(&Entity::Origin, Position(Vec3::ZERO));
+    }
+    // Resources
+    {
+        world.resources.add(InputRecorder::new());
+        world.resources.add(light::Lights::default());
+        world.resources.add(DebugDraw::default());
+    }
+    // Teapot entity
+    {
+        let teapot = Entity::Custom("Teapot".to_string());
+        world.spawn(Some(teapot.clone())).unwrap();
+        world
+            .components
+            .add_component(&teapot, components::Position(Vec3::ZERO));
+        world
+            .components
+            .add_component(&teapot, components::Rotation(Quat::IDENTITY));
+        world
+            .components
+            .add_component(&teapot, components::Scale(Vec3::ONE));
+        world.components.add_component(
+            &teapot,
+            components::MeshRenderable {
+                mesh: 0,
+                materials: 0..material_count,
+            },
+        );
+    }
+    // Sun entity
+    {
+        let sun = Entity::Custom("Sun".to_string());
+        world.spawn(Some(sun.clone())).unwrap();
+        world.components.add_component(
+            &sun,
+            components::Rotation(Quat::from_rotation_arc(
+                Vec3::NEG_Z,
+                Vec3::new(0.0f32, -1.0f32, -1.0f32).normalize(),
+            )),
+        );
+        world.components.add_component(
+            &sun,
+            components::DirectionalLight {
+                color: Vec3::ONE,
+                intensity: 1.0f32,
+            },
+        );
+    }
+    // Camera entity
+    {
+        world.spawn(Some(ecs::Entity::Camera)).unwrap();
+        world.components.add_component(
+            &Entity::Camera,
+            components::Position(Vec3::ZERO.with_z(5.0f32)),
+        );
+        world
+            .components
+            .add_component(&Entity::Camera, components::Rotation(Quat::IDENTITY));
+        world.components.add_component(
+            &Entity::Camera,
+            components::Camera {
+                aspect_ratio,
+                fov: 90.0f32,
+                near: 0.1f32,
+                far: 200.0f32,
+                // mode: camera::Mode::Follow {
+                //     target: Entity::Origin,
+                //     yaw: 0.0f32,
+                //     pitch: 0.0f32,
+                // },
+                mode: camera::Mode::Free,
+            },
+        );
+        world.components.add_component(
+            &Entity::Camera,
+            components::PhysicsBody {
+                acceleration: Vec3::ZERO,
+                velocity: Vec3::ZERO,
+                angular_acceleration: Vec3::ZERO,
+                angular_velocity: Vec3::ZERO,
+                integrate: Some(Box::new(|entity, world| {
+                    impl<F> IntegrateFn for F
+                    where
+                        F: FnMut(Duration),
+                    {
+                        fn integrate(&mut self, dt: Duration) {
+                            self(dt)
+                        }
+                    };
+
+                    Box::new(|dt: Duration| {
+                        let cam = unsafe {
+                            world
+                                .as_unsafe_mut()
+                                .components
+                                .get_component_mut::<Camera>(entity)
+                                .unwrap()
+                        };
+                        let position = unsafe {
+                            world
+                                .as_unsafe_mut()
+                                .components
+                                .get_component_mut::<Position>(entity)
+                                .unwrap()
+                        };
+                        let body = unsafe {
+                            world
+                                .as_unsafe_mut()
+                                .components
+                                .get_component_mut::<PhysicsBody>(entity)
+                                .unwrap()
+                        };
+
+                        match &mut cam.mode {
+                            camera::Mode::LookAt { target, yaw, pitch } => {
+                                let target_position = unsafe {
+                                    world
+                                        .as_unsafe_mut()
+                                        .components
+                                        .get_component::<Position>(&target)
+                                        .unwrap()
+                                };
+                                *yaw = (*yaw + body.velocity.x * dt.as_secs_f32());
+                                *pitch = (*pitch + body.velocity.y * dt.as_secs_f32()).clamp(
+                                    -std::f32::consts::FRAC_PI_2 + 0.001f32,
+                                    std::f32::consts::FRAC_PI_2 - 0.001f32,
+                                );
+                                let mut distance = Vec3::ZERO.with_z(
+                                    (position.0.distance(target_position.0)
+                                        + body.velocity.z * dt.as_secs_f32())
+                                    .max(0.01f32),
+                                );
+                                let yaw_rot = Quat::from_axis_angle(Vec3::Y, *yaw);
+                                distance = yaw_rot * distance;
+                                let right = distance.cross(Vec3::Y).normalize();
+                                let pitch_rot = Quat::from_axis_angle(right, *pitch);
+                                distance = pitch_rot * distance;
+
+                                position.0 = target_position.0 + distance;
+                            }
+                            camera::Mode::Free => {
+                                let rotation = unsafe {
+                                    world
+                                        .as_unsafe_mut()
+                                        .components
+                                        .get_component_mut::<Rotation>(entity)
+                                        .unwrap()
+                                };
+
+                                let yaw = body.angular_velocity.y * dt.as_secs_f32();
+                                let pitch = body.angular_velocity.x * dt.as_secs_f32();
+
+                                let yaw_rot = Quat::from_axis_angle(Vec3::Y, yaw); // world Y axis
+                                let right = rotation.0 * Vec3::X; // camera's local right
+                                let pitch_rot = Quat::from_axis_angle(right, pitch);
+
+                                rotation.0 = (yaw_rot * pitch_rot) * rotation.0;
+
+                                // --- MOVEMENT (local to world space) ---
+                                let world_velocity = rotation.0 * body.velocity;
+                                position.0 += world_velocity * dt.as_secs_f32();
+                            }
+                            _ => {}
+                        };
+                    })
+                })),
+            },
+        );
+
+        world.components.add_component(
+            &Entity::Camera,
+            components::Input(Rc::new(|entity, world| {
+                let input_recorder = world
+                    .resources
+                    .get::<InputRecorder>()
+                    .ok()
+                    .flatten()
+                    .unwrap();
+                let physics_body = world
+                    .components
+                    .get_component_mut::<PhysicsBody>(&entity)
+                    .unwrap();
+
+                let mut velocity = Vec3::ZERO;
+                let mut angular_velocity = Vec3::ZERO;
+
+                match input_recorder.last(&KeyCode::KeyW) {
+                    Some(InputEnum::Down(_)) => {
+                        velocity += Vec3::NEG_Z;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::KeyS) {
+                    Some(InputEnum::Down(_)) => {
+                        velocity += Vec3::Z;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::KeyA) {
+                    Some(InputEnum::Down(_)) => {
+                        velocity += Vec3::NEG_X;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::KeyD) {
+                    Some(InputEnum::Down(_)) => {
+                        velocity += Vec3::X;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::Space) {
+                    Some(InputEnum::Down(_)) => {
+                        velocity += Vec3::Y;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::ControlLeft) {
+                    Some(InputEnum::Down(_)) => {
+                        velocity += Vec3::NEG_Y;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::ArrowUp) {
+                    Some(InputEnum::Down(_)) => {
+                        angular_velocity += Vec3::X;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::ArrowDown) {
+                    Some(InputEnum::Down(_)) => {
+                        angular_velocity += Vec3::NEG_X;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::ArrowLeft) {
+                    Some(InputEnum::Down(_)) => {
+                        angular_velocity += Vec3::Y;
+                    }
+                    _ => {}
+                }
+                match input_recorder.last(&KeyCode::ArrowRight) {
+                    Some(InputEnum::Down(_)) => {
+                        angular_velocity += Vec3::NEG_Y;
+                    }
+                    _ => {}
+                }
+
+                physics_body.velocity = velocity;
+                physics_body.angular_velocity = angular_velocity;
+            })),
+        );
+    }
+    world
+}
 
-        if let Some(allocator) = &render_engine.allocator {
-            mesh.unload(&allocator);
+// View projection of the camera entity
+fn scene_camera(world: &World) -> light::SceneCamera {
+    let position = world
+        .components
+        .get_component::<Position>(&Entity::Camera)
+        .unwrap();
+    let rotation = world
+        .components
+        .get_component::<Rotation>(&Entity::Camera)
+        .unwrap_or(&Rotation(Quat::IDENTITY));
+    let camera = world
+        .components
+        .get_component::<Camera>(&Entity::Camera)
+        .unwrap();
+
+    let view = {
+        match &camera.mode {
+            camera::Mode::LookAt { target, .. } => {
+                let target_position = world.components.get_component::<Position>(target).unwrap();
+
+                glam::Mat4::look_at_rh(position.0, target_position.0, glam::Vec3::Y)
+            }
+            camera::Mode::Free => (glam::Mat4::from_translation(position.0)
+                * glam::Mat4::from_quat(rotation.0))
+            .inverse(),
+            _ => Mat4::IDENTITY,
         }
-        unsafe {
-            render_engine
-                .device
-                .destroy_pipeline_layout(pipeline_layout.as_vk(), None)
-        };
+    };
 
-        unsafe { render_engine.destroy() };
+    let projection = glam::Mat4::perspective_rh(
+        camera.fov.to_radians(),
+        camera.aspect_ratio,
+        camera.near,
+        camera.far,
+    );
+
+    let fix_upside = glam::Mat4 {
+        y_axis: glam::Vec4::NEG_Y,
+        ..glam::Mat4::IDENTITY
+    };
+
+    light::SceneCamera {
+        view_projection: projection * fix_upside * view,
+        position: position.0,
+        near: camera.near,
+        far: camera.far,
     }
+}
 
-    Ok(())
+// Application name and active present mode
+fn window_title(render_engine: &ft_vk::Engine) -> String {
+    format!(
+        "{} - {}",
+        conf::WINDOW_TITLE,
+        ft_vk::present_mode_name(render_engine.swapchain.present_mode)
+    )
 }
 
-// Handle window resize events and update the engine, material, and camera accordingly.
-fn on_resize<TPipelineLayout>(
-    material: &mut Option<Material<Pipeline>>,
-    pipeline_layout: &PipelineLayout<TPipelineLayout>,
+// Handle window resize events and update the engine and camera accordingly.
+// Material pipelines use a dynamic viewport and survive the resize.
+fn on_resize(
     world: &mut World,
     render_engine: &mut ft_vk::Engine,
     new_size: window::Size,
-) {
-    // Engine
-    unsafe { render_engine.device.device_wait_idle() };
-    unsafe { render_engine.handle_resize((new_size.width, new_size.height)) };
-
-    // Material
-    *material = Some(
-        material
-            .take()
-            .unwrap()
-            .unload_pipeline(&render_engine.device)
-            .load_pipeline(
-                &render_engine.device,
-                render_engine.render_pass,
-                render_engine.swapchain.extent,
-                pipeline_layout,
-            ),
-    );
+) -> Result<(), ft_vk::EngineError> {
+    // Engine, the previous swapchain is destroyed once its frames are complete
+    let status = unsafe { render_engine.handle_resize((new_size.width, new_size.height))? };
+    if status == ft_vk::ResizeStatus::Minimized {
+        return Result::Ok(());
+    }
 
     // Camera
     world
@@ -596,6 +929,65 @@ fn on_resize<TPipelineLayout>(
         .get_component_mut::<components::Camera>(&Entity::Camera)
         .unwrap()
         .aspect_ratio = render_engine.swapchain.aspect_ratio();
+
+    Result::Ok(())
+}
+
+// Rebuild the engine attachments and the material pipelines for a new sample count.
+fn on_msaa_change(
+    scene: &mut SceneResources,
+    render_engine: &mut ft_vk::Engine,
+    samples: u32,
+    shading_modes: ShadingModes,
+) -> Result<(), ft_vk::EngineError> {
+    let previous = render_engine.msaa_samples;
+    let mut applied = unsafe { render_engine.set_msaa_samples(samples)? };
+    if applied == previous && samples > previous.as_raw() {
+        // already at the device maximum, wrap around
+        applied = unsafe { render_engine.set_msaa_samples(1)? };
+    }
+    if applied == previous {
+        return Result::Ok(());
+    }
+
+    scene.reload_pipelines(render_engine, shading_modes)?;
+    scene.debug_renderer.reload_pipeline(render_engine)?;
+
+    Result::Ok(())
+}
+
+// DeviceLost requests a recovery (see recover_device_lost), other engine errors exit the event loop.
+fn on_engine_error<T>(
+    err: ft_vk::EngineError,
+    elwt: &winit::event_loop::EventLoopWindowTarget<T>,
+) -> bool {
+    match err {
+        ft_vk::EngineError::DeviceLost => {
+            log::error!("Device lost, recreating the engine");
+            true
+        }
+        err => {
+            log::error!("{}, exiting", err);
+            elwt.exit();
+            false
+        }
+    }
+}
+
+// Destroy the scene resources and the engine then build both again, the world is kept.
+unsafe fn recover_device_lost<'a>(
+    render_engine: &mut ft_vk::Engine,
+    scene: &mut SceneResources<'a>,
+    window: &winit::window::Window,
+    mesh_asset: &'a MeshAsset<Vertex>,
+    material_assets: &[MaterialAsset],
+    shading_modes: ShadingModes,
+) -> Result<ft_vk::uploader::UploadToken, ft_vk::EngineError> {
+    scene.destroy(render_engine);
+    render_engine.recreate(window, DebugConfig::default())?;
+    *scene = SceneResources::new(render_engine, mesh_asset, material_assets, shading_modes)?;
+
+    render_engine.submit_uploads()
 }
 
 // Process input events and apply them to the world.
@@ -627,3 +1019,189 @@ fn physics_system(world: &mut World, engine: &mut physics::Engine) {
 
     engine.tick(bodies);
 }
+
+// Gather light components into the Lights resource used by the renderer.
+fn lights_system(world: &mut World) {
+    let lights = light::gather(&world.components);
+
+    if let Some(resource) = world.resources.get_mut::<light::Lights>().ok().flatten() {
+        resource.0 = lights;
+    }
+}
+
+// Position/Rotation/Scale of the entity, missing components are the identity
+fn model_matrix(components: &ComponentsStorage, entity: &Entity) -> Mat4 {
+    let position = components
+        .get_component::<Position>(entity)
+        .map(|position| position.0)
+        .unwrap_or(Vec3::ZERO);
+    let rotation = components
+        .get_component::<Rotation>(entity)
+        .map(|rotation| rotation.0)
+        .unwrap_or(Quat::IDENTITY);
+    let scale = components
+        .get_component::<Scale>(entity)
+        .map(|scale| scale.0)
+        .unwrap_or(Vec3::ONE);
+    Mat4::from_scale_rotation_translation(scale, rotation, position)
+}
+
+// Built-in DebugDraw shapes selected by its toggles.
+fn debug_draw_system(world: &mut World, meshes: &[Mesh<Vertex>]) {
+    const GRID_COLOR: glam::Vec4 = glam::Vec4::new(0.4, 0.4, 0.4, 1.0);
+    const AABB_COLOR: glam::Vec4 = glam::Vec4::new(1.0, 1.0, 0.0, 1.0);
+    const NORMAL_COLOR: glam::Vec4 = glam::Vec4::new(0.0, 1.0, 1.0, 1.0);
+    const NORMAL_LENGTH: f32 = 0.05f32;
+    const LIGHT_ARROW_LENGTH: f32 = 3.0f32;
+
+    let Some(toggles) = world
+        .resources
+        .get::<DebugDraw>()
+        .ok()
+        .flatten()
+        .map(|debug_draw| debug_draw.toggles)
+    else {
+        return;
+    };
+
+    let mut debug_draw = DebugDraw::default();
+    if toggles.grid {
+        debug_draw.grid(20, 1.0f32, GRID_COLOR);
+    }
+    if toggles.origin_axes {
+        debug_draw.axes(model_matrix(&world.components, &Entity::Origin), 1.0f32);
+    }
+    if toggles.aabbs || toggles.normals {
+        if let Some(storage) = world.components.get_component_storage::<MeshRenderable>() {
+            for (entity, renderable) in storage.iter() {
+                let model = model_matrix(&world.components, entity);
+                let asset = meshes[renderable.mesh].asset;
+
+                if toggles.aabbs && !asset.submeshes.is_empty() {
+                    // world space box around the transformed local bounds
+                    let (local_min, local_max) = asset.submeshes.iter().fold(
+                        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                        |(min, max), submesh| {
+                            (min.min(submesh.bounds_min), max.max(submesh.bounds_max))
+                        },
+                    );
+                    let (min, max) = (0..8)
+                        .map(|index| {
+                            model.transform_point3(Vec3::select(
+                                glam::BVec3::new(index & 1 != 0, index & 2 != 0, index & 4 != 0),
+                                local_max,
+                                local_min,
+                            ))
+                        })
+                        .fold(
+                            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                            |(min, max), corner| (min.min(corner), max.max(corner)),
+                        );
+                    debug_draw.aabb(min, max, AABB_COLOR);
+                }
+                if toggles.normals {
+                    let normal_matrix = glam::Mat3::from_mat4(model).inverse().transpose();
+                    for vertex in &asset.vertices {
+                        let position = model.transform_point3(vertex.position);
+                        let normal = (normal_matrix * vertex.normal).normalize_or_zero();
+                        debug_draw.line(position, position + normal * NORMAL_LENGTH, NORMAL_COLOR);
+                    }
+                }
+            }
+        }
+    }
+
+    if toggles.lights {
+        let components = &world.components;
+        let position =
+            |entity: &Entity| model_matrix(components, entity).transform_point3(Vec3::ZERO);
+        let direction = |entity: &Entity| {
+            components
+                .get_component::<Rotation>(entity)
+                .map(|rotation| rotation.0)
+                .unwrap_or(Quat::IDENTITY)
+                * Vec3::NEG_Z
+        };
+
+        // directional lights have no position, their arrow points to the origin
+        if let Some(storage) = components.get_component_storage::<DirectionalLight>() {
+            for (entity, light) in storage.iter() {
+                let from = -direction(entity) * LIGHT_ARROW_LENGTH;
+                debug_draw.arrow(from, Vec3::ZERO, light.color.extend(1.0f32));
+                debug_draw.text(
+                    from,
+                    format!("directional light {:?}", entity),
+                    light.color.extend(1.0f32),
+                );
+            }
+        }
+        if let Some(storage) = components.get_component_storage::<PointLight>() {
+            for (entity, light) in storage.iter() {
+                let position = position(entity);
+                debug_draw.sphere(position, light.range, light.color.extend(1.0f32));
+                debug_draw.text(
+                    position,
+                    format!("point light {:?}", entity),
+                    light.color.extend(1.0f32),
+                );
+            }
+        }
+        if let Some(storage) = components.get_component_storage::<SpotLight>() {
+            for (entity, light) in storage.iter() {
+                let position = position(entity);
+                let to = position + direction(entity) * light.range;
+                debug_draw.arrow(position, to, light.color.extend(1.0f32));
+                debug_draw.text(
+                    position,
+                    format!("spot light {:?}", entity),
+                    light.color.extend(1.0f32),
+                );
+            }
+        }
+    }
+
+    if let Some(resource) = world.resources.get_mut::<DebugDraw>().ok().flatten() {
+        resource.vertices.append(&mut debug_draw.vertices);
+        resource.text_anchors.append(&mut debug_draw.text_anchors);
+    }
+}
+
+// Submit every MeshRenderable entity to the render queue, model matrix from Position/Rotation/Scale.
+fn render_system<'a>(
+    world: &World,
+    render_queue: &mut RenderQueue<'a>,
+    meshes: &'a [Mesh<'a, Vertex>],
+    materials: &'a [Material<Pipeline>],
+    default_material: &'a Material<Pipeline>,
+    pipeline_layout: &PipelineLayout<MeshConstants<'a>>,
+) {
+    let Some(storage) = world.components.get_component_storage::<MeshRenderable>() else {
+        return;
+    };
+
+    for (entity, renderable) in storage.iter() {
+        let model = model_matrix(&world.components, entity);
+
+        let mesh = &meshes[renderable.mesh];
+        render_queue.push_mesh(
+            mesh,
+            materials
+                .get(renderable.materials.clone())
+                .unwrap_or_default(),
+            default_material,
+            model,
+            pipeline_layout,
+            &MeshConstants {
+                model,
+                normal_matrix: mesh_constants::normal_matrix(model),
+                vertex_buffer: mesh
+                    .vertex_buffer
+                    .as_ref()
+                    .unwrap()
+                    .device_address
+                    .as_ref()
+                    .unwrap(),
+            },
+        );
+    }
+}
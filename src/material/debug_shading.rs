@@ -0,0 +1,50 @@
+// DEBUG_SHADING specialization constant values, must match mesh.frag
+const NONE: u32 = 0;
+const NORMALS: u32 = 1;
+const UV_CHECKER: u32 = 2;
+const LINEAR_DEPTH: u32 = 3;
+const TRIANGLE_ID: u32 = 4;
+const SUBMESH_ID: u32 = 5;
+const SMOOTHING_GROUP_ID: u32 = 6;
+
+/**
+ * Replaces the lit color of mesh.frag by a debug visualization.
+ * Ids are hashed into colors, SubmeshId is also the material id (one submesh per material).
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugShading {
+    #[default]
+    None,
+    Normals,     // world normals as colors
+    UvChecker,   // checker pattern from the texture coordinates
+    LinearDepth, // distance to the camera between near and far
+    TriangleId,
+    SubmeshId,
+    SmoothingGroupId, // black when smoothing is off
+}
+
+impl DebugShading {
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Normals,
+            Self::Normals => Self::UvChecker,
+            Self::UvChecker => Self::LinearDepth,
+            Self::LinearDepth => Self::TriangleId,
+            Self::TriangleId => Self::SubmeshId,
+            Self::SubmeshId => Self::SmoothingGroupId,
+            Self::SmoothingGroupId => Self::None,
+        }
+    }
+
+    pub fn shader_mode(self) -> u32 {
+        match self {
+            Self::None => NONE,
+            Self::Normals => NORMALS,
+            Self::UvChecker => UV_CHECKER,
+            Self::LinearDepth => LINEAR_DEPTH,
+            Self::TriangleId => TRIANGLE_ID,
+            Self::SubmeshId => SUBMESH_ID,
+            Self::SmoothingGroupId => SMOOTHING_GROUP_ID,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use ash::vk;
+
+// DISPLAY_MODE specialization constant values, must match mesh.frag
+const SHADED: u32 = 0;
+const WIREFRAME: u32 = 1;
+const POINTS: u32 = 2;
+const SHADED_WIREFRAME: u32 = 3;
+
+/**
+ * How material pipelines rasterize meshes.
+ * Wireframe and Points use a LINE/POINT polygon mode when fillModeNonSolid is enabled,
+ * otherwise the triangles are filled and mesh.frag keeps only the edges/corners (barycentrics).
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayMode {
+    #[default]
+    Shaded,
+    Wireframe,
+    Points,
+    ShadedWireframe, // always barycentric, lines would z-fight with the filled triangles
+}
+
+impl DisplayMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Shaded => Self::Wireframe,
+            Self::Wireframe => Self::Points,
+            Self::Points => Self::ShadedWireframe,
+            Self::ShadedWireframe => Self::Shaded,
+        }
+    }
+
+    pub fn polygon_mode(self, fill_mode_non_solid: bool) -> vk::PolygonMode {
+        match self {
+            Self::Wireframe if fill_mode_non_solid => vk::PolygonMode::LINE,
+            Self::Points if fill_mode_non_solid => vk::PolygonMode::POINT,
+            _ => vk::PolygonMode::FILL,
+        }
+    }
+
+    // fragment shader variant, see polygon_mode
+    pub fn shader_mode(self, fill_mode_non_solid: bool) -> u32 {
+        match self {
+            Self::Shaded => SHADED,
+            Self::Wireframe | Self::Points if fill_mode_non_solid => SHADED,
+            Self::Wireframe => WIREFRAME,
+            Self::Points => POINTS,
+            Self::ShadedWireframe => SHADED_WIREFRAME,
+        }
+    }
+}
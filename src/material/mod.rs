@@ -1,148 +1,235 @@
+mod debug_shading;
+mod display_mode;
 mod material_params;
 
-use ash::vk::{self, DescriptorSetLayout, Framebuffer};
-use glam::Vec3;
+use ash::vk;
+
+pub use debug_shading::DebugShading;
+pub use display_mode::DisplayMode;
 
 use crate::{
     ft_vk::{
-        self,
-        allocated_buffer::AllocatedBuffer,
-        allocated_image::{self, AllocatedImage},
-        descriptor_allocator::DescriptorAllocator,
-        descriptor_set_layout::DescriptorSetLayoutCreateInfoBuilder,
-        Engine, GraphicsPipelineInfoBuilder, PipelineLayout, ShaderModule,
+        self, allocated_buffer::AllocatedBuffer, descriptor_write::DescriptorWriter,
+        reflection::ShaderReflection, Engine, EngineError, GraphicsPipelineInfoBuilder,
+        PipelineLayout, ShaderModule,
     },
-    helpers::{buffer::load_buffer, default_viewports_and_scissors},
+    helpers::buffer::load_buffer,
     material_asset::MaterialAsset,
-    obj_asset::{self, MaterialLib, ObjAsset},
 };
 
+const VERTEX_SHADER: &str = "./shaders/mesh_dba.vert.spv";
+const FRAGMENT_SHADER: &str = "./shaders/mesh.frag.spv";
+
+// Pipeline variants selected at runtime, changing them reloads the material pipelines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShadingModes {
+    pub display: DisplayMode,
+    pub debug: DebugShading,
+}
+
 pub struct Material<TPipeline = NoPipeline> {
+    pub name: String,
     pub descriptor_set: vk::DescriptorSet,
     params: AllocatedBuffer,
+    transparent: bool, // drawn with the blended pipeline variant
     pub pipeline: TPipeline,
 }
 
-impl Material<NoPipeline> {
-    pub fn new(
-        engine: &mut Engine,
-        asset: &MaterialAsset,
-        descriptor_set_layout: vk::DescriptorSetLayout,
-    ) -> Self {
-        let device = &engine.device;
-
-        let params = {
-            let params: material_params::Params = asset.into();
-            let allocator = engine.allocator.as_mut().unwrap();
-            let command_pool = engine.frames[0].command_pool;
-            let command_buffer = engine.frames[0].command_buffer;
-            let graphics_queue = engine.graphics_queue;
-
-            load_buffer(
-                device,
-                allocator,
-                command_pool,
-                command_buffer,
-                engine.graphics_queue,
-                &params,
-            )
-        };
-
-        let descriptor_set = {
-            let descriptor_allocator = &mut engine.descriptor_allocator;
-            descriptor_allocator.allocate_descriptor_set(&engine.device, descriptor_set_layout)
-        };
+impl<TPipeline> Material<TPipeline> {
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
 
-        // fn update_descriptor_set
-        unsafe {
-            device.update_descriptor_sets(
-                &[
-                    // params
-                    vk::WriteDescriptorSet::default()
-                        .dst_set(descriptor_set)
-                        .dst_binding(0)
-                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-                        .buffer_info(&[vk::DescriptorBufferInfo::default()
-                            .buffer(params.buffer)
-                            .range(vk::WHOLE_SIZE)]),
-                ],
-                &[],
-            )
-        };
+    // Mesh pipeline of this material for modes, the shader modules only live during the call
+    fn create_pipeline<TPushConstants>(
+        &self,
+        engine: &Engine,
+        layout: &PipelineLayout<TPushConstants>,
+        modes: ShadingModes,
+    ) -> Result<vk::Pipeline, EngineError> {
+        let device = &engine.device;
 
-        Self {
-            params,
-            descriptor_set,
-            pipeline: NoPipeline,
-        }
-    }
+        // DISPLAY_MODE and DEBUG_SHADING specialization constants of mesh.frag
+        let specialization_entries = [
+            vk::SpecializationMapEntry::default()
+                .constant_id(0)
+                .offset(0)
+                .size(std::mem::size_of::<u32>()),
+            vk::SpecializationMapEntry::default()
+                .constant_id(1)
+                .offset(std::mem::size_of::<u32>() as u32)
+                .size(std::mem::size_of::<u32>()),
+        ];
+        let specialization_data: Vec<u8> = [
+            modes.display.shader_mode(engine.fill_mode_non_solid),
+            modes.debug.shader_mode(),
+        ]
+        .iter()
+        .flat_map(|value| value.to_ne_bytes())
+        .collect();
+        let specialization_info = vk::SpecializationInfo::default()
+            .map_entries(&specialization_entries)
+            .data(&specialization_data);
 
-    pub fn load_pipeline<'a, TPushConstants>(
-        self,
-        device: &ash::Device,
-        render_pass: vk::RenderPass,
-        extent: vk::Extent2D,
-        layout: &'a PipelineLayout<TPushConstants>,
-    ) -> Material<Pipeline> {
         let main_entry = std::ffi::CString::new("main").unwrap();
-        let vert_module = ShaderModule::create_from_file(device, "./shaders/mesh_dba.vert.spv");
+        let vert_module = ShaderModule::create_from_file(device, VERTEX_SHADER)?;
         let vert_stage = vk::PipelineShaderStageCreateInfo::default()
             .stage(vk::ShaderStageFlags::VERTEX)
             .module(vert_module)
             .name(main_entry.as_c_str());
-        let frag_module = ShaderModule::create_from_file(device, "./shaders/mesh.frag.spv");
+        let frag_module = match ShaderModule::create_from_file(device, FRAGMENT_SHADER) {
+            Ok(frag_module) => frag_module,
+            Err(err) => {
+                unsafe { device.destroy_shader_module(vert_module, None) };
+                return Err(err);
+            }
+        };
         let frag_stage = vk::PipelineShaderStageCreateInfo::default()
             .stage(vk::ShaderStageFlags::FRAGMENT)
             .module(frag_module)
-            .name(main_entry.as_c_str());
+            .name(main_entry.as_c_str())
+            .specialization_info(&specialization_info);
         let stages = [vert_stage, frag_stage];
 
-        let (viewports, scissors) = default_viewports_and_scissors(extent);
-        let viewport_state = vk::PipelineViewportStateCreateInfo::default()
-            .viewports(&viewports)
-            .scissors(&scissors);
-
         let mut default_pipeline_info = GraphicsPipelineInfoBuilder::new();
-        let pipeline_info = default_pipeline_info
+        default_pipeline_info
             .set_obj_compatible()
             .set_depth_stencil()
-            //.enable_blending_additive()
+            .set_samples(engine.msaa_samples)
+            .set_rendering_formats(engine.swapchain.surface_format.format, ft_vk::DEPTH_FORMAT)
+            .set_polygon_mode(modes.display.polygon_mode(engine.fill_mode_non_solid))
+            .enable_dynamic_viewport()
+            .enable_dynamic_cull_mode();
+        if self.transparent {
+            default_pipeline_info
+                .disable_depth_write()
+                .enable_blending_alphablend();
+        }
+        let pipeline_info = default_pipeline_info
             .build()
             .stages(&stages)
-            .viewport_state(&viewport_state)
             .layout(layout.as_vk())
-            .render_pass(render_pass);
+            .render_pass(engine.render_pass);
 
         let pipelines = unsafe {
-            device
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
-                .unwrap()
+            device.create_graphics_pipelines(engine.pipeline_cache.as_vk(), &[pipeline_info], None)
         };
 
         unsafe { device.destroy_shader_module(frag_module, None) };
         unsafe { device.destroy_shader_module(vert_module, None) };
 
-        Material {
+        match pipelines {
+            Ok(pipelines) => Ok(pipelines[0]),
+            Err((_, error)) => Err(error.into()),
+        }
+    }
+}
+
+impl Material<NoPipeline> {
+    pub fn new(
+        engine: &mut Engine,
+        asset: &MaterialAsset,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> Result<Self, EngineError> {
+        let params = {
+            let params: material_params::Params = asset.into();
+            load_buffer(engine, &params)?
+        };
+        let device = &engine.device;
+
+        let descriptor_set = {
+            let descriptor_allocator = &mut engine.descriptor_allocator;
+            descriptor_allocator.allocate_descriptor_set(&engine.device, descriptor_set_layout)?
+        };
+
+        DescriptorWriter::new()
+            // params
+            .write_buffer(
+                0,
+                params.buffer,
+                vk::WHOLE_SIZE,
+                0,
+                vk::DescriptorType::STORAGE_BUFFER,
+            )?
+            .update_set(device, descriptor_set);
+
+        Ok(Self {
+            name: asset.material_name.clone(),
+            params,
+            descriptor_set,
+            transparent: asset.dissolve < 1.0 || asset.dissolve_map.is_some(),
+            pipeline: NoPipeline,
+        })
+    }
+
+    pub fn load_pipeline<'a, TPushConstants>(
+        self,
+        engine: &Engine,
+        layout: &'a PipelineLayout<TPushConstants>,
+        modes: ShadingModes,
+    ) -> Result<Material<Pipeline>, EngineError> {
+        let pipeline = self.create_pipeline(engine, layout, modes)?;
+
+        Ok(Material {
+            name: self.name,
             descriptor_set: self.descriptor_set,
             params: self.params,
-            pipeline: Pipeline(pipelines[0]),
-        }
+            transparent: self.transparent,
+            pipeline: Pipeline(pipeline),
+        })
     }
 
     pub fn destroy(mut self, allocator: &vk_mem::Allocator) {
         unsafe { allocator.destroy_buffer(self.params.buffer, &mut self.params.allocation) };
     }
+
+    // params buffer and descriptor set are released once the frames using them are complete
+    pub fn destroy_later(self, engine: &mut Engine) {
+        engine.destroy_later(self.params);
+        engine.destroy_later(ft_vk::deletion_queue::Resource::DescriptorSet(
+            self.descriptor_set,
+        ));
+    }
 }
 
 impl Material<Pipeline> {
+    // Swap in a pipeline for modes, the old one is destroyed once the frames in flight complete
+    pub fn reload_pipeline<TPushConstants>(
+        &mut self,
+        engine: &mut Engine,
+        layout: &PipelineLayout<TPushConstants>,
+        modes: ShadingModes,
+    ) -> Result<(), EngineError> {
+        let pipeline = Pipeline(self.create_pipeline(engine, layout, modes)?);
+        let previous = std::mem::replace(&mut self.pipeline, pipeline);
+        engine.destroy_later(ft_vk::deletion_queue::Resource::Pipeline(previous.0));
+
+        Ok(())
+    }
+
     pub fn unload_pipeline(self, device: &ash::Device) -> Material<NoPipeline> {
         unsafe {
             device.destroy_pipeline(self.pipeline.0, None);
         }
 
         Material {
+            name: self.name,
             descriptor_set: self.descriptor_set,
             params: self.params,
+            transparent: self.transparent,
+            pipeline: NoPipeline,
+        }
+    }
+
+    // same as unload_pipeline without waiting for the frames in flight
+    pub fn unload_pipeline_later(self, engine: &mut Engine) -> Material<NoPipeline> {
+        engine.destroy_later(ft_vk::deletion_queue::Resource::Pipeline(self.pipeline.0));
+
+        Material {
+            name: self.name,
+            descriptor_set: self.descriptor_set,
+            params: self.params,
+            transparent: self.transparent,
             pipeline: NoPipeline,
         }
     }
@@ -157,17 +244,87 @@ impl Pipeline {
     }
 }
 
-pub fn descriptor_set_layout(device: &ash::Device) -> vk::DescriptorSetLayout {
-    let bindings = vec![
-        // params
-        vk::DescriptorSetLayoutBinding::default()
-            .binding(0)
-            .descriptor_count(1)
-            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT),
-    ];
+// Mesh shaders interface, the set layouts and push constant range of the mesh pipelines come from it
+pub fn reflect_shaders() -> Result<[ShaderReflection; 2], EngineError> {
+    Ok([
+        ShaderReflection::from_file(VERTEX_SHADER)?,
+        ShaderReflection::from_file(FRAGMENT_SHADER)?,
+    ])
+}
+
+// Check the rust types shared with the mesh shaders against their SPIR-V layouts
+pub fn validate_shaders() -> anyhow::Result<()> {
+    use crate::{
+        ft_vk::reflection::{check_layout, rust_layout, StructLayout},
+        light,
+        mesh_constants::MeshConstantsOwned,
+        vertex::Vertex,
+    };
+
+    let [vert, frag] = reflect_shaders()?;
+
+    let find_struct = |name: &str| -> anyhow::Result<&StructLayout> {
+        vert.structs
+            .get(name)
+            .or_else(|| frag.structs.get(name))
+            .ok_or_else(|| anyhow::anyhow!("struct {} not found in the mesh shaders", name))
+    };
+    let push_constants = vert
+        .push_constants
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("{} has no push constants", VERTEX_SHADER))?;
 
-    let info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+    let mut mismatches = vec![];
+    mismatches.extend(check_layout(
+        push_constants,
+        &rust_layout!(MeshConstantsOwned {
+            model,
+            normal_matrix,
+            vertex_buffer
+        }),
+    ));
+    mismatches.extend(check_layout(
+        find_struct("MaterialParams")?,
+        &rust_layout!(material_params::Params {
+            ambient,
+            shininess_exponent,
+            diffuse,
+            optical_density,
+            specular,
+            dissolve,
+            emission,
+            illumination
+        }),
+    ));
+    mismatches.extend(check_layout(
+        find_struct("Vertex")?,
+        &rust_layout!(Vertex {
+            position,
+            uv_x,
+            color,
+            uv_y,
+            normal,
+            smoothing_group
+        }),
+    ));
+    mismatches.extend(check_layout(
+        find_struct("Light")?,
+        &rust_layout!(light::Params {
+            position,
+            kind,
+            direction,
+            range,
+            color,
+            intensity,
+            inner_cutoff,
+            outer_cutoff,
+            _pad
+        }),
+    ));
+
+    if !mismatches.is_empty() {
+        anyhow::bail!("shader layout mismatch:\n{}", mismatches.join("\n"));
+    }
 
-    unsafe { device.create_descriptor_set_layout(&info, None).unwrap() }
+    Ok(())
 }
@@ -25,6 +25,17 @@ pub struct MaterialAsset {
     pub bump_map: Option<String>,            // bump
 }
 
+// Fallback of submeshes without a material (e.g. an OBJ without .mtl), unlit white
+impl Default for MaterialAsset {
+    fn default() -> Self {
+        obj_asset::Material {
+            ambient: Vec3::ONE,
+            ..obj_asset::Material::new("default")
+        }
+        .into()
+    }
+}
+
 impl From<obj_asset::Material> for MaterialAsset {
     fn from(value: obj_asset::Material) -> Self {
         let obj_asset::Material {
@@ -8,13 +8,13 @@ use glam::Vec3;
 use vk_mem::Alloc;
 
 use crate::ft_vk::allocated_buffer::{self, AllocatedBuffer};
-use crate::helpers::buffer::{copy_buffer, create_buffer, load_staging_buffer};
+use crate::helpers::buffer::create_buffer;
 use crate::helpers::{arr_to_bytes, print_bytes_in_hex};
 use crate::mesh_asset::MeshAsset;
 use crate::obj_asset::obj_raw::face::Face;
 use crate::obj_asset::{self, ObjAsset, ObjRaw};
 use crate::{
-    ft_vk::Engine,
+    ft_vk::{Engine, EngineError},
     helpers::struct_to_bytes,
     vertex::{self, Vertex},
 };
@@ -27,7 +27,11 @@ pub struct Mesh<'a, T> {
 }
 
 impl<'a, T> Mesh<'a, T> {
-    pub fn create_vertex_buffer(&mut self, device: &ash::Device, allocator: &vk_mem::Allocator) {
+    pub fn create_vertex_buffer(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_mem::Allocator,
+    ) -> Result<(), EngineError> {
         if self.vertex_buffer.is_some() {
             panic!("vertex buffer already allocated");
         }
@@ -35,7 +39,7 @@ impl<'a, T> Mesh<'a, T> {
         let (buffer, buffer_size, allocation) = create_buffer(
             allocator,
             (self.asset.vertices.len() * std::mem::size_of::<T>()) as vk::DeviceSize,
-        );
+        )?;
 
         // is driven by create_buffer allocation
         let device_address = {
@@ -51,9 +55,14 @@ impl<'a, T> Mesh<'a, T> {
         };
 
         self.vertex_buffer = Some(allocated_buffer);
+
+        Ok(())
     }
 
-    pub fn create_index_buffer(&mut self, allocator: &vk_mem::Allocator) {
+    pub fn create_index_buffer(
+        &mut self,
+        allocator: &vk_mem::Allocator,
+    ) -> Result<(), EngineError> {
         if self.index_buffer.is_some() {
             panic!("index buffer already allocated");
         }
@@ -70,11 +79,8 @@ impl<'a, T> Mesh<'a, T> {
                 ..vk_mem::AllocationCreateInfo::default()
             };
 
-            let (buffer, allocation) = unsafe {
-                allocator
-                    .create_buffer(&buffer_info, &allocation_info)
-                    .unwrap()
-            };
+            let (buffer, allocation) =
+                unsafe { allocator.create_buffer(&buffer_info, &allocation_info)? };
 
             (buffer, buffer_size, allocation)
         };
@@ -85,74 +91,32 @@ impl<'a, T> Mesh<'a, T> {
             buffer_size,
             allocation,
         });
+
+        Ok(())
     }
 
-    pub fn unload(&mut self, allocator: &vk_mem::Allocator) {
-        match &mut self.vertex_buffer.take() {
-            Some(allocated_buffer) => unsafe {
-                allocator.destroy_buffer(allocated_buffer.buffer, &mut allocated_buffer.allocation);
-            },
-            _ => {}
+    // buffers are destroyed once the frames drawing this mesh are complete
+    pub fn unload_later(&mut self, engine: &mut Engine) {
+        if let Some(vertex_buffer) = self.vertex_buffer.take() {
+            engine.destroy_later(vertex_buffer);
         }
-
-        match &mut self.index_buffer.take() {
-            Some(allocated_buffer) => unsafe {
-                allocator.destroy_buffer(allocated_buffer.buffer, &mut allocated_buffer.allocation);
-            },
-            _ => {}
+        if let Some(index_buffer) = self.index_buffer.take() {
+            engine.destroy_later(index_buffer);
         }
     }
 
-    pub fn load(
-        &mut self,
-        device: &ash::Device,
-        allocator: &mut vk_mem::Allocator,
-        graphics_queue: vk::Queue,
-        cmd: vk::CommandBuffer,
-        command_pool: vk::CommandPool,
-    ) {
-        // Vertex
+    // Buffers are created now, their content is uploaded by the next Engine::submit_uploads or draw_frame
+    pub fn load(&mut self, engine: &mut Engine) -> Result<(), EngineError> {
         {
-            let data = arr_to_bytes(&self.asset.vertices);
-            let mut staging_buffer = load_staging_buffer(allocator, data);
-
-            self.create_vertex_buffer(device, allocator);
-            let vertex_buffer = self.vertex_buffer.as_ref().unwrap();
-
-            copy_buffer(
-                device,
-                command_pool,
-                graphics_queue,
-                staging_buffer.buffer,
-                vertex_buffer.buffer,
-                vertex_buffer.buffer_size,
-            );
-
-            unsafe {
-                allocator.destroy_buffer(staging_buffer.buffer, &mut staging_buffer.allocation);
-            }
+            let allocator = engine.allocator.as_ref().unwrap();
+            self.create_vertex_buffer(&engine.device, allocator)?;
+            self.create_index_buffer(allocator)?;
         }
 
-        // Index
-        {
-            let data = arr_to_bytes(&self.asset.indices);
-            let mut staging_buffer = load_staging_buffer(allocator, data);
-
-            self.create_index_buffer(allocator);
-            let index_buffer = self.index_buffer.as_ref().unwrap();
-
-            copy_buffer(
-                device,
-                command_pool,
-                graphics_queue,
-                staging_buffer.buffer,
-                index_buffer.buffer,
-                index_buffer.buffer_size,
-            );
-
-            unsafe {
-                allocator.destroy_buffer(staging_buffer.buffer, &mut staging_buffer.allocation);
-            }
-        }
+        let vertex_buffer = self.vertex_buffer.as_ref().unwrap().buffer;
+        engine.upload_buffer(vertex_buffer, 0, arr_to_bytes(&self.asset.vertices))?;
+
+        let index_buffer = self.index_buffer.as_ref().unwrap().buffer;
+        engine.upload_buffer(index_buffer, 0, arr_to_bytes(&self.asset.indices))
     }
 }
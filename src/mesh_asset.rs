@@ -1,11 +1,32 @@
 use glam::Vec3;
 
-use crate::{obj_asset::ObjAsset, vertex::Vertex};
+use crate::{
+    obj_asset::{self, obj_raw::SmoothingGroup, ObjAsset},
+    vertex::Vertex,
+};
 
 #[derive(Debug)]
 pub struct MeshAsset<T> {
     pub vertices: Vec<T>,
     pub indices: Vec<u32>,
+    pub submeshes: Vec<Submesh>,
+}
+
+// Range of indices drawn with a single material
+#[derive(Debug, Clone)]
+pub struct Submesh {
+    pub material_name: Option<String>,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub bounds_min: Vec3,
+    pub bounds_max: Vec3,
+}
+
+impl Submesh {
+    // center of the bounding box, used to sort transparent draws
+    pub fn center(&self) -> Vec3 {
+        (self.bounds_min + self.bounds_max) * 0.5f32
+    }
 }
 
 impl MeshAsset<Vertex> {
@@ -18,7 +39,7 @@ impl MeshAsset<Vertex> {
                     color: glam::Vec3::new(0.0, 0.0, 0.0),
                     uv_y: 0f32,
                     normal: glam::Vec3::ZERO,
-                    _padding_hack: 0.0f32,
+                    smoothing_group: 0,
                 },
                 Vertex {
                     position: glam::Vec3::new(1.0, 0.0, 0.0),
@@ -26,7 +47,7 @@ impl MeshAsset<Vertex> {
                     color: glam::Vec3::new(1.0, 0.0, 0.0),
                     uv_y: 0f32,
                     normal: glam::Vec3::ZERO,
-                    _padding_hack: 0.0f32,
+                    smoothing_group: 0,
                 },
                 Vertex {
                     position: glam::Vec3::new(0.0, 1.0, 0.0),
@@ -34,7 +55,7 @@ impl MeshAsset<Vertex> {
                     color: glam::Vec3::new(0.0, 1.0, 0.0),
                     uv_y: 0f32,
                     normal: glam::Vec3::ZERO,
-                    _padding_hack: 0.0f32,
+                    smoothing_group: 0,
                 },
                 Vertex {
                     position: glam::Vec3::new(1.0, 1.0, 0.0),
@@ -42,37 +63,85 @@ impl MeshAsset<Vertex> {
                     color: glam::Vec3::new(1.0, 1.0, 0.0),
                     uv_y: 0f32,
                     normal: glam::Vec3::ZERO,
-                    _padding_hack: 0.0f32,
+                    smoothing_group: 0,
                 },
             ],
             indices: vec![0, 1, 2, 2, 1, 3],
+            submeshes: vec![Submesh {
+                material_name: None,
+                first_index: 0,
+                index_count: 6,
+                bounds_min: Vec3::ZERO,
+                bounds_max: Vec3::new(1.0, 1.0, 0.0),
+            }],
         }
     }
 
     pub fn from_obj(obj: &ObjAsset) -> Self {
         let mut vertices: Vec<Vertex> = vec![];
         let mut indices: Vec<u32> = vec![];
+        let mut submeshes: Vec<Submesh> = vec![];
+
+        // group faces by material, in order of first usemtl
+        let mut groups: Vec<(&Option<String>, Vec<(&[obj_asset::Vertex; 3], u32)>)> = vec![];
+        for ((face, material_name), smoothing_group) in obj
+            .faces()
+            .iter()
+            .zip(obj.material_names())
+            .zip(obj.smoothing_groups())
+        {
+            let smoothing_group = match smoothing_group {
+                SmoothingGroup::On(group) => *group,
+                SmoothingGroup::Off => 0,
+            };
+            match groups.iter_mut().find(|(name, _)| *name == material_name) {
+                Some((_, faces)) => faces.push((face, smoothing_group)),
+                None => groups.push((material_name, vec![(face, smoothing_group)])),
+            }
+        }
 
         let mut indice: u32 = 0;
-        for face in obj.faces() {
-            for vertex in face {
-                indices.push(indice);
-
-                vertices.push(Vertex {
-                    position: vertex.position.truncate(),
-                    normal: vertex.normal.unwrap_or(Vec3::ZERO),
-                    uv_x: vertex.texture.unwrap_or_default().x,
-                    uv_y: vertex.texture.unwrap_or_default().y,
-                    color: Vec3::ZERO,
-                    ..Default::default()
-                });
-
-                indice += 1;
+        for (material_name, faces) in groups {
+            let first_index = indices.len() as u32;
+            let mut bounds_min = Vec3::splat(f32::MAX);
+            let mut bounds_max = Vec3::splat(f32::MIN);
+
+            for (face, smoothing_group) in faces {
+                for vertex in face {
+                    indices.push(indice);
+
+                    let position = vertex.position.truncate();
+                    bounds_min = bounds_min.min(position);
+                    bounds_max = bounds_max.max(position);
+
+                    vertices.push(Vertex {
+                        position,
+                        normal: vertex.normal.unwrap_or(Vec3::ZERO),
+                        uv_x: vertex.texture.unwrap_or_default().x,
+                        uv_y: vertex.texture.unwrap_or_default().y,
+                        color: Vec3::ZERO,
+                        smoothing_group,
+                    });
+
+                    indice += 1;
+                }
+                indices.push(u32::MAX); // triangle_strip but actually obj is triangle_list ready
             }
-            indices.push(u32::MAX); // triangle_strip but actually obj is triangle_list ready
+
+            submeshes.push(Submesh {
+                material_name: material_name.clone(),
+                first_index,
+                index_count: indices.len() as u32 - first_index,
+                bounds_min,
+                bounds_max,
+            });
         }
 
-        MeshAsset { vertices, indices }
+        MeshAsset {
+            vertices,
+            indices,
+            submeshes,
+        }
     }
 }
 
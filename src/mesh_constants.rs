@@ -1,16 +1,24 @@
 use ash::vk;
+use glam::{Mat3, Mat4, Vec4};
 
+/**
+ * struct MeshConstants
+ * pushed per draw, view_projection lives in the scene buffer to stay under 128 bytes
+ * normal_matrix is a glsl mat3 (3 columns padded to vec4)
+ */
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct MeshConstants<'a> {
-    pub render_matrix: glam::Mat4,
+    pub model: glam::Mat4,
+    pub normal_matrix: [Vec4; 3],
     pub vertex_buffer: &'a vk::DeviceAddress,
 }
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct MeshConstantsOwned {
-    pub render_matrix: glam::Mat4,
+    pub model: glam::Mat4,
+    pub normal_matrix: [Vec4; 3],
     pub vertex_buffer: vk::DeviceAddress,
 }
 
@@ -19,8 +27,20 @@ impl<'a> crate::traits::IntoOwned for MeshConstants<'a> {
 
     fn into_owned(&self) -> Self::Owned {
         MeshConstantsOwned {
-            render_matrix: self.render_matrix,
+            model: self.model,
+            normal_matrix: self.normal_matrix,
             vertex_buffer: *self.vertex_buffer,
         }
     }
 }
+
+// inverse transpose of the model upper 3x3, keeps normals orthogonal under non uniform scale
+pub fn normal_matrix(model: Mat4) -> [Vec4; 3] {
+    let normal_matrix = Mat3::from_mat4(model).inverse().transpose();
+
+    [
+        normal_matrix.x_axis.extend(0.0f32),
+        normal_matrix.y_axis.extend(0.0f32),
+        normal_matrix.z_axis.extend(0.0f32),
+    ]
+}
@@ -32,6 +32,7 @@ impl Material {
     pub fn new(material_name: &str) -> Self {
         Material {
             material_name: material_name.to_string(),
+            dissolve: 1.0, // opaque when d is omitted
             ..Default::default()
         }
     }
@@ -15,11 +15,21 @@ use obj_raw::{
 };
 use utils::calculate_tri_normal;
 
-pub struct ObjAsset(Vec<[Vertex; 3]>);
+pub struct ObjAsset(Vec<[Vertex; 3]>, Vec<Option<String>>, Vec<SmoothingGroup>);
 impl ObjAsset {
     pub fn faces(&self) -> &Vec<[Vertex; 3]> {
         &self.0
     }
+
+    // usemtl of each face (same order as faces)
+    pub fn material_names(&self) -> &Vec<Option<String>> {
+        &self.1
+    }
+
+    // smoothing group of each face (same order as faces)
+    pub fn smoothing_groups(&self) -> &Vec<SmoothingGroup> {
+        &self.2
+    }
 }
 
 pub struct ObjAssetBuilder<'a> {
@@ -94,7 +104,15 @@ impl<'a> ObjAssetBuilder<'a> {
                     .collect::<Vec<[Vertex; 3]>>()
             })
             .collect();
-        ObjAsset(tris)
+        let material_names: Vec<Option<String>> = face_tris
+            .iter()
+            .flat_map(|(face, tris)| tris.iter().map(|_| face.material_name.clone()))
+            .collect();
+        let smoothing_groups: Vec<SmoothingGroup> = face_tris
+            .iter()
+            .flat_map(|(face, tris)| tris.iter().map(|_| face.smoothing_group))
+            .collect();
+        ObjAsset(tris, material_names, smoothing_groups)
     }
 
     fn vertex(&self, vertex_attribute: &VertexAttribute) -> Vertex {
@@ -0,0 +1,249 @@
+use ash::vk;
+use glam::Mat4;
+
+use crate::{
+    debug_draw::{DebugDraw, DebugVertex},
+    ft_vk::{
+        allocated_buffer::AllocatedBuffer, deletion_queue::Resource, Engine, EngineError,
+        GraphicsPipelineInfoBuilder, PipelineLayout, Renderer, ShaderModule, DEPTH_FORMAT,
+    },
+    helpers::{arr_to_bytes, buffer::create_buffer, struct_to_bytes},
+};
+
+const VERTEX_SHADER: &str = "./shaders/debug_line.vert.spv";
+const FRAGMENT_SHADER: &str = "./shaders/debug_line.frag.spv";
+
+/**
+ * struct DebugConstants
+ * pushed once per frame, vertices are read through the buffer device address
+ */
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DebugConstants {
+    pub view_projection: Mat4,
+    pub vertex_buffer: vk::DeviceAddress,
+}
+
+/**
+ * Draws the DebugDraw lines as a single line list, depth tested but not written.
+ * One vertex buffer per frame in flight, lines above the capacity are dropped.
+ */
+pub struct DebugRenderer {
+    pipeline_layout: PipelineLayout<DebugConstants>,
+    pipeline: vk::Pipeline,
+    buffers: Vec<AllocatedBuffer>,
+    capacity: usize, // vertices per buffer
+}
+
+impl DebugRenderer {
+    pub fn new(engine: &Engine, capacity: usize) -> Result<Self, EngineError> {
+        let push_constant_ranges = [vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(std::mem::size_of::<DebugConstants>() as u32)];
+        let pipeline_layout = PipelineLayout::<DebugConstants> {
+            layout: {
+                let info = vk::PipelineLayoutCreateInfo::default()
+                    .push_constant_ranges(&push_constant_ranges);
+                unsafe { engine.device.create_pipeline_layout(&info, None)? }
+            },
+            _marker: std::marker::PhantomData,
+        };
+
+        let allocator = engine.allocator.as_ref().unwrap();
+        let buffers = (0..engine.frames.len())
+            .map(|_| {
+                let (buffer, buffer_size, allocation) = create_buffer(
+                    allocator,
+                    (capacity * std::mem::size_of::<DebugVertex>()) as vk::DeviceSize,
+                )?;
+                let device_address = {
+                    let info = vk::BufferDeviceAddressInfo::default().buffer(buffer);
+                    unsafe { engine.device.get_buffer_device_address(&info) }
+                };
+
+                Ok(AllocatedBuffer {
+                    buffer,
+                    device_address: Some(device_address),
+                    buffer_size,
+                    allocation,
+                })
+            })
+            .collect::<Result<_, EngineError>>()?;
+
+        let pipeline = create_pipeline(engine, &pipeline_layout)?;
+
+        Ok(Self {
+            pipeline_layout,
+            pipeline,
+            buffers,
+            capacity,
+        })
+    }
+
+    // after a render pass change (e.g. Engine::set_msaa_samples)
+    pub fn reload_pipeline(&mut self, engine: &mut Engine) -> Result<(), EngineError> {
+        let pipeline = create_pipeline(engine, &self.pipeline_layout)?;
+        engine.destroy_later(Resource::Pipeline(std::mem::replace(
+            &mut self.pipeline,
+            pipeline,
+        )));
+
+        Ok(())
+    }
+
+    // Renderer recording draw's lines for the current frame
+    pub fn frame<'a>(&'a self, draw: &'a DebugDraw, view_projection: Mat4) -> DebugPass<'a> {
+        DebugPass {
+            renderer: self,
+            vertices: &draw.vertices,
+            view_projection,
+        }
+    }
+
+    // handles are left dangling, the renderer must be dropped or rebuilt
+    pub fn destroy(&mut self, engine: &mut Engine) {
+        for buffer in self.buffers.drain(..) {
+            engine.destroy_later(buffer);
+        }
+        engine.destroy_later(Resource::Pipeline(self.pipeline));
+        engine.destroy_later(Resource::PipelineLayout(self.pipeline_layout.as_vk()));
+    }
+}
+
+pub struct DebugPass<'a> {
+    renderer: &'a DebugRenderer,
+    vertices: &'a [DebugVertex],
+    view_projection: Mat4,
+}
+
+impl<'a> Renderer for DebugPass<'a> {
+    unsafe fn render(&self, engine: &Engine, cmd: vk::CommandBuffer) -> Result<(), EngineError> {
+        // whole lines only
+        let count = self.vertices.len().min(self.renderer.capacity) & !1;
+        if count == 0 {
+            return Ok(());
+        }
+
+        // frame buffer is free, its fence was waited by draw_frame
+        let allocator = engine.allocator.as_ref().unwrap();
+        let buffer = &self.renderer.buffers[engine.frame_index()];
+        let data_ptr = allocator
+            .get_allocation_info(&buffer.allocation)
+            .mapped_data as *mut u8;
+        if data_ptr.is_null() {
+            return Err(EngineError::Vulkan(vk::Result::ERROR_MEMORY_MAP_FAILED));
+        }
+        let vertices = arr_to_bytes(&self.vertices[..count]);
+        std::ptr::copy_nonoverlapping(vertices.as_ptr(), data_ptr, vertices.len());
+        allocator.flush_allocation(&buffer.allocation, 0, vertices.len() as vk::DeviceSize)?;
+
+        let scope = engine.begin_profile_scope(cmd, "debug lines");
+        let constants = DebugConstants {
+            view_projection: self.view_projection,
+            vertex_buffer: buffer.device_address.unwrap(),
+        };
+        engine.device.cmd_bind_pipeline(
+            cmd,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.renderer.pipeline,
+        );
+        engine.device.cmd_push_constants(
+            cmd,
+            self.renderer.pipeline_layout.as_vk(),
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            struct_to_bytes(&constants),
+        );
+        engine.device.cmd_draw(cmd, count as u32, 1, 0, 0);
+        engine.end_profile_scope(cmd, scope);
+
+        Ok(())
+    }
+}
+
+fn create_pipeline(
+    engine: &Engine,
+    layout: &PipelineLayout<DebugConstants>,
+) -> Result<vk::Pipeline, EngineError> {
+    let device = &engine.device;
+
+    let main_entry = std::ffi::CString::new("main").unwrap();
+    let vert_module = ShaderModule::create_from_file(device, VERTEX_SHADER)?;
+    let frag_module = match ShaderModule::create_from_file(device, FRAGMENT_SHADER) {
+        Ok(frag_module) => frag_module,
+        Err(err) => {
+            unsafe { device.destroy_shader_module(vert_module, None) };
+            return Err(err);
+        }
+    };
+    let stages = [
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vert_module)
+            .name(main_entry.as_c_str()),
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(frag_module)
+            .name(main_entry.as_c_str()),
+    ];
+
+    let mut pipeline_info = GraphicsPipelineInfoBuilder::new();
+    pipeline_info
+        .set_topology(vk::PrimitiveTopology::LINE_LIST)
+        .set_depth_stencil()
+        .disable_depth_write()
+        .set_samples(engine.msaa_samples)
+        .set_rendering_formats(engine.swapchain.surface_format.format, DEPTH_FORMAT)
+        .enable_dynamic_viewport();
+    let pipeline_info = pipeline_info
+        .build()
+        .stages(&stages)
+        .layout(layout.as_vk())
+        .render_pass(engine.render_pass);
+
+    let pipelines = unsafe {
+        device.create_graphics_pipelines(engine.pipeline_cache.as_vk(), &[pipeline_info], None)
+    };
+
+    unsafe { device.destroy_shader_module(frag_module, None) };
+    unsafe { device.destroy_shader_module(vert_module, None) };
+
+    match pipelines {
+        Ok(pipelines) => Ok(pipelines[0]),
+        Err((_, error)) => Err(error.into()),
+    }
+}
+
+// debug_line.vert must agree with DebugConstants and DebugVertex
+pub fn validate_shaders() -> anyhow::Result<()> {
+    use crate::ft_vk::reflection::{check_layout, rust_layout, ShaderReflection};
+
+    let vert = ShaderReflection::from_file(VERTEX_SHADER)?;
+    let push_constants = vert
+        .push_constants
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("{} has no push constants", VERTEX_SHADER))?;
+    let vertex = vert
+        .structs
+        .get("DebugVertex")
+        .ok_or_else(|| anyhow::anyhow!("struct DebugVertex not found in {}", VERTEX_SHADER))?;
+
+    let mut mismatches = vec![];
+    mismatches.extend(check_layout(
+        push_constants,
+        &rust_layout!(DebugConstants {
+            view_projection,
+            vertex_buffer
+        }),
+    ));
+    mismatches.extend(check_layout(
+        vertex,
+        &rust_layout!(DebugVertex { position, color }),
+    ));
+
+    if !mismatches.is_empty() {
+        anyhow::bail!("shader layout mismatch:\n{}", mismatches.join("\n"));
+    }
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
-mod mesh_renderer;
+mod debug_renderer;
+mod render_queue;
 // mod tri_renderer;
 
-pub use mesh_renderer::MeshRenderer;
+pub use debug_renderer::{validate_shaders as validate_debug_shaders, DebugRenderer};
+pub use render_queue::RenderQueue;
 // pub use tri_renderer::TriRenderer;
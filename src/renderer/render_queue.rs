@@ -0,0 +1,211 @@
+use ash::vk;
+use glam::Mat4;
+
+use crate::{
+    ft_vk::{Engine, EngineError, PipelineLayout, Renderer},
+    light::{self, SceneCamera, SceneLights},
+    material::{Material, Pipeline},
+    mesh::Mesh,
+    mesh_asset::Submesh,
+    vertex::Vertex,
+};
+
+// One indexed draw, push constants are stored as bytes so items with different layouts share the queue
+pub struct DrawItem<'a> {
+    pub mesh: &'a Mesh<'a, Vertex>,
+    pub submesh: &'a Submesh,
+    pub material: &'a Material<Pipeline>,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub push_constants: Vec<u8>,
+    pub submesh_index: u32, // drawn as first_instance, gl_InstanceIndex in the shaders (debug shading)
+    pub transparent_distance: Option<f32>, // squared distance to the camera, None when opaque
+}
+
+/**
+ * Draw items of a frame, recorded inside the render pass owned by Engine::draw_frame.
+ * Opaque items are sorted by pipeline then material to minimise binds,
+ * transparent ones are drawn afterwards back-to-front.
+ */
+pub struct RenderQueue<'a> {
+    pub items: Vec<DrawItem<'a>>,
+    pub camera: SceneCamera,
+    pub scene: &'a SceneLights,
+    pub lights: &'a [light::Params],
+    pub cull_mode: vk::CullModeFlags, // dynamic state of the material pipelines, BACK by default
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new(
+        scene: &'a SceneLights,
+        lights: &'a [light::Params],
+        camera: SceneCamera,
+    ) -> Self {
+        Self {
+            items: vec![],
+            camera,
+            scene,
+            lights,
+            cull_mode: vk::CullModeFlags::BACK,
+        }
+    }
+
+    pub fn push<TPushConstants: crate::traits::IntoOwned>(
+        &mut self,
+        mesh: &'a Mesh<'a, Vertex>,
+        submesh: &'a Submesh,
+        material: &'a Material<Pipeline>,
+        model: Mat4, // only used to sort transparent items
+        pipeline_layout: &PipelineLayout<TPushConstants>,
+        push_constants: &TPushConstants,
+    ) {
+        let tmp = push_constants.into_owned();
+        let transparent_distance = material.is_transparent().then(|| {
+            model
+                .transform_point3(submesh.center())
+                .distance_squared(self.camera.position)
+        });
+
+        let submesh_index = mesh
+            .asset
+            .submeshes
+            .iter()
+            .position(|other| std::ptr::eq(other, submesh))
+            .unwrap_or_default() as u32;
+
+        self.items.push(DrawItem {
+            mesh,
+            submesh,
+            submesh_index,
+            material,
+            pipeline_layout: pipeline_layout.as_vk(),
+            push_constants: crate::helpers::struct_to_bytes(&tmp).to_vec(),
+            transparent_distance,
+        });
+    }
+
+    /**
+     * Every submesh of the mesh, material is matched by name with a fallback to the first one,
+     * or to default_material when materials is empty.
+     */
+    pub fn push_mesh<TPushConstants: crate::traits::IntoOwned>(
+        &mut self,
+        mesh: &'a Mesh<'a, Vertex>,
+        materials: &'a [Material<Pipeline>],
+        default_material: &'a Material<Pipeline>,
+        model: Mat4,
+        pipeline_layout: &PipelineLayout<TPushConstants>,
+        push_constants: &TPushConstants,
+    ) {
+        for submesh in &mesh.asset.submeshes {
+            let material = submesh
+                .material_name
+                .as_ref()
+                .and_then(|name| materials.iter().find(|material| &material.name == name))
+                .or(materials.first())
+                .unwrap_or(default_material);
+            self.push(mesh, submesh, material, model, pipeline_layout, push_constants);
+        }
+    }
+
+    fn sorted_items(&self) -> Vec<&DrawItem<'a>> {
+        let (mut transparents, mut opaques): (Vec<_>, Vec<_>) = self
+            .items
+            .iter()
+            .partition(|item| item.transparent_distance.is_some());
+
+        opaques.sort_by_key(|item| (item.material.pipeline.as_vk(), item.material.descriptor_set));
+        transparents.sort_by(|a, b| {
+            b.transparent_distance
+                .unwrap()
+                .total_cmp(&a.transparent_distance.unwrap())
+        });
+
+        opaques.extend(transparents);
+        opaques
+    }
+}
+
+impl<'a> Renderer for RenderQueue<'a> {
+    unsafe fn render(&self, engine: &Engine, cmd: vk::CommandBuffer) -> Result<(), EngineError> {
+        // scene
+        let frame_index = engine.frame_index();
+        self.scene.write(
+            engine.allocator.as_ref().unwrap(),
+            frame_index,
+            &self.camera,
+            self.lights,
+        )?;
+
+        let scope = engine.begin_profile_scope(cmd, "meshes");
+        let mut bound_pipeline = vk::Pipeline::null();
+        let mut bound_layout = vk::PipelineLayout::null();
+        let mut bound_material = vk::DescriptorSet::null();
+        let mut bound_index_buffer = vk::Buffer::null();
+
+        for item in self.sorted_items() {
+            let pipeline = item.material.pipeline.as_vk();
+            if pipeline != bound_pipeline {
+                engine
+                    .device
+                    .cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                engine.device.cmd_set_cull_mode(cmd, self.cull_mode);
+                bound_pipeline = pipeline;
+            }
+
+            if item.pipeline_layout != bound_layout {
+                engine.device.cmd_bind_descriptor_sets(
+                    cmd,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    item.pipeline_layout,
+                    1,
+                    &[self.scene.descriptor_sets[frame_index]],
+                    &[],
+                );
+                bound_layout = item.pipeline_layout;
+                bound_material = vk::DescriptorSet::null();
+            }
+
+            if item.material.descriptor_set != bound_material {
+                engine.device.cmd_bind_descriptor_sets(
+                    cmd,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    item.pipeline_layout,
+                    0,
+                    &[item.material.descriptor_set],
+                    &[],
+                );
+                bound_material = item.material.descriptor_set;
+            }
+
+            let index_buffer = item.mesh.index_buffer.as_ref().unwrap().buffer;
+            if index_buffer != bound_index_buffer {
+                engine
+                    .device
+                    .cmd_bind_index_buffer(cmd, index_buffer, 0, vk::IndexType::UINT32);
+                bound_index_buffer = index_buffer;
+            }
+
+            if !item.push_constants.is_empty() {
+                engine.device.cmd_push_constants(
+                    cmd,
+                    item.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    &item.push_constants,
+                );
+            }
+
+            engine.device.cmd_draw_indexed(
+                cmd,
+                item.submesh.index_count,
+                1,
+                item.submesh.first_index,
+                0,
+                item.submesh_index,
+            );
+        }
+        engine.end_profile_scope(cmd, scope);
+
+        Ok(())
+    }
+}
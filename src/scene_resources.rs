@@ -0,0 +1,148 @@
+use ash::vk;
+
+use crate::{
+    conf,
+    ft_vk::{reflection, Engine, EngineError, PipelineLayout},
+    light::SceneLights,
+    material::{self, Material, Pipeline, ShadingModes},
+    material_asset::MaterialAsset,
+    mesh::Mesh,
+    mesh_asset::MeshAsset,
+    mesh_constants::MeshConstants,
+    renderer::DebugRenderer,
+    vertex::Vertex,
+};
+
+/**
+ * GPU resources of the scene, created from the assets kept by main.
+ * Rebuilt with new when the device is lost (see Engine::recreate).
+ */
+pub struct SceneResources<'a> {
+    pub meshes: Vec<Mesh<'a, Vertex>>, // referenced by MeshRenderable.mesh
+    pub materials: Vec<Material<Pipeline>>, // referenced by MeshRenderable.materials
+    default_material: Option<Material<Pipeline>>, // see default_material, None once destroyed
+    pub pipeline_layout: PipelineLayout<MeshConstants<'a>>,
+    pub scene_lights: SceneLights,
+    pub debug_renderer: DebugRenderer,
+    material_set_layout: vk::DescriptorSetLayout,
+    scene_set_layout: vk::DescriptorSetLayout,
+}
+
+impl<'a> SceneResources<'a> {
+    // Buffers are uploaded by the next Engine::submit_uploads or draw_frame
+    pub fn new(
+        engine: &mut Engine,
+        mesh_asset: &'a MeshAsset<Vertex>,
+        material_assets: &[MaterialAsset],
+        shading_modes: ShadingModes,
+    ) -> Result<Self, EngineError> {
+        let mut meshes = vec![Mesh {
+            asset: mesh_asset,
+            index_buffer: None,
+            vertex_buffer: None,
+        }];
+        for mesh in &mut meshes {
+            mesh.load(engine)?;
+        }
+
+        // set 0 is the material params, set 1 the camera + lights
+        let reflections = material::reflect_shaders()?;
+        let material_set_layout =
+            reflection::create_descriptor_set_layout(&engine.device, &reflections, 0)?;
+        let scene_set_layout =
+            reflection::create_descriptor_set_layout(&engine.device, &reflections, 1)?;
+        // pipeline_layout, mesh constants (model / normal_matrix / mesh_buffer_address)
+        let push_constant_ranges = reflection::push_constant_ranges(&reflections);
+        let pipeline_layout = PipelineLayout::<MeshConstants> {
+            layout: {
+                let set_layouts = [material_set_layout, scene_set_layout];
+                let info = vk::PipelineLayoutCreateInfo::default()
+                    .push_constant_ranges(&push_constant_ranges)
+                    .set_layouts(&set_layouts);
+                unsafe { engine.device.create_pipeline_layout(&info, None)? }
+            },
+            _marker: std::marker::PhantomData,
+        };
+
+        let mut materials = Vec::with_capacity(material_assets.len());
+        for material_asset in material_assets {
+            materials.push(
+                Material::new(engine, material_asset, material_set_layout)?.load_pipeline(
+                    engine,
+                    &pipeline_layout,
+                    shading_modes,
+                )?,
+            );
+        }
+
+        let default_material = Material::new(
+            engine,
+            &MaterialAsset::default(),
+            material_set_layout,
+        )?
+        .load_pipeline(engine, &pipeline_layout, shading_modes)?;
+
+        let scene_lights = SceneLights::new(engine, scene_set_layout, conf::MAX_LIGHTS)?;
+        let debug_renderer = DebugRenderer::new(engine, conf::MAX_DEBUG_VERTICES)?;
+
+        Ok(Self {
+            meshes,
+            materials,
+            default_material: Some(default_material),
+            pipeline_layout,
+            scene_lights,
+            debug_renderer,
+            material_set_layout,
+            scene_set_layout,
+        })
+    }
+
+    // Recreate every material pipeline, the old ones are destroyed once the frames in flight complete.
+    // Materials keep their previous pipeline when a reload fails.
+    pub fn reload_pipelines(
+        &mut self,
+        engine: &mut Engine,
+        shading_modes: ShadingModes,
+    ) -> Result<(), EngineError> {
+        for material in self
+            .materials
+            .iter_mut()
+            .chain(self.default_material.as_mut())
+        {
+            material.reload_pipeline(engine, &self.pipeline_layout, shading_modes)?;
+        }
+
+        Ok(())
+    }
+
+    // Drawn for submeshes without a material, e.g. an OBJ without .mtl
+    pub fn default_material(&self) -> &Material<Pipeline> {
+        self.default_material.as_ref().unwrap()
+    }
+
+    // Waits for the device, handles are left dangling: drop or replace self afterwards
+    pub fn destroy(&mut self, engine: &mut Engine) {
+        for material in self.materials.drain(..).chain(self.default_material.take()) {
+            material.unload_pipeline_later(engine).destroy_later(engine);
+        }
+        self.debug_renderer.destroy(engine);
+        for mesh in &mut self.meshes {
+            mesh.unload_later(engine);
+        }
+        unsafe { engine.flush_deletion_queue() };
+
+        self.scene_lights
+            .destroy(engine.allocator.as_ref().unwrap());
+        unsafe {
+            engine
+                .device
+                .destroy_descriptor_set_layout(self.scene_set_layout, None);
+            engine
+                .device
+                .destroy_descriptor_set_layout(self.material_set_layout, None);
+            engine
+                .device
+                .destroy_pipeline_layout(self.pipeline_layout.as_vk(), None);
+        }
+    }
+}
@@ -8,5 +8,5 @@ pub struct Vertex {
     pub color: glam::Vec3,
     pub uv_y: f32,
     pub normal: glam::Vec3,
-    pub _padding_hack: f32,
+    pub smoothing_group: u32, // obj `s` group, 0 when off (debug shading)
 }
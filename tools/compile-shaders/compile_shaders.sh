@@ -7,4 +7,7 @@ glslc -g -O0 -o ./colored_tri.vert.spv ./colored_tri.vert
 glslc -g -O0 -o ./mesh.vert.spv ./mesh.vert
 glslc -g -O0 -o ./mesh.frag.spv ./mesh.frag
 
-glslc -g -O0 -o ./mesh_dba.vert.spv ./mesh_dba.vert
\ No newline at end of file
+glslc -g -O0 -o ./mesh_dba.vert.spv ./mesh_dba.vert
+
+glslc -g -O0 -o ./debug_line.vert.spv ./debug_line.vert
+glslc -g -O0 -o ./debug_line.frag.spv ./debug_line.frag
\ No newline at end of file